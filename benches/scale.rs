@@ -0,0 +1,134 @@
+//! 4K frame → hash throughput: full-resolution RGB24 (previous) vs downscaled GRAY8 (current).
+//!
+//! The GRAY8 path runs the pipeline's own helpers (`src/video/scale.rs`). As in the pipeline,
+//! each scaler is built once, outside the measured iterations.
+//!
+//! Run with `cargo bench --bench scale`.
+
+use std::time::{Duration, Instant};
+
+use ffmpeg::{frame, software::scaling, util::format::pixel::Pixel};
+use ffmpeg_next as ffmpeg;
+use img_hash::image::{DynamicImage, ImageBuffer, Rgb};
+use img_hash::{HashAlg, Hasher, HasherConfig};
+
+#[path = "../src/video/scale.rs"]
+mod scale;
+
+const SRC_W: u32 = 3840;
+const SRC_H: u32 = 2160;
+const HASH_SIZE: u32 = 16;
+const ITERATIONS: u32 = 100;
+
+/// Synthetic YUV420P frame with a gradient + checker pattern so the hash isn't trivial.
+fn synthetic_frame() -> frame::Video {
+    let mut f = frame::Video::new(Pixel::YUV420P, SRC_W, SRC_H);
+    for plane in 0..3 {
+        let stride = f.stride(plane);
+        let (pw, ph) = match plane {
+            0 => (SRC_W as usize, SRC_H as usize),
+            _ => (SRC_W as usize / 2, SRC_H as usize / 2),
+        };
+        let data = f.data_mut(plane);
+        for y in 0..ph {
+            for x in 0..pw {
+                let checker = if ((x / 64) + (y / 64)) % 2 == 0 {
+                    40
+                } else {
+                    0
+                };
+                data[y * stride + x] = ((x * 255 / pw) as u8).saturating_add(checker);
+            }
+        }
+    }
+    f
+}
+
+/// Previous path: BILINEAR to RGB24 at source size, copy, hash.
+fn hash_rgb_full(
+    src: &frame::Video,
+    scaler: &mut scaling::Context,
+    hasher: &Hasher,
+) -> Result<(), ffmpeg::Error> {
+    let mut rgb = frame::Video::empty();
+    scaler.run(src, &mut rgb)?;
+
+    let (w, h) = (rgb.width() as usize, rgb.height() as usize);
+    let stride = rgb.stride(0);
+    let mut owned = vec![0u8; w * h * 3];
+    for y in 0..h {
+        owned[y * w * 3..(y + 1) * w * 3]
+            .copy_from_slice(&rgb.data(0)[y * stride..y * stride + w * 3]);
+    }
+    let buf: ImageBuffer<Rgb<u8>, _> = ImageBuffer::from_raw(w as u32, h as u32, owned).unwrap();
+    hasher.hash_image(&DynamicImage::ImageRgb8(buf));
+    Ok(())
+}
+
+/// Current path: FAST_BILINEAR to GRAY8 at 8x hash size, copy, hash.
+fn hash_gray_small(
+    src: &frame::Video,
+    scaler: &mut scaling::Context,
+    hasher: &Hasher,
+) -> Result<(), ffmpeg::Error> {
+    let mut gray = frame::Video::empty();
+    scaler.run(src, &mut gray)?;
+    let img = scale::gray_to_image(&gray).expect("GRAY8 buffer");
+    hasher.hash_image(&img);
+    Ok(())
+}
+
+fn measure(name: &str, mut f: impl FnMut() -> Result<(), ffmpeg::Error>) -> Duration {
+    // Warm-up
+    f().expect("scale + hash");
+
+    let start = Instant::now();
+    for _ in 0..ITERATIONS {
+        f().expect("scale + hash");
+    }
+    let elapsed = start.elapsed();
+    println!(
+        "{name:<28} {:>8.2} ms/frame {:>8.1} frames/s",
+        elapsed.as_secs_f64() * 1000.0 / ITERATIONS as f64,
+        ITERATIONS as f64 / elapsed.as_secs_f64()
+    );
+    elapsed
+}
+
+fn main() {
+    ffmpeg::init().expect("ffmpeg init");
+    let hasher = HasherConfig::new()
+        .hash_size(HASH_SIZE, HASH_SIZE)
+        .hash_alg(HashAlg::DoubleGradient)
+        .to_hasher();
+    let src = synthetic_frame();
+
+    let mut rgb_scaler = scaling::Context::get(
+        src.format(),
+        SRC_W,
+        SRC_H,
+        Pixel::RGB24,
+        SRC_W,
+        SRC_H,
+        scaling::Flags::BILINEAR,
+    )
+    .expect("rgb24 scaler");
+    let dst = scale::scaled_dims(SRC_W, SRC_H, HASH_SIZE, HASH_SIZE);
+    let mut gray_scaler =
+        scale::gray_scaler(src.format(), (SRC_W, SRC_H), dst).expect("gray8 scaler");
+
+    println!(
+        "{SRC_W}x{SRC_H} YUV420P -> {}x{} GRAY8, {ITERATIONS} iterations",
+        dst.0, dst.1
+    );
+    let before = measure("rgb24 full-res (before)", || {
+        hash_rgb_full(&src, &mut rgb_scaler, &hasher)
+    });
+    let after = measure("gray8 downscaled (after)", || {
+        hash_gray_small(&src, &mut gray_scaler, &hasher)
+    });
+    println!(
+        "speed-up: {:.1}x",
+        before.as_secs_f64() / after.as_secs_f64()
+    );
+}
@@ -0,0 +1,429 @@
+//! File actions with an undo journal. Files are never deleted: they are moved into a trash
+//! directory (mirroring their absolute path under `<trash>/<batch>/`), and every move is
+//! appended to a JSON Lines journal so a batch can be restored, also in a later session.
+
+use std::{
+    fs,
+    io::{self, BufRead, BufReader, Write},
+    path::{self, Component, Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{errors::ActionError, hashing};
+
+/// One journal line.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "op", rename_all = "lowercase")]
+enum Record {
+    Move {
+        batch: u64,
+        from: PathBuf,
+        to: PathBuf,
+        blake3: String,
+        /// Seconds since the Unix epoch.
+        at: u64,
+    },
+    /// One file of `batch` moved back, written as it's restored.
+    Restore {
+        batch: u64,
+        from: PathBuf,
+        at: u64,
+    },
+    Undo {
+        batch: u64,
+        at: u64,
+    },
+}
+
+/// A file to move to the trash, with the BLAKE3 it had when scanned.
+pub struct TrashFile<'a> {
+    pub path: &'a Path,
+    pub blake3: &'a str,
+}
+
+pub struct Journal {
+    path: PathBuf,
+    trash: PathBuf,
+}
+
+impl Journal {
+    pub fn new(path: &Path, trash: &Path) -> io::Result<Self> {
+        Ok(Journal {
+            path: path::absolute(path)?,
+            trash: path::absolute(trash)?,
+        })
+    }
+
+    /// Move `files` to the trash as one batch; returns the batch number.
+    /// Every file is checked against its BLAKE3 first, and nothing is moved if one changed.
+    /// A move that fails part way returns `ActionError::Partial` with the files already moved.
+    pub fn trash(&self, files: &[TrashFile]) -> Result<u64, ActionError> {
+        let mut moves = Vec::with_capacity(files.len());
+        let batch = self.records()?.iter().map(Record::batch).max().unwrap_or(0) + 1;
+        let dir = self.trash.join(batch.to_string());
+        for f in files {
+            let from = path::absolute(f.path)?;
+            match hashing::compute_blake3(&from) {
+                Ok(b3) if b3 == f.blake3 => {}
+                _ => return Err(ActionError::Changed(from)),
+            }
+            let to = mirror(&dir, &from);
+            if to.exists() {
+                return Err(ActionError::Exists(to));
+            }
+            moves.push((from, to, f.blake3));
+        }
+
+        // Journal each move as it's made, so an interrupted batch can still be undone
+        let partial = |moved, error: ActionError| ActionError::Partial {
+            batch,
+            moved,
+            error: Box::new(error),
+        };
+        let mut moved = Vec::with_capacity(moves.len());
+        for (from, to, blake3) in moves {
+            if let Err(e) = move_file(&from, &to) {
+                return Err(partial(moved, e.into()));
+            }
+            moved.push(from.clone());
+            let record = Record::Move {
+                batch,
+                from,
+                to,
+                blake3: blake3.to_string(),
+                at: now(),
+            };
+            if let Err(e) = self.append(&record) {
+                return Err(partial(moved, e));
+            }
+        }
+        Ok(batch)
+    }
+
+    /// Restore the most recent batch not yet undone; returns the restored paths.
+    /// A batch left half restored by a failed undo is finished by the next one.
+    pub fn undo_last(&self) -> Result<Vec<PathBuf>, ActionError> {
+        let records = self.records()?;
+        let undone: Vec<u64> = records
+            .iter()
+            .filter_map(|r| match r {
+                Record::Undo { batch, .. } => Some(*batch),
+                _ => None,
+            })
+            .collect();
+        let Some(batch) = records
+            .iter()
+            .rev()
+            .map(Record::batch)
+            .find(|b| !undone.contains(b))
+        else {
+            return Err(ActionError::NothingToUndo);
+        };
+        let restored: Vec<&Path> = records
+            .iter()
+            .filter_map(|r| match r {
+                Record::Restore { batch: b, from, .. } if *b == batch => Some(from.as_path()),
+                _ => None,
+            })
+            .collect();
+        let moves: Vec<(&Path, &Path)> = records
+            .iter()
+            .filter_map(|r| match r {
+                Record::Move {
+                    batch: b, from, to, ..
+                } if *b == batch && !restored.contains(&from.as_path()) => {
+                    Some((from.as_path(), to.as_path()))
+                }
+                _ => None,
+            })
+            .collect();
+
+        // Check every destination first, so a batch is restored whole or not at all
+        for (from, to) in &moves {
+            if from.exists() {
+                return Err(ActionError::Exists(from.to_path_buf()));
+            }
+            if !to.exists() {
+                return Err(ActionError::Changed(to.to_path_buf()));
+            }
+        }
+
+        // Journal each restore as it's made, so an interrupted undo can be finished
+        let partial = |restored, error: ActionError| ActionError::PartialUndo {
+            batch,
+            restored,
+            error: Box::new(error),
+        };
+        let mut done = Vec::with_capacity(moves.len());
+        for (from, to) in moves.into_iter().rev() {
+            if let Err(e) = move_file(to, from) {
+                return Err(partial(done, e.into()));
+            }
+            done.push(from.to_path_buf());
+            let record = Record::Restore {
+                batch,
+                from: from.to_path_buf(),
+                at: now(),
+            };
+            if let Err(e) = self.append(&record) {
+                return Err(partial(done, e));
+            }
+        }
+        self.append(&Record::Undo { batch, at: now() })?;
+        Ok(done)
+    }
+
+    fn records(&self) -> Result<Vec<Record>, ActionError> {
+        let file = match fs::File::open(&self.path) {
+            Ok(f) => f,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(e.into()),
+        };
+        let mut records = Vec::new();
+        for line in BufReader::new(file).lines() {
+            let line = line?;
+            if !line.trim().is_empty() {
+                records.push(serde_json::from_str(&line)?);
+            }
+        }
+        Ok(records)
+    }
+
+    fn append(&self, record: &Record) -> Result<(), ActionError> {
+        let mut f = fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)?;
+        let mut line = serde_json::to_vec(record)?;
+        line.push(b'\n');
+        f.write_all(&line)?;
+        f.sync_data()?;
+        Ok(())
+    }
+}
+
+impl Record {
+    fn batch(&self) -> u64 {
+        match self {
+            Record::Move { batch, .. }
+            | Record::Restore { batch, .. }
+            | Record::Undo { batch, .. } => *batch,
+        }
+    }
+}
+
+/// `dir` + the absolute path's components (drive prefix and root dropped).
+fn mirror(dir: &Path, file: &Path) -> PathBuf {
+    let mut out = dir.to_path_buf();
+    for c in file.components() {
+        if let Component::Normal(part) = c {
+            out.push(part);
+        }
+    }
+    out
+}
+
+/// Rename, or copy and remove when `to` is on another file system.
+/// A failed copy or remove deletes the copy, so the file is never left in both places.
+fn move_file(from: &Path, to: &Path) -> io::Result<()> {
+    if let Some(parent) = to.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    match fs::rename(from, to) {
+        Err(e) if e.kind() == io::ErrorKind::CrossesDevices => {}
+        result => return result,
+    }
+    let copied = fs::copy(from, to).and_then(|_| fs::remove_file(from));
+    if copied.is_err() {
+        let _ = fs::remove_file(to);
+    }
+    copied
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Scratch directory under the system temp dir, removed on drop.
+    struct Scratch(PathBuf);
+
+    impl Scratch {
+        fn new(name: &str) -> Self {
+            let dir = std::env::temp_dir()
+                .join(format!("rust-phash-actions-{name}-{}", std::process::id()));
+            let _ = fs::remove_dir_all(&dir);
+            fs::create_dir_all(&dir).unwrap();
+            Scratch(dir)
+        }
+
+        /// Write `contents` to `name`; returns the path and its BLAKE3.
+        fn file(&self, name: &str, contents: &str) -> (PathBuf, String) {
+            let path = self.0.join(name);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(&path, contents).unwrap();
+            let blake3 = hashing::compute_blake3(&path).unwrap();
+            (path, blake3)
+        }
+
+        fn journal(&self) -> Journal {
+            Journal::new(&self.0.join("journal.jsonl"), &self.0.join("trash")).unwrap()
+        }
+    }
+
+    impl Drop for Scratch {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    fn trash_one(journal: &Journal, (path, blake3): &(PathBuf, String)) -> u64 {
+        journal.trash(&[TrashFile { path, blake3 }]).expect("trash")
+    }
+
+    #[test]
+    fn changed_file_aborts_before_any_move() {
+        let dir = Scratch::new("changed");
+        let (a, a_b3) = dir.file("a.jpg", "a");
+        let (b, _) = dir.file("b.jpg", "b");
+        let journal = dir.journal();
+        let files = [
+            TrashFile {
+                path: &a,
+                blake3: &a_b3,
+            },
+            TrashFile {
+                path: &b,
+                blake3: &"00".repeat(32),
+            },
+        ];
+        assert!(matches!(journal.trash(&files), Err(ActionError::Changed(p)) if p == b));
+        assert!(a.exists() && b.exists());
+        assert!(!dir.0.join("trash").exists());
+        assert!(journal.records().unwrap().is_empty());
+    }
+
+    #[test]
+    fn existing_destination_is_not_overwritten() {
+        let dir = Scratch::new("exists");
+        let a = dir.file("a.jpg", "a");
+        let journal = dir.journal();
+        let taken = mirror(&dir.0.join("trash").join("1"), &a.0);
+        fs::create_dir_all(taken.parent().unwrap()).unwrap();
+        fs::write(&taken, "other").unwrap();
+
+        let files = [TrashFile {
+            path: &a.0,
+            blake3: &a.1,
+        }];
+        assert!(matches!(journal.trash(&files), Err(ActionError::Exists(p)) if p == taken));
+        assert!(a.0.exists());
+        assert_eq!(fs::read_to_string(&taken).unwrap(), "other");
+    }
+
+    #[test]
+    fn undo_restores_the_latest_batch_not_undone_after_a_reload() {
+        let dir = Scratch::new("undo");
+        let a = dir.file("a.jpg", "a");
+        let b = dir.file("b.jpg", "b");
+        let c = dir.file("c.jpg", "c");
+        {
+            let journal = dir.journal();
+            assert_eq!(trash_one(&journal, &a), 1);
+            assert_eq!(trash_one(&journal, &b), 2);
+            assert_eq!(journal.undo_last().unwrap(), vec![b.0.clone()]);
+            assert_eq!(trash_one(&journal, &c), 3);
+        }
+        assert!(!a.0.exists() && b.0.exists() && !c.0.exists());
+
+        // A new session reads the same journal
+        let journal = dir.journal();
+        assert_eq!(journal.undo_last().unwrap(), vec![c.0.clone()]);
+        assert_eq!(journal.undo_last().unwrap(), vec![a.0.clone()]);
+        assert!(a.0.exists() && b.0.exists() && c.0.exists());
+        assert!(matches!(
+            journal.undo_last(),
+            Err(ActionError::NothingToUndo)
+        ));
+    }
+
+    #[test]
+    fn undoing_twice_has_nothing_to_undo() {
+        let dir = Scratch::new("twice");
+        let a = dir.file("a.jpg", "a");
+        let journal = dir.journal();
+        trash_one(&journal, &a);
+        journal.undo_last().unwrap();
+        assert!(matches!(
+            journal.undo_last(),
+            Err(ActionError::NothingToUndo)
+        ));
+        assert!(a.0.exists());
+    }
+
+    #[test]
+    fn interrupted_undo_is_finished_by_the_next() {
+        let dir = Scratch::new("partial");
+        let (a, a_b3) = dir.file("a.jpg", "a");
+        let (b, b_b3) = dir.file("sub/b.jpg", "b");
+        let journal = dir.journal();
+        let files = [
+            TrashFile {
+                path: &b,
+                blake3: &b_b3,
+            },
+            TrashFile {
+                path: &a,
+                blake3: &a_b3,
+            },
+        ];
+        journal.trash(&files).unwrap();
+
+        // `b` can't go back while a file stands where its directory was; `a` is restored first
+        let sub = dir.0.join("sub");
+        fs::remove_dir(&sub).unwrap();
+        fs::write(&sub, "").unwrap();
+        match journal.undo_last() {
+            Err(ActionError::PartialUndo {
+                batch, restored, ..
+            }) => {
+                assert_eq!(batch, 1);
+                assert_eq!(restored, vec![a.clone()]);
+            }
+            other => panic!("expected a partial undo, got {other:?}"),
+        }
+
+        fs::remove_file(&sub).unwrap();
+        assert_eq!(journal.undo_last().unwrap(), vec![b.clone()]);
+        assert!(a.exists() && b.exists());
+        assert!(matches!(
+            journal.undo_last(),
+            Err(ActionError::NothingToUndo)
+        ));
+    }
+
+    #[test]
+    fn mirror_drops_the_root() {
+        let dir = Path::new("/trash/3");
+        assert_eq!(
+            mirror(dir, Path::new("/home/me/a.jpg")),
+            Path::new("/trash/3/home/me/a.jpg")
+        );
+    }
+
+    #[cfg(windows)]
+    #[test]
+    fn mirror_drops_the_drive_prefix() {
+        assert_eq!(
+            mirror(Path::new(r"D:\trash\3"), Path::new(r"C:\Users\me\a.jpg")),
+            Path::new(r"D:\trash\3\Users\me\a.jpg")
+        );
+    }
+}
@@ -0,0 +1,183 @@
+//! Animated images (GIF, APNG, WebP): sampled, hashed and aggregated like short videos.
+
+use std::{fs::File, io::BufReader, path::Path};
+
+use image_webp::WebPDecoder;
+use img_hash::image::codecs::{gif::GifDecoder, png::PngDecoder};
+use img_hash::image::{
+    AnimationDecoder, DynamicImage, GenericImageView, RgbImage, RgbaImage, imageops::FilterType,
+};
+use img_hash::{Hasher, ImageHash};
+
+use crate::{color, errors::HashError, preprocess::Preprocess, types as T, video::aggregate};
+
+/// Frames are shrunk while decoding to this multiple of the hash size (long side),
+/// matching video frames; only sampled frames are kept.
+const DOWNSCALE_FACTOR: u32 = 8;
+
+/// Aggregate hash plus the ordered per-frame hashes.
+pub struct AnimatedHash {
+    pub hash: ImageHash,
+    pub frames: Vec<T::FrameHash>,
+}
+
+/// One `AnimatedHash` per hasher, plus the colour signature of the sampled frames (`--color`).
+pub struct Animated {
+    pub hashes: Vec<AnimatedHash>,
+    pub color: Option<String>,
+}
+
+/// Decoded frames as (delay in ms, frame), ending at the last frame or the first bad one.
+type FrameIter = Box<dyn Iterator<Item = (f64, DynamicImage)>>;
+
+/// Hash an animated GIF/APNG/WebP from `sample_count` evenly spaced frames, aggregated with
+/// `aggregation`; one `AnimatedHash` per hasher.
+/// `Ok(None)` if the file is not animated (single frame, still PNG, other formats), or if
+/// fewer than two frames decode: the still image path then hashes (or reports) it.
+pub fn hash_animated(
+    path: &Path,
+    cfg: &T::AppConfig,
+    hashers: &[Hasher],
+) -> Result<Option<Animated>, HashError> {
+    let Some(frames) = open_frames(path)? else {
+        return Ok(None);
+    };
+
+    // Decode every frame (later frames are composited onto earlier ones), keeping small copies.
+    // A truncated or corrupt animation keeps the frames before the bad one.
+    let target = cfg
+        .hash_w
+        .max(cfg.hash_h)
+        .saturating_mul(DOWNSCALE_FACTOR)
+        .max(1);
+    let mut decoded: Vec<(f64, DynamicImage)> = Vec::new();
+    let mut pts_ms = 0.0f64;
+    for (delay_ms, img) in frames {
+        let (w, h) = img.dimensions();
+        let img = match w.max(h) > target {
+            true => img.resize(target, target, FilterType::Triangle),
+            false => img,
+        };
+        decoded.push((pts_ms / 1000.0, img));
+        pts_ms += delay_ms;
+    }
+    if decoded.len() < 2 {
+        return Ok(None);
+    }
+
+    // Evenly spaced sample across all frames
+    let n = decoded.len();
+    let take = cfg.sample_count.clamp(1, n);
+    let picked: Vec<usize> = (0..take).map(|k| k * n / take).collect();
+    let (times, images): (Vec<f64>, Vec<DynamicImage>) = decoded
+        .into_iter()
+        .enumerate()
+        .filter(|(i, _)| picked.contains(i))
+        .map(|(_, f)| f)
+        .unzip();
+    let images = Preprocess::from_cfg(cfg).apply_all(images);
+
+    let color = match cfg.color {
+        true => color::signature(&images),
+        false => None,
+    };
+
+    let hashes = hashers
+        .iter()
+        .map(|hasher| {
+            let hashes: Vec<ImageHash> = images.iter().map(|img| hasher.hash_image(img)).collect();
+            let hash = match cfg.aggregation {
+                T::Aggregation::Medoid => aggregate::aggregate_medoid(&hashes),
+                T::Aggregation::Majority => aggregate::aggregate_majority_as_real(&hashes),
+            }
+            .expect("at least one sampled frame");
+            let frames = times
+                .iter()
+                .zip(&hashes)
+                .map(|(&pts_secs, h)| T::FrameHash {
+                    pts_secs,
+                    hash: h.to_base64(),
+                })
+                .collect();
+            AnimatedHash { hash, frames }
+        })
+        .collect();
+    Ok(Some(Animated { hashes, color }))
+}
+
+/// Frame iterator for animations of two or more frames; `None` for everything else.
+/// Frame counts come from the headers (GIF: the first two frame descriptors), so still
+/// images aren't decoded here and again by the still image path.
+fn open_frames(path: &Path) -> Result<Option<FrameIter>, HashError> {
+    let ext = path
+        .extension()
+        .and_then(|e| e.to_str())
+        .map(|e| e.to_ascii_lowercase());
+    let reader = || -> Result<BufReader<File>, HashError> { Ok(BufReader::new(File::open(path)?)) };
+    let image_frames = |frames: img_hash::image::Frames<'static>| -> FrameIter {
+        Box::new(frames.map_while(Result::ok).map(|frame| {
+            let (num, den) = frame.delay().numer_denom_ms();
+            let delay_ms = match den {
+                0 => 0.0,
+                _ => num as f64 / den as f64,
+            };
+            (delay_ms, DynamicImage::ImageRgba8(frame.into_buffer()))
+        }))
+    };
+
+    // Headers that don't parse are left to the still image path to report
+    match ext.as_deref() {
+        Some("gif") => {
+            let Ok(mut gif) = gif::DecodeOptions::new().read_info(reader()?) else {
+                return Ok(None);
+            };
+            let mut next = || matches!(gif.next_frame_info(), Ok(Some(_)));
+            if !(next() && next()) {
+                return Ok(None);
+            }
+            Ok(Some(image_frames(
+                GifDecoder::new(reader()?)?.into_frames(),
+            )))
+        }
+        Some("png") => {
+            let Ok((_, png)) = png::Decoder::new(reader()?).read_info() else {
+                return Ok(None);
+            };
+            let frames = png.info().animation_control().map_or(0, |a| a.num_frames);
+            let png = PngDecoder::new(reader()?)?;
+            match frames > 1 && png.is_apng() {
+                true => Ok(Some(image_frames(png.apng().into_frames()))),
+                false => Ok(None),
+            }
+        }
+        Some("webp") => {
+            let Ok(webp) = WebPDecoder::new(reader()?) else {
+                return Ok(None);
+            };
+            match webp.is_animated() && webp.num_frames() > 1 {
+                true => Ok(Some(Box::new(WebPFrames(webp)))),
+                false => Ok(None),
+            }
+        }
+        _ => Ok(None),
+    }
+}
+
+/// Animated WebP frames (composited by the decoder).
+struct WebPFrames(WebPDecoder<BufReader<File>>);
+
+impl Iterator for WebPFrames {
+    type Item = (f64, DynamicImage);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let (w, h) = self.0.dimensions();
+        let mut buf = vec![0; self.0.output_buffer_size()?];
+        // Ends after the last frame (`NoMoreFrames`) or at a bad one
+        let delay_ms = self.0.read_frame(&mut buf).ok()? as f64;
+        let img = match self.0.has_alpha() {
+            true => RgbaImage::from_raw(w, h, buf).map(DynamicImage::ImageRgba8),
+            false => RgbImage::from_raw(w, h, buf).map(DynamicImage::ImageRgb8),
+        }?;
+        Some((delay_ms, img))
+    }
+}
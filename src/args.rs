@@ -1,15 +1,76 @@
 use crate::types::{self as T};
-use clap::{ArgAction, Parser, ValueHint};
-use std::path::PathBuf;
+use clap::{
+    ArgAction, ArgMatches, CommandFactory, FromArgMatches, Parser, Subcommand, ValueHint,
+    parser::ValueSource,
+};
+use std::{ffi::OsStr, path::Path, path::PathBuf};
 
+/// `rust-phash <DIRECTORY> [THRESHOLD] [OPTIONS]` is short for `rust-phash scan ...`.
 #[derive(Debug, Parser)]
 #[command(
     name = "rust-phash",
     version,
     about = "Perceptual hashing to detect near-duplicate images.",
-    author = "Blkhurst"
+    author = "Blkhurst",
+    args_conflicts_with_subcommands = true,
+    subcommand_negates_reqs = true
 )]
-pub struct Args {
+pub struct Cli {
+    #[command(subcommand)]
+    pub command: Option<Command>,
+
+    #[command(flatten)]
+    pub scan: Option<ScanArgs>,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum Command {
+    /// Scan a directory for near-duplicates (the default command)
+    Scan(ScanArgs),
+
+    /// Print the hashes of files
+    Hash(HashArgs),
+
+    /// Regroup hashes saved with `scan --save-hashes`, without rescanning
+    Group(GroupArgs),
+
+    /// Explain the distance between two files
+    Diff(DiffArgs),
+
+    /// Find files near a file or hash, in saved hashes or the cache
+    Query(QueryArgs),
+
+    /// Inspect or clear the hash cache
+    Cache(CacheArgs),
+
+    /// Review duplicate groups interactively: mark keepers, then move the rest to a trash directory (undoable)
+    Review(ReviewArgs),
+}
+
+impl Cli {
+    /// Parse the command line, with the checks clap can't express.
+    pub fn parse_checked() -> Self {
+        let matches = Cli::command().get_matches();
+        let mut cli = Cli::from_arg_matches(&matches).unwrap_or_else(|e| e.exit());
+        // clap only fills an optional flattened struct when its group is matched, which the
+        // nested option groups prevent; without a subcommand, the top-level args are the scan
+        if cli.command.is_none() && cli.scan.is_none() {
+            cli.scan = Some(ScanArgs::from_arg_matches(&matches).unwrap_or_else(|e| e.exit()));
+        }
+        let scan = match (&cli.command, &cli.scan) {
+            (Some(Command::Scan(args)), _) => matches.subcommand_matches("scan").map(|m| (args, m)),
+            (None, Some(args)) => Some((args, &matches)),
+            _ => None,
+        };
+        if let Some((args, matches)) = scan {
+            args.check_media(matches);
+        }
+        cli
+    }
+}
+
+#[derive(Debug, clap::Args)]
+pub struct ScanArgs {
     /// Directory to recursively scan
     #[arg(value_hint = ValueHint::DirPath)]
     pub directory: PathBuf,
@@ -18,13 +79,132 @@ pub struct Args {
     #[arg(default_value_t = T::DEFAULT_THRESHOLD)]
     pub threshold: u32,
 
-    /// Print JSON output
-    #[arg(long = "json", action = ArgAction::SetTrue)]
-    pub json: bool,
+    /// Media types to scan, grouped separately (comma-separated)
+    #[arg(
+        long = "media",
+        value_enum,
+        value_delimiter = ',',
+        default_value = "images"
+    )]
+    pub media: Vec<T::MediaType>,
+
+    /// Process videos instead of images (same as --media videos)
+    #[arg(long = "video", action = ArgAction::SetTrue, conflicts_with = "media")]
+    pub video: bool,
+
+    /// Scan images and videos; report images matching a sampled video frame
+    #[arg(long = "match-frames", action = ArgAction::SetTrue, conflicts_with_all = ["video", "media"])]
+    pub match_frames: bool,
+
+    /// Include file size, mtime, dimensions, format/codec, duration and hash in JSON output
+    #[arg(long = "with-metadata", action = ArgAction::SetTrue)]
+    pub with_metadata: bool,
+
+    /// Save every file's hashes, to regroup later with `rust-phash group`
+    #[arg(long = "save-hashes", value_hint = ValueHint::FilePath)]
+    pub save_hashes: Option<PathBuf>,
+
+    #[command(flatten)]
+    pub hashing: HashOpts,
+
+    #[command(flatten)]
+    pub video_opts: VideoOpts,
 
-    /// Hashing Algorithm
-    #[arg(long = "hash-alg", value_enum, default_value_t = T::DEFAULT_HASH_ALG)]
-    pub hash_alg: T::HashAlg,
+    #[command(flatten)]
+    pub matching: MatchOpts,
+
+    #[command(flatten)]
+    pub output: OutputOpts,
+}
+
+/// Options that only apply to videos, by argument id.
+/// (`--sample-count` and `--aggregation` also apply to animated images.)
+const VIDEO_ONLY: &[&str] = &[
+    "sample_start",
+    "sample_window",
+    "sample_interval",
+    "decode_timeout",
+    "video_compare",
+    "min_similarity",
+    "max_duration_diff",
+    "max_aspect_diff",
+    "match_on",
+    "audio_threshold",
+];
+
+/// Options that only apply to images, by argument id.
+const IMAGE_ONLY: &[&str] = &[
+    "alpha",
+    "color",
+    "color_threshold",
+    "crop_resistant",
+    "min_tile_match",
+];
+
+impl ScanArgs {
+    /// Default settings for `directory`, as `rust-phash scan <DIRECTORY>` would use.
+    pub fn defaults(directory: &Path) -> Self {
+        let argv = ["rust-phash", "scan", "--"].map(OsStr::new);
+        match Cli::parse_from(argv.into_iter().chain([directory.as_os_str()])).command {
+            Some(Command::Scan(args)) => args,
+            _ => unreachable!("parsed as `scan`"),
+        }
+    }
+
+    /// Media types scanned: --match-frames needs both, --video is shorthand for videos only.
+    pub fn media_types(&self) -> Vec<T::MediaType> {
+        match (self.match_frames, self.video) {
+            (true, _) => vec![T::MediaType::Image, T::MediaType::Video],
+            (false, true) => vec![T::MediaType::Video],
+            (false, false) => self.media.clone(),
+        }
+    }
+
+    /// Reject options given for a media type that isn't scanned (they would be ignored).
+    fn check_media(&self, matches: &ArgMatches) {
+        let media = self.media_types();
+        for (ids, media_type, hint) in [
+            (VIDEO_ONLY, T::MediaType::Video, "--media videos"),
+            (IMAGE_ONLY, T::MediaType::Image, "--media images"),
+        ] {
+            if media.contains(&media_type) {
+                continue;
+            }
+            let given = ids
+                .iter()
+                .find(|id| matches.value_source(id) == Some(ValueSource::CommandLine));
+            if let Some(id) = given {
+                let kind = match media_type {
+                    T::MediaType::Image => "images",
+                    T::MediaType::Video => "videos",
+                };
+                Cli::command()
+                    .error(
+                        clap::error::ErrorKind::ArgumentConflict,
+                        format!(
+                            "--{} only applies to {kind}, which aren't scanned (add {hint})",
+                            id.replace('_', "-")
+                        ),
+                    )
+                    .exit();
+            }
+        }
+    }
+}
+
+/// Perceptual hash settings (part of the cache key) and how hashing runs.
+#[derive(Debug, Clone, clap::Args)]
+#[command(next_help_heading = "Hashing")]
+pub struct HashOpts {
+    /// Hashing Algorithm(s), comma-separated; several are fused (see --fusion)
+    #[arg(
+        long = "hash-alg",
+        value_enum,
+        value_delimiter = ',',
+        default_values_t = [T::DEFAULT_HASH_ALG]
+    )]
+    // Full path: the `default_values_t` expansion has its own generic `T`
+    pub hash_alg: Vec<crate::types::HashAlg>,
 
     /// Hash width (bits across).
     #[arg(long = "hash-w", default_value_t = T::DEFAULT_HASH_W)]
@@ -34,6 +214,14 @@ pub struct Args {
     #[arg(long = "hash-h", default_value_t = T::DEFAULT_HASH_H)]
     pub hash_h: u32,
 
+    /// Crop black or white bars (letterbox, pillarbox, frames) before hashing
+    #[arg(long = "autocrop", action = ArgAction::SetTrue)]
+    pub autocrop: bool,
+
+    /// Image ~ How transparent pixels are flattened before hashing
+    #[arg(long = "alpha", value_enum, default_value_t = T::DEFAULT_ALPHA)]
+    pub alpha: T::AlphaPolicy,
+
     /// Maximum parallelism (Rayon threads)
     #[arg(long = "parallel", default_value_t = T::DEFAULT_PARALLELISM)]
     pub parallel: usize,
@@ -42,27 +230,283 @@ pub struct Args {
     #[arg(long = "cache-file", default_value = T::DEFAULT_CACHE_FILE_NAME)]
     pub cache_file: PathBuf,
 
-    /// Process videos instead of images
-    #[arg(long = "video", action = ArgAction::SetTrue)]
-    pub video: bool,
+    /// Retry files that failed to hash in a previous run (otherwise skipped via the cache)
+    #[arg(long = "retry-failed", action = ArgAction::SetTrue)]
+    pub retry_failed: bool,
+}
+
+impl HashOpts {
+    /// Algorithms to hash with: first is primary, duplicates dropped.
+    pub fn hash_algs(&self) -> Vec<T::HashAlg> {
+        let mut algs: Vec<T::HashAlg> = Vec::new();
+        for alg in &self.hash_alg {
+            if !algs.contains(alg) {
+                algs.push(*alg);
+            }
+        }
+        algs
+    }
+}
 
-    /// Video ~ Frame to start sampling from.
+/// How videos are sampled and decoded.
+#[derive(Debug, Clone, clap::Args)]
+#[command(next_help_heading = "Video")]
+pub struct VideoOpts {
+    /// Frame to start sampling from.
     #[arg(long = "sample-start", default_value_t = T::DEFAULT_SAMPLE_START)]
     pub sample_start: usize,
 
-    /// Video ~ Number of frames samples; evenly-spaced between sample-start and sample-window
+    /// Number of frames samples; evenly-spaced between sample-start and sample-window
     #[arg(long = "sample-count", default_value_t = T::DEFAULT_SAMPLE_COUNT)]
     pub sample_count: usize,
 
-    /// Video ~ Number of frames to sample over; 0 = auto (whole video).
+    /// Number of frames to sample over; 0 = auto (whole video).
     #[arg(long = "sample-window", default_value_t = T::DEFAULT_SAMPLE_WINDOW)]
     pub sample_window: usize,
 
-    /// Video ~ Aggregation method
+    /// Seconds between samples, replacing sample-count; 0 = off (clips default: 1s).
+    #[arg(long = "sample-interval", default_value_t = T::DEFAULT_SAMPLE_INTERVAL)]
+    pub sample_interval: f64,
+
+    /// Aggregation method
     #[arg(long = "aggregation", value_enum, default_value_t = T::DEFAULT_AGGREGATION)]
     pub aggregation: T::Aggregation,
 
-    /// Output JSON to a file
+    /// Seconds before decoding a file is abandoned; 0 = no limit
+    #[arg(long = "decode-timeout", default_value_t = T::DEFAULT_DECODE_TIMEOUT)]
+    pub decode_timeout: f64,
+}
+
+/// When two files count as duplicates.
+#[derive(Debug, Clone, clap::Args)]
+#[command(next_help_heading = "Matching")]
+pub struct MatchOpts {
+    /// Multi-hash fusion: all algorithms within threshold, or weighted mean of distances
+    #[arg(long = "fusion", value_enum, default_value_t = T::DEFAULT_FUSION)]
+    pub fusion: T::Fusion,
+
+    /// Weighted fusion: one weight per --hash-alg, comma-separated (default: equal)
+    #[arg(long = "fusion-weights", value_delimiter = ',')]
+    pub fusion_weights: Vec<f64>,
+
+    /// Image ~ Colour signature: flag or exclude duplicates whose colours differ (grayscale, tints, recolours)
+    #[arg(long = "color", value_enum, default_value_t = T::DEFAULT_COLOR_MODE)]
+    pub color: T::ColorMode,
+
+    /// Image ~ Colour signature distance threshold (mean chroma difference, 0-255)
+    #[arg(long = "color-threshold", default_value_t = T::DEFAULT_COLOR_THRESHOLD)]
+    pub color_threshold: u32,
+
+    /// Image ~ Also match cropped copies by hashing a grid of overlapping tiles (slower)
+    #[arg(long = "crop-resistant", action = ArgAction::SetTrue)]
+    pub crop_resistant: bool,
+
+    /// Image ~ Crop-resistant: fraction of the cropped image's tiles that must match (0-1)
+    #[arg(long = "min-tile-match", default_value_t = T::DEFAULT_MIN_TILE_MATCH)]
+    pub min_tile_match: f64,
+
+    /// Video ~ Comparison method
+    #[arg(long = "video-compare", value_enum, default_value_t = T::DEFAULT_VIDEO_COMPARE)]
+    pub video_compare: T::VideoCompare,
+
+    /// Video ~ Sequence/Clips comparison: fraction of frames that must match (0-1)
+    #[arg(long = "min-similarity", default_value_t = T::DEFAULT_MIN_SIMILARITY)]
+    pub min_similarity: f64,
+
+    /// Video ~ Only compare videos whose durations differ by at most this: seconds or percent (e.g. 5%)
+    #[arg(long = "max-duration-diff", value_name = "DIFF")]
+    pub max_duration_diff: Option<T::Tolerance>,
+
+    /// Video ~ Only compare videos whose aspect ratios differ by at most this: absolute or percent
+    #[arg(long = "max-aspect-diff", value_name = "DIFF")]
+    pub max_aspect_diff: Option<T::Tolerance>,
+
+    /// Video ~ Group videos on visual hash, audio fingerprint, or both
+    #[arg(long = "match-on", value_enum, default_value_t = T::DEFAULT_MATCH_ON)]
+    pub match_on: T::MatchOn,
+
+    /// Video ~ Audio fingerprint Hamming distance threshold (bits per 384, at the best offset)
+    #[arg(long = "audio-threshold", default_value_t = T::DEFAULT_AUDIO_THRESHOLD)]
+    pub audio_threshold: u32,
+}
+
+/// Where and how results are written.
+#[derive(Debug, Clone, clap::Args)]
+#[command(next_help_heading = "Output")]
+pub struct OutputOpts {
+    /// Print JSON output (same as --format json)
+    #[arg(long = "json", action = ArgAction::SetTrue, conflicts_with = "format")]
+    pub json: bool,
+
+    /// Output format: pretty text, JSON, NDJSON (one group per line) or CSV (one row per member) [default: pretty, json with --output]
+    #[arg(long = "format", value_enum)]
+    pub format: Option<T::OutputFormat>,
+
+    /// Write output to a file (JSON unless --format is given)
     #[arg(long = "output", value_hint = ValueHint::FilePath)]
     pub output: Option<PathBuf>,
+
+    /// Write an HTML report with thumbnails to review groups
+    #[arg(long = "report", value_hint = ValueHint::FilePath)]
+    pub report: Option<PathBuf>,
+
+    /// Write one PNG contact sheet per group (thumbnails, or sampled video frames) into this directory
+    #[arg(long = "contact-sheets", value_hint = ValueHint::DirPath)]
+    pub contact_sheets: Option<PathBuf>,
+
+    /// Write a reviewable cleanup script (PowerShell if it ends in .ps1, otherwise sh)
+    #[arg(long = "emit-script", value_hint = ValueHint::FilePath)]
+    pub emit_script: Option<PathBuf>,
+
+    /// Cleanup script: which file of each group to keep
+    #[arg(long = "keep", value_enum, default_value_t = T::DEFAULT_KEEP_POLICY)]
+    pub keep: T::KeepPolicy,
+
+    /// Cleanup script: move duplicates under this directory instead of deleting them
+    #[arg(long = "quarantine", value_hint = ValueHint::DirPath, requires = "emit_script")]
+    pub quarantine: Option<PathBuf>,
+}
+
+impl OutputOpts {
+    /// Output format: a file gets JSON unless another format is asked for.
+    pub fn format(&self) -> T::OutputFormat {
+        match (self.json, &self.output, self.format) {
+            (_, _, Some(format)) => format,
+            (true, _, None) | (false, Some(_), None) => T::OutputFormat::Json,
+            (false, None, None) => T::DEFAULT_OUTPUT_FORMAT,
+        }
+    }
+}
+
+#[derive(Debug, clap::Args)]
+pub struct HashArgs {
+    /// Files to hash (images or videos)
+    #[arg(required = true, value_hint = ValueHint::FilePath)]
+    pub files: Vec<PathBuf>,
+
+    /// Bit string order: hash bit order (img_hash stores bit i in byte i/8, least significant first), or byte by byte like the hex
+    #[arg(long = "bit-order", value_enum, default_value = "lsb-first")]
+    pub bit_order: T::BitOrder,
+
+    /// Print JSON output
+    #[arg(long = "json", action = ArgAction::SetTrue)]
+    pub json: bool,
+
+    #[command(flatten)]
+    pub hashing: HashOpts,
+
+    #[command(flatten)]
+    pub video_opts: VideoOpts,
+}
+
+#[derive(Debug, clap::Args)]
+pub struct GroupArgs {
+    /// Hashes file written with `scan --save-hashes`
+    #[arg(value_hint = ValueHint::FilePath)]
+    pub hashes: PathBuf,
+
+    /// Hamming distance threshold
+    #[arg(default_value_t = T::DEFAULT_THRESHOLD)]
+    pub threshold: u32,
+
+    /// Report images matching a sampled video frame (hashes of a --match-frames scan)
+    #[arg(long = "match-frames", action = ArgAction::SetTrue)]
+    pub match_frames: bool,
+
+    #[command(flatten)]
+    pub matching: MatchOpts,
+
+    #[command(flatten)]
+    pub output: OutputOpts,
+}
+
+#[derive(Debug, clap::Args)]
+pub struct DiffArgs {
+    /// First file
+    #[arg(value_hint = ValueHint::FilePath)]
+    pub a: PathBuf,
+
+    /// Second file
+    #[arg(value_hint = ValueHint::FilePath)]
+    pub b: PathBuf,
+
+    /// Hamming distance threshold the distances are judged against
+    #[arg(default_value_t = T::DEFAULT_THRESHOLD)]
+    pub threshold: u32,
+
+    #[command(flatten)]
+    pub hashing: HashOpts,
+
+    #[command(flatten)]
+    pub video_opts: VideoOpts,
+
+    #[command(flatten)]
+    pub matching: MatchOpts,
+}
+
+#[derive(Debug, clap::Args)]
+pub struct QueryArgs {
+    /// File to look up, or a perceptual hash (base64, as printed by `hash`)
+    pub target: PathBuf,
+
+    /// Hamming distance threshold
+    #[arg(default_value_t = T::DEFAULT_THRESHOLD)]
+    pub threshold: u32,
+
+    /// Search hashes saved with `scan --save-hashes` (and use their hash settings) instead of the cache, which knows files by BLAKE3 only
+    #[arg(long = "hashes", value_hint = ValueHint::FilePath)]
+    pub hashes: Option<PathBuf>,
+
+    /// Print JSON output
+    #[arg(long = "json", action = ArgAction::SetTrue)]
+    pub json: bool,
+
+    #[command(flatten)]
+    pub hashing: HashOpts,
+
+    #[command(flatten)]
+    pub video_opts: VideoOpts,
+}
+
+#[derive(Debug, clap::Args)]
+pub struct CacheArgs {
+    #[command(subcommand)]
+    pub action: CacheAction,
+
+    /// Cache file path.
+    #[arg(long = "cache-file", default_value = T::DEFAULT_CACHE_FILE_NAME, global = true)]
+    pub cache_file: PathBuf,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum CacheAction {
+    /// Show where the cache is and what it holds
+    Info,
+    /// Delete the cache file
+    Clear,
+    /// Forget recorded failures, so those files are retried
+    ClearFailed,
+}
+
+#[derive(Debug, clap::Args)]
+pub struct ReviewArgs {
+    /// Results file written with --output (JSON), or a directory to scan with default settings
+    #[arg(value_hint = ValueHint::AnyPath)]
+    pub input: PathBuf,
+
+    /// Removed files are moved here, so they can be restored with undo
+    #[arg(long = "trash", default_value = T::DEFAULT_TRASH_DIR, value_hint = ValueHint::DirPath)]
+    pub trash: PathBuf,
+
+    /// Journal of executed actions, used for undo (also across sessions)
+    #[arg(long = "journal", default_value = T::DEFAULT_JOURNAL_FILE, value_hint = ValueHint::FilePath)]
+    pub journal: PathBuf,
+
+    /// Selection exported from the HTML report (--report); its files start marked for the trash
+    #[arg(long = "selection", value_hint = ValueHint::FilePath)]
+    pub selection: Option<PathBuf>,
+
+    /// Image previews in the terminal
+    #[arg(long = "preview", value_enum, default_value_t = T::DEFAULT_PREVIEW)]
+    pub preview: T::PreviewMode,
 }
@@ -1,6 +1,17 @@
-use crate::{errors::CacheError, types as T};
-use serde_json;
-use std::{fs, io::Write, path::Path};
+use crate::{
+    errors::{AppError, CacheError},
+    types as T,
+};
+use std::{
+    fs,
+    io::Write,
+    path::{Path, PathBuf},
+};
+
+/// `--cache-file` resolved next to the executable.
+pub fn cache_path(cache_file: &Path) -> std::io::Result<PathBuf> {
+    Ok(std::env::current_exe()?.with_file_name(cache_file))
+}
 
 /// Load + Deserialise, or Create CacheFile
 pub fn load_cache(path: &Path) -> Result<T::CacheFile, CacheError> {
@@ -39,65 +50,106 @@ pub fn save_cache(path: &Path, cache: &T::CacheFile) -> Result<(), CacheError> {
     Ok(())
 }
 
-/// Lookup cache entry for a given BLAKE3 key AND the current config.
+/// Lookup cache entries for a given BLAKE3 key AND the current config:
+/// one per `--hash-alg` (in order), or `None` unless all are cached.
 pub fn lookup(
     cache: &T::CacheFile,
     key: &str,
     cfg: &T::AppConfig,
-    is_video: bool,
-) -> Option<T::CacheEntry> {
+    media_type: T::MediaType,
+) -> Option<Vec<T::CacheEntry>> {
     let entries = cache.by_blake3.get(key)?;
-    if is_video {
-        entries
-            .iter()
-            .find(|e| matches_video_params(e, cfg))
-            .cloned()
-    } else {
-        entries
-            .iter()
-            .find(|e| matches_image_params(e, cfg))
-            .cloned()
-    }
+    cfg.hash_algs
+        .iter()
+        .map(|&alg| {
+            let params = T::HashParams::from_cfg(cfg, alg, media_type);
+            let animated = T::HashParams::for_animation(cfg, alg);
+            entries
+                .iter()
+                .find(|e| {
+                    let params_match = match e.animated {
+                        true => media_type == T::MediaType::Image && e.params == animated,
+                        false => e.params == params,
+                    };
+                    // An entry with an audio fingerprint / colour signature / tiles also serves runs without
+                    let still_image = media_type == T::MediaType::Image && !e.animated;
+                    params_match
+                        && (e.audio || !cfg.audio)
+                        && (e.color.is_some() || !cfg.color || media_type == T::MediaType::Video)
+                        && (e.tiles.is_some() || !cfg.crop_resistant || !still_image)
+                        && (e.media.is_some() || !cfg.with_metadata)
+                })
+                .cloned()
+        })
+        .collect()
 }
 
 /// Insert or replace the cache entry for this BLAKE3 key and config.
+/// `audio`, `color`, `tiles` and `media` are not part of the key: an entry with them supersedes one without.
 pub fn upsert(cache: &mut T::CacheFile, key: String, entry: T::CacheEntry) {
+    // A successful hash clears any recorded failure with the same parameters.
+    if let Some(failed) = cache.failed_by_blake3.get_mut(&key) {
+        failed.retain(|f| f.params != entry.params);
+        if failed.is_empty() {
+            cache.failed_by_blake3.remove(&key);
+        }
+    }
+
     let vec = cache.by_blake3.entry(key).or_default();
     // Replace if same config already stored, else append.
-    if let Some(existing) = vec.iter_mut().find(|e| {
-        // Compare config fields
-        e.hash_alg == entry.hash_alg
-            && e.hash_w == entry.hash_w
-            && e.hash_h == entry.hash_h
-            && e.sample_start == entry.sample_start
-            && e.sample_count == entry.sample_count
-            && e.sample_window == entry.sample_window
-            && e.aggregation == entry.aggregation
-    }) {
+    if let Some(existing) = vec.iter_mut().find(|e| e.params == entry.params) {
         *existing = entry;
     } else {
         vec.push(entry);
     }
 }
 
-/// Check if image configuration exists
-fn matches_image_params(e: &T::CacheEntry, cfg: &T::AppConfig) -> bool {
-    e.hash_alg == cfg.hash_alg
-        && e.hash_w == cfg.hash_w
-        && e.hash_h == cfg.hash_h
-        && e.sample_start.is_none()
-        && e.sample_count.is_none()
-        && e.sample_window.is_none()
-        && e.aggregation.is_none()
+/// Recorded failure for this BLAKE3 key and config (primary algorithm) that should still be skipped.
+/// Failures from another tool version are retried, as are timeouts given a longer limit.
+pub fn lookup_failure(
+    cache: &T::CacheFile,
+    key: &str,
+    cfg: &T::AppConfig,
+    media_type: T::MediaType,
+) -> Option<T::FailedEntry> {
+    if cfg.retry_failed {
+        return None;
+    }
+    let params = T::HashParams::from_cfg(cfg, cfg.primary_alg(), media_type);
+    cache
+        .failed_by_blake3
+        .get(key)?
+        .iter()
+        .find(|f| {
+            f.params == params
+                && f.tool_version == T::TOOL_VERSION
+                && (f.kind != T::FailureKind::Timeout
+                    || (cfg.decode_timeout > 0.0 && cfg.decode_timeout <= f.decode_timeout))
+        })
+        .cloned()
 }
 
-/// Check if video configuration exists
-fn matches_video_params(e: &T::CacheEntry, cfg: &T::AppConfig) -> bool {
-    e.hash_alg == cfg.hash_alg
-        && e.hash_w == cfg.hash_w
-        && e.hash_h == cfg.hash_h
-        && e.sample_start == Some(cfg.sample_start)
-        && e.sample_count == Some(cfg.sample_count)
-        && e.sample_window == Some(cfg.sample_window)
-        && e.aggregation == Some(cfg.aggregation)
+/// Record a failure for this BLAKE3 key and config, if it is worth remembering.
+pub fn record_failure(
+    cache: &mut T::CacheFile,
+    key: String,
+    cfg: &T::AppConfig,
+    media_type: T::MediaType,
+    err: &AppError,
+) {
+    let Some(kind) = err.failure_kind() else {
+        return;
+    };
+    let entry = T::FailedEntry {
+        params: T::HashParams::from_cfg(cfg, cfg.primary_alg(), media_type),
+        kind,
+        error: err.to_string(),
+        tool_version: T::TOOL_VERSION.to_string(),
+        decode_timeout: cfg.decode_timeout,
+    };
+    let vec = cache.failed_by_blake3.entry(key).or_default();
+    match vec.iter_mut().find(|f| f.params == entry.params) {
+        Some(existing) => *existing = entry,
+        None => vec.push(entry),
+    }
 }
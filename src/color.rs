@@ -0,0 +1,121 @@
+//! Colour signature: coarse chroma layout, a secondary signal beside the (luma-only) perceptual hashes.
+//! Separates grayscale conversions, sepia/tints and recolours from true duplicates.
+
+use img_hash::ImageHash;
+use img_hash::image::{DynamicImage, imageops::FilterType};
+
+/// Signature grid (cells per side); each cell stores its mean Cb and Cr.
+const GRID: u32 = 8;
+
+/// Chroma (Cb, Cr) of each cell of a `GRID`×`GRID` downscale, averaged over `imgs`
+/// (one still image, or the sampled frames of an animation). Base64, like the hashes.
+pub fn signature(imgs: &[DynamicImage]) -> Option<String> {
+    if imgs.is_empty() {
+        return None;
+    }
+    let cells = (GRID * GRID) as usize;
+    let mut sums = vec![0.0f64; cells * 2];
+    for img in imgs {
+        let small = img.resize_exact(GRID, GRID, FilterType::Triangle).to_rgb8();
+        for (k, px) in small.pixels().enumerate() {
+            let [r, g, b] = px.0.map(f64::from);
+            sums[2 * k] += 128.0 - 0.168_736 * r - 0.331_264 * g + 0.5 * b;
+            sums[2 * k + 1] += 128.0 + 0.5 * r - 0.418_688 * g - 0.081_312 * b;
+        }
+    }
+    let bytes: Vec<u8> = sums
+        .iter()
+        .map(|s| (s / imgs.len() as f64).round().clamp(0.0, 255.0) as u8)
+        .collect();
+    ImageHash::<Box<[u8]>>::from_bytes(&bytes)
+        .ok()
+        .map(|h| h.to_base64())
+}
+
+/// Decode a stored signature.
+pub fn decode(signature: &str) -> Option<Vec<u8>> {
+    ImageHash::<Box<[u8]>>::from_base64(signature)
+        .ok()
+        .map(|h| h.as_bytes().to_vec())
+}
+
+/// Mean per-cell chroma distance (Euclidean over Cb, Cr; 0-255 scale).
+/// 0 for identical colour, a few units for re-encodes, tens for grayscale/tinted versions.
+pub fn distance(a: &[u8], b: &[u8]) -> u32 {
+    let cells = a.len().min(b.len()) / 2;
+    if cells == 0 {
+        return 0;
+    }
+    let sum: f64 = a
+        .chunks_exact(2)
+        .zip(b.chunks_exact(2))
+        .map(|(x, y)| {
+            let dcb = x[0] as f64 - y[0] as f64;
+            let dcr = x[1] as f64 - y[1] as f64;
+            dcb.hypot(dcr)
+        })
+        .sum();
+    (sum / cells as f64).round() as u32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::DEFAULT_COLOR_THRESHOLD;
+    use img_hash::image::{ImageOutputFormat, RgbImage};
+
+    /// Red/green gradients with a blue diagonal: a different colour in every cell.
+    fn colourful() -> DynamicImage {
+        DynamicImage::ImageRgb8(RgbImage::from_fn(64, 48, |x, y| {
+            let (r, g) = ((x * 4) as u8, (y * 5) as u8);
+            img_hash::image::Rgb([r, g, 255 - r / 2 - g / 2])
+        }))
+    }
+
+    fn dist(a: &DynamicImage, b: &DynamicImage) -> u32 {
+        let sig = |img: &DynamicImage| decode(&signature(std::slice::from_ref(img)).unwrap());
+        distance(&sig(a).unwrap(), &sig(b).unwrap())
+    }
+
+    #[test]
+    fn identical_colour_is_zero() {
+        let img = colourful();
+        assert_eq!(dist(&img, &img.clone()), 0);
+    }
+
+    #[test]
+    fn re_encodes_and_brightening_stay_within_a_few_units() {
+        let img = colourful();
+        assert!(dist(&img, &img.brighten(20)) <= 3);
+
+        let mut jpeg = Vec::new();
+        img.write_to(&mut jpeg, ImageOutputFormat::Jpeg(80))
+            .unwrap();
+        let reencoded = img_hash::image::load_from_memory(&jpeg).unwrap();
+        assert!(dist(&img, &reencoded) <= 3);
+    }
+
+    #[test]
+    fn grayscale_and_tinted_versions_are_far_apart() {
+        let img = colourful();
+        let gray = DynamicImage::ImageRgb8(img.grayscale().to_rgb8());
+        let d = dist(&img, &gray);
+        assert!(d >= 2 * DEFAULT_COLOR_THRESHOLD, "grayscale distance {d}");
+
+        let luma = gray.to_rgb8();
+        let sepia = DynamicImage::ImageRgb8(RgbImage::from_fn(64, 48, |x, y| {
+            let l = luma.get_pixel(x, y).0[0] as f32;
+            let tint = |f: f32| (l * f).min(255.0) as u8;
+            img_hash::image::Rgb([tint(1.07), tint(0.74), tint(0.43)])
+        }));
+        let d = dist(&img, &sepia);
+        assert!(d >= 2 * DEFAULT_COLOR_THRESHOLD, "sepia distance {d}");
+    }
+
+    #[test]
+    fn empty_input_has_no_signature() {
+        assert!(signature(&[]).is_none());
+        assert_eq!(distance(&[], &[]), 0);
+        assert_eq!(distance(&[128, 128], &[]), 0);
+    }
+}
@@ -0,0 +1,161 @@
+//! Small subcommands: `hash` and `cache`.
+
+use std::{
+    fs,
+    io::{self, Write},
+    path::Path,
+};
+
+use img_hash::ImageHash;
+use serde::Serialize;
+
+use crate::{
+    args::{CacheAction, CacheArgs, HashArgs},
+    cache,
+    errors::AppError,
+    hashing, report, run,
+    types::{BitOrder, FileError, MediaType, TOOL_VERSION},
+};
+
+/// `hash`: print each file's BLAKE3 and perceptual hash(es), through the cache.
+pub fn hash(args: &HashArgs) -> Result<(), AppError> {
+    let cfg = run::config(&args.hashing, &args.video_opts, args.files.clone());
+    // Failures are reported by the pipelines
+    let (results, errors) = run::hash_paths(&cfg, &args.hashing.cache_file)?;
+
+    // In argument order (pipelines finish in any order)
+    let files: Vec<HashedFile> = args
+        .files
+        .iter()
+        .filter_map(|path| results.iter().find(|r| &r.path == path))
+        .map(|pr| HashedFile {
+            path: &pr.path,
+            media_type: pr.media_type,
+            blake3: &pr.blake3,
+            hashes: cfg
+                .hash_algs
+                .iter()
+                .zip(&pr.hashes)
+                .filter_map(|(alg, hash)| Encodings::new(alg.name(), hash, args.bit_order))
+                .collect(),
+        })
+        .collect();
+
+    if args.json {
+        let out = HashOutput {
+            tool_version: TOOL_VERSION,
+            hash_w: cfg.hash_w,
+            hash_h: cfg.hash_h,
+            bit_order: args.bit_order,
+            files,
+            errors: &errors,
+        };
+        let mut stdout = io::stdout().lock();
+        serde_json::to_writer_pretty(&mut stdout, &out).map_err(io::Error::from)?;
+        writeln!(stdout)?;
+        return Ok(());
+    }
+    let width = cfg
+        .hash_algs
+        .iter()
+        .map(|a| a.name().len())
+        .max()
+        .unwrap_or(0);
+    for file in &files {
+        println!("{}", file.path.display());
+        println!("  {:width$}  {}", "blake3", file.blake3);
+        for h in &file.hashes {
+            println!("  {:width$}  base64  {}", h.alg, h.base64);
+            println!("  {:width$}  hex     {}", "", h.hex);
+            println!("  {:width$}  bits    {}", "", h.bit_string);
+        }
+    }
+    Ok(())
+}
+
+/// `hash --json`
+#[derive(Serialize)]
+struct HashOutput<'a> {
+    tool_version: &'a str,
+    hash_w: u32,
+    hash_h: u32,
+    /// Order of `bit_string`
+    bit_order: BitOrder,
+    files: Vec<HashedFile<'a>>,
+    errors: &'a [FileError],
+}
+
+#[derive(Serialize)]
+struct HashedFile<'a> {
+    path: &'a Path,
+    media_type: MediaType,
+    blake3: &'a str,
+    hashes: Vec<Encodings<'a>>,
+}
+
+/// One perceptual hash in each encoding.
+#[derive(Serialize)]
+struct Encodings<'a> {
+    alg: String,
+    bits: usize,
+    /// As stored in the cache and saved hashes
+    base64: &'a str,
+    hex: String,
+    bit_string: String,
+}
+
+impl<'a> Encodings<'a> {
+    /// `None` if `base64` isn't a valid stored hash.
+    fn new(alg: String, base64: &'a str, order: BitOrder) -> Option<Self> {
+        let hash = ImageHash::<Box<[u8]>>::from_base64(base64).ok()?;
+        let bytes = hash.as_bytes();
+        Some(Encodings {
+            alg,
+            bits: bytes.len() * 8,
+            base64,
+            hex: hashing::to_hex(bytes),
+            bit_string: hashing::to_bit_string(bytes, order),
+        })
+    }
+}
+
+/// `cache info | clear | clear-failed`
+pub fn cache(args: &CacheArgs) -> Result<(), AppError> {
+    let path = cache::cache_path(&args.cache_file)?;
+    if !path.exists() {
+        println!("No cache at \"{}\"", path.display());
+        return Ok(());
+    }
+    match args.action {
+        CacheAction::Info => {
+            let cache = cache::load_cache(&path)?;
+            let entries: usize = cache.by_blake3.values().map(Vec::len).sum();
+            let failed: usize = cache.failed_by_blake3.values().map(Vec::len).sum();
+            println!("Cache \"{}\"", path.display());
+            println!("  version:  {}", cache.version);
+            println!(
+                "  size:     {}",
+                report::human_size(fs::metadata(&path)?.len())
+            );
+            println!("  files:    {}", cache.by_blake3.len());
+            println!("  entries:  {entries} (one per file and hash settings)");
+            println!("  failures: {failed}");
+        }
+        CacheAction::Clear => {
+            match fs::remove_file(&path) {
+                Ok(()) => {}
+                Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+                Err(e) => return Err(e.into()),
+            }
+            println!("Removed \"{}\"", path.display());
+        }
+        CacheAction::ClearFailed => {
+            let mut cache = cache::load_cache(&path)?;
+            let failed: usize = cache.failed_by_blake3.values().map(Vec::len).sum();
+            cache.failed_by_blake3.clear();
+            cache::save_cache(&path, &cache)?;
+            println!("Forgot {failed} failure(s); those files will be retried");
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,422 @@
+//! Contact sheets (`--contact-sheets`): one PNG per duplicate group, for sharing where an
+//! HTML page won't do. Images are shown as a grid of labelled thumbnails; videos as one row
+//! per file with the sampled frames used for hashing.
+
+use std::{fs, path::Path};
+
+use img_hash::image::{GenericImageView, Rgb, RgbImage, imageops};
+use indicatif::ParallelProgressIterator;
+use rayon::prelude::*;
+
+use crate::{
+    errors::{AppError, HashError},
+    grouping::Group,
+    progress,
+    types::{AppConfig, MediaType, PipelineResult},
+    video,
+};
+
+/// Image ~ Thumbnail box (px) and thumbnails per row.
+const THUMB_SIZE: u32 = 240;
+const COLUMNS: usize = 4;
+
+/// Video ~ Frames per row, evenly spaced over the sampling window.
+const STRIP_FRAMES: usize = 10;
+
+/// Video ~ Label length allowed even when the frames are narrower.
+const MIN_ROW_CHARS: u32 = 48;
+
+const PAD: u32 = 8;
+
+/// Labels: the 5x7 font at 2x, captions under video frames at 1x.
+const LABEL_SCALE: u32 = 2;
+const CAPTION_SCALE: u32 = 1;
+
+const BACKGROUND: Rgb<u8> = Rgb([255, 255, 255]);
+const TEXT: Rgb<u8> = Rgb([20, 20, 20]);
+const MUTED: Rgb<u8> = Rgb([110, 110, 110]);
+
+/// Write `group-001.png`, `group-002.png`, … into `dir` (created if missing).
+/// Video frames are re-decoded with the same sampling as hashing.
+pub fn write_contact_sheets(
+    groups: &[Group],
+    items: &[PipelineResult],
+    cfg: &AppConfig,
+    dir: &Path,
+) -> Result<(), AppError> {
+    fs::create_dir_all(dir)?;
+    let pb = progress::bar(groups.len() as u64, "Contact sheets");
+    let width = groups.len().max(1).to_string().len().max(3);
+    groups
+        .par_iter()
+        .enumerate()
+        .progress_with(pb.clone())
+        .try_for_each(|(gi, g)| {
+            let sheet = render(gi + 1, g, items, cfg);
+            let path = dir.join(format!("group-{:0width$}.png", gi + 1));
+            sheet
+                .save(&path)
+                .map_err(|e| AppError::Hash(HashError::from(e)))
+        })?;
+    pb.finish_and_clear();
+    Ok(())
+}
+
+/// One member's label: file name, and distance to the group's first member.
+fn labels(items: &[PipelineResult], index: usize, dist_bits: u32, first: bool) -> [String; 2] {
+    let pr = &items[index];
+    let name = pr.path.file_name().map_or_else(
+        || pr.path.display().to_string(),
+        |n| n.to_string_lossy().into_owned(),
+    );
+    let dist = match first {
+        true => "first".to_string(),
+        false => format!("dist {dist_bits} bits"),
+    };
+    [name, dist]
+}
+
+fn render(number: usize, group: &Group, items: &[PipelineResult], cfg: &AppConfig) -> RgbImage {
+    let kind = items[group.members[0].index].media_type;
+    let title = format!(
+        "Group {number} - {} {} - avg dist {:.2} bits",
+        group.members.len(),
+        match kind {
+            MediaType::Image => "images",
+            MediaType::Video => "videos",
+        },
+        group.avg_dist_bits
+    );
+    let blocks: Vec<RgbImage> = group
+        .members
+        .iter()
+        .enumerate()
+        .map(|(mi, m)| {
+            let label = labels(items, m.index, m.dist_bits, mi == 0);
+            match kind {
+                MediaType::Image => image_cell(&items[m.index], &label),
+                MediaType::Video => video_row(&items[m.index], &label, cfg),
+            }
+        })
+        .collect();
+    let columns = match kind {
+        MediaType::Image => COLUMNS,
+        MediaType::Video => 1,
+    };
+    layout(&title, &blocks, columns)
+}
+
+/// Thumbnail centred in a fixed box, two label lines below.
+fn image_cell(pr: &PipelineResult, label: &[String; 2]) -> RgbImage {
+    let line_h = line_height(LABEL_SCALE);
+    let mut cell = RgbImage::from_pixel(THUMB_SIZE, THUMB_SIZE + 2 * line_h, BACKGROUND);
+    match img_hash::image::open(&pr.path) {
+        Ok(img) => {
+            let thumb = img.thumbnail(THUMB_SIZE, THUMB_SIZE).to_rgb8();
+            let (x, y) = (
+                (THUMB_SIZE - thumb.width()) / 2,
+                (THUMB_SIZE - thumb.height()) / 2,
+            );
+            imageops::overlay(&mut cell, &thumb, x, y);
+        }
+        Err(_) => draw_text(
+            &mut cell,
+            PAD,
+            THUMB_SIZE / 2,
+            "(unreadable)",
+            LABEL_SCALE,
+            MUTED,
+        ),
+    }
+    let max_chars = (THUMB_SIZE / char_width(LABEL_SCALE)) as usize;
+    draw_text(
+        &mut cell,
+        0,
+        THUMB_SIZE + 2,
+        &fit(&label[0], max_chars),
+        LABEL_SCALE,
+        TEXT,
+    );
+    draw_text(
+        &mut cell,
+        0,
+        THUMB_SIZE + 2 + line_h,
+        &label[1],
+        LABEL_SCALE,
+        MUTED,
+    );
+    cell
+}
+
+/// Label line, then up to `STRIP_FRAMES` sampled frames (as hashed: small and grayscale) with
+/// their timestamps.
+fn video_row(pr: &PipelineResult, label: &[String; 2], cfg: &AppConfig) -> RgbImage {
+    let frames =
+        video::decode::decode_sample_frames(&pr.path, cfg, STRIP_FRAMES).unwrap_or_default();
+    let (frame_w, frame_h) = frames
+        .iter()
+        .map(|f| f.image.dimensions())
+        .fold((0, 0), |(w, h), (fw, fh)| (w.max(fw), h.max(fh)));
+    let caption_h = line_height(CAPTION_SCALE);
+    let line_h = line_height(LABEL_SCALE);
+    let frames_w = frames.len() as u32 * (frame_w + PAD);
+    let max_chars = (frames_w / char_width(LABEL_SCALE)).max(MIN_ROW_CHARS) as usize;
+    let text = fit(&format!("{}  ({})", label[0], label[1]), max_chars);
+    let width = frames_w.max(text.chars().count() as u32 * char_width(LABEL_SCALE));
+    let height = line_h + frame_h + caption_h + PAD;
+
+    let mut row = RgbImage::from_pixel(width.max(1), height, BACKGROUND);
+    draw_text(&mut row, 0, 0, &text, LABEL_SCALE, TEXT);
+    if frames.is_empty() {
+        draw_text(
+            &mut row,
+            0,
+            line_h,
+            "(no frames decoded)",
+            LABEL_SCALE,
+            MUTED,
+        );
+    }
+    for (i, f) in frames.iter().enumerate() {
+        let (x, y) = (i as u32 * (frame_w + PAD), line_h);
+        imageops::overlay(&mut row, &f.image.to_rgb8(), x, y);
+        let caption = format!("{:.1}s", f.pts_secs);
+        draw_text(&mut row, x, y + frame_h + 1, &caption, CAPTION_SCALE, MUTED);
+    }
+    row
+}
+
+/// Title, then `blocks` in rows of `columns`.
+fn layout(title: &str, blocks: &[RgbImage], columns: usize) -> RgbImage {
+    let title_h = line_height(LABEL_SCALE) + PAD;
+    let rows: Vec<&[RgbImage]> = blocks.chunks(columns).collect();
+    let row_w = |row: &[RgbImage]| row.iter().map(|b| b.width() + PAD).sum::<u32>();
+    let row_h = |row: &[RgbImage]| row.iter().map(|b| b.height()).max().unwrap_or(0) + PAD;
+    let title_w = title.chars().count() as u32 * char_width(LABEL_SCALE);
+    let width = rows
+        .iter()
+        .map(|r| row_w(r))
+        .max()
+        .unwrap_or(0)
+        .max(title_w)
+        + PAD;
+    let height = PAD + title_h + rows.iter().map(|r| row_h(r)).sum::<u32>();
+
+    let mut sheet = RgbImage::from_pixel(width, height, BACKGROUND);
+    draw_text(&mut sheet, PAD, PAD, title, LABEL_SCALE, TEXT);
+    let mut y = PAD + title_h;
+    for row in rows {
+        let mut x = PAD;
+        for block in row {
+            imageops::overlay(&mut sheet, block, x, y);
+            x += block.width() + PAD;
+        }
+        y += row_h(row);
+    }
+    sheet
+}
+
+/// Shorten to `max` characters, keeping the end (extension, numbering) visible.
+fn fit(text: &str, max: usize) -> String {
+    let n = text.chars().count();
+    match n <= max {
+        true => text.to_string(),
+        false => {
+            let tail: String = text.chars().skip(n + 3 - max).collect();
+            format!("...{tail}")
+        }
+    }
+}
+
+fn char_width(scale: u32) -> u32 {
+    (GLYPH_W + 1) * scale
+}
+
+fn line_height(scale: u32) -> u32 {
+    (GLYPH_H + 3) * scale
+}
+
+/// Draw `text` with its top-left at (x, y), clipped to the image. Characters outside
+/// printable ASCII are drawn as `?`.
+fn draw_text(img: &mut RgbImage, x: u32, y: u32, text: &str, scale: u32, color: Rgb<u8>) {
+    for (i, c) in text.chars().enumerate() {
+        let c = match c {
+            ' '..='~' => c,
+            _ => '?',
+        };
+        let glyph = &FONT[c as usize - ' ' as usize];
+        let gx = x + i as u32 * char_width(scale);
+        for (col, bits) in glyph.iter().enumerate() {
+            for row in 0..GLYPH_H {
+                if bits >> row & 1 == 0 {
+                    continue;
+                }
+                for dy in 0..scale {
+                    for dx in 0..scale {
+                        let (px, py) = (gx + col as u32 * scale + dx, y + row * scale + dy);
+                        if px < img.width() && py < img.height() {
+                            img.put_pixel(px, py, color);
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+const GLYPH_W: u32 = 5;
+const GLYPH_H: u32 = 7;
+
+/// Classic 5x7 LCD font, printable ASCII (space to `~`). One byte per column, bit 0 = top row.
+#[rustfmt::skip]
+const FONT: [[u8; 5]; 95] = [
+    [0x00, 0x00, 0x00, 0x00, 0x00], // ' '
+    [0x00, 0x00, 0x5F, 0x00, 0x00], // '!'
+    [0x00, 0x07, 0x00, 0x07, 0x00], // '"'
+    [0x14, 0x7F, 0x14, 0x7F, 0x14], // '#'
+    [0x24, 0x2A, 0x7F, 0x2A, 0x12], // '$'
+    [0x23, 0x13, 0x08, 0x64, 0x62], // '%'
+    [0x36, 0x49, 0x55, 0x22, 0x50], // '&'
+    [0x00, 0x05, 0x03, 0x00, 0x00], // '\''
+    [0x00, 0x1C, 0x22, 0x41, 0x00], // '('
+    [0x00, 0x41, 0x22, 0x1C, 0x00], // ')'
+    [0x14, 0x08, 0x3E, 0x08, 0x14], // '*'
+    [0x08, 0x08, 0x3E, 0x08, 0x08], // '+'
+    [0x00, 0x50, 0x30, 0x00, 0x00], // ','
+    [0x08, 0x08, 0x08, 0x08, 0x08], // '-'
+    [0x00, 0x60, 0x60, 0x00, 0x00], // '.'
+    [0x20, 0x10, 0x08, 0x04, 0x02], // '/'
+    [0x3E, 0x51, 0x49, 0x45, 0x3E], // '0'
+    [0x00, 0x42, 0x7F, 0x40, 0x00], // '1'
+    [0x42, 0x61, 0x51, 0x49, 0x46], // '2'
+    [0x21, 0x41, 0x45, 0x4B, 0x31], // '3'
+    [0x18, 0x14, 0x12, 0x7F, 0x10], // '4'
+    [0x27, 0x45, 0x45, 0x45, 0x39], // '5'
+    [0x3C, 0x4A, 0x49, 0x49, 0x30], // '6'
+    [0x01, 0x71, 0x09, 0x05, 0x03], // '7'
+    [0x36, 0x49, 0x49, 0x49, 0x36], // '8'
+    [0x06, 0x49, 0x49, 0x29, 0x1E], // '9'
+    [0x00, 0x36, 0x36, 0x00, 0x00], // ':'
+    [0x00, 0x56, 0x36, 0x00, 0x00], // ';'
+    [0x08, 0x14, 0x22, 0x41, 0x00], // '<'
+    [0x14, 0x14, 0x14, 0x14, 0x14], // '='
+    [0x00, 0x41, 0x22, 0x14, 0x08], // '>'
+    [0x02, 0x01, 0x51, 0x09, 0x06], // '?'
+    [0x32, 0x49, 0x79, 0x41, 0x3E], // '@'
+    [0x7E, 0x11, 0x11, 0x11, 0x7E], // 'A'
+    [0x7F, 0x49, 0x49, 0x49, 0x36], // 'B'
+    [0x3E, 0x41, 0x41, 0x41, 0x22], // 'C'
+    [0x7F, 0x41, 0x41, 0x22, 0x1C], // 'D'
+    [0x7F, 0x49, 0x49, 0x49, 0x41], // 'E'
+    [0x7F, 0x09, 0x09, 0x09, 0x01], // 'F'
+    [0x3E, 0x41, 0x49, 0x49, 0x7A], // 'G'
+    [0x7F, 0x08, 0x08, 0x08, 0x7F], // 'H'
+    [0x00, 0x41, 0x7F, 0x41, 0x00], // 'I'
+    [0x20, 0x40, 0x41, 0x3F, 0x01], // 'J'
+    [0x7F, 0x08, 0x14, 0x22, 0x41], // 'K'
+    [0x7F, 0x40, 0x40, 0x40, 0x40], // 'L'
+    [0x7F, 0x02, 0x0C, 0x02, 0x7F], // 'M'
+    [0x7F, 0x04, 0x08, 0x10, 0x7F], // 'N'
+    [0x3E, 0x41, 0x41, 0x41, 0x3E], // 'O'
+    [0x7F, 0x09, 0x09, 0x09, 0x06], // 'P'
+    [0x3E, 0x41, 0x51, 0x21, 0x5E], // 'Q'
+    [0x7F, 0x09, 0x19, 0x29, 0x46], // 'R'
+    [0x46, 0x49, 0x49, 0x49, 0x31], // 'S'
+    [0x01, 0x01, 0x7F, 0x01, 0x01], // 'T'
+    [0x3F, 0x40, 0x40, 0x40, 0x3F], // 'U'
+    [0x1F, 0x20, 0x40, 0x20, 0x1F], // 'V'
+    [0x3F, 0x40, 0x38, 0x40, 0x3F], // 'W'
+    [0x63, 0x14, 0x08, 0x14, 0x63], // 'X'
+    [0x07, 0x08, 0x70, 0x08, 0x07], // 'Y'
+    [0x61, 0x51, 0x49, 0x45, 0x43], // 'Z'
+    [0x00, 0x7F, 0x41, 0x41, 0x00], // '['
+    [0x02, 0x04, 0x08, 0x10, 0x20], // '\\'
+    [0x00, 0x41, 0x41, 0x7F, 0x00], // ']'
+    [0x04, 0x02, 0x01, 0x02, 0x04], // '^'
+    [0x40, 0x40, 0x40, 0x40, 0x40], // '_'
+    [0x00, 0x01, 0x02, 0x04, 0x00], // '`'
+    [0x20, 0x54, 0x54, 0x54, 0x78], // 'a'
+    [0x7F, 0x48, 0x44, 0x44, 0x38], // 'b'
+    [0x38, 0x44, 0x44, 0x44, 0x20], // 'c'
+    [0x38, 0x44, 0x44, 0x48, 0x7F], // 'd'
+    [0x38, 0x54, 0x54, 0x54, 0x18], // 'e'
+    [0x08, 0x7E, 0x09, 0x01, 0x02], // 'f'
+    [0x0C, 0x52, 0x52, 0x52, 0x3E], // 'g'
+    [0x7F, 0x08, 0x04, 0x04, 0x78], // 'h'
+    [0x00, 0x44, 0x7D, 0x40, 0x00], // 'i'
+    [0x20, 0x40, 0x44, 0x3D, 0x00], // 'j'
+    [0x7F, 0x10, 0x28, 0x44, 0x00], // 'k'
+    [0x00, 0x41, 0x7F, 0x40, 0x00], // 'l'
+    [0x7C, 0x04, 0x18, 0x04, 0x78], // 'm'
+    [0x7C, 0x08, 0x04, 0x04, 0x78], // 'n'
+    [0x38, 0x44, 0x44, 0x44, 0x38], // 'o'
+    [0x7C, 0x14, 0x14, 0x14, 0x08], // 'p'
+    [0x08, 0x14, 0x14, 0x18, 0x7C], // 'q'
+    [0x7C, 0x08, 0x04, 0x04, 0x08], // 'r'
+    [0x48, 0x54, 0x54, 0x54, 0x20], // 's'
+    [0x04, 0x3F, 0x44, 0x40, 0x20], // 't'
+    [0x3C, 0x40, 0x40, 0x20, 0x7C], // 'u'
+    [0x1C, 0x20, 0x40, 0x20, 0x1C], // 'v'
+    [0x3C, 0x40, 0x30, 0x40, 0x3C], // 'w'
+    [0x44, 0x28, 0x10, 0x28, 0x44], // 'x'
+    [0x0C, 0x50, 0x50, 0x50, 0x3C], // 'y'
+    [0x44, 0x64, 0x54, 0x4C, 0x44], // 'z'
+    [0x00, 0x08, 0x36, 0x41, 0x00], // '{'
+    [0x00, 0x00, 0x7F, 0x00, 0x00], // '|'
+    [0x00, 0x41, 0x36, 0x08, 0x00], // '}'
+    [0x08, 0x04, 0x08, 0x10, 0x08], // '~'
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fit_keeps_the_extension_in_exactly_max_chars() {
+        assert_eq!(fit("short.png", 20), "short.png");
+        let long = "a-rather-long-file-name-0042.jpeg";
+        let fitted = fit(long, 16);
+        assert_eq!(fitted.chars().count(), 16);
+        assert!(fitted.starts_with("..."));
+        assert!(fitted.ends_with("-0042.jpeg"), "{fitted}");
+
+        let fitted = fit("ünïcödé-ñame-001.png", 12);
+        assert_eq!(fitted.chars().count(), 12);
+        assert!(fitted.ends_with("001.png"), "{fitted}");
+    }
+
+    #[test]
+    fn layout_wraps_blocks_into_rows_of_columns() {
+        let block = RgbImage::from_pixel(50, 30, BACKGROUND);
+        let title_h = line_height(LABEL_SCALE) + PAD;
+        let row_h = 30 + PAD;
+        let size = |n: usize| {
+            let sheet = layout("", &vec![block.clone(); n], COLUMNS);
+            sheet.dimensions()
+        };
+        assert_eq!(size(1), (PAD + 50 + PAD, PAD + title_h + row_h));
+        let full_row = PAD + COLUMNS as u32 * (50 + PAD);
+        assert_eq!(size(COLUMNS), (full_row, PAD + title_h + row_h));
+        assert_eq!(size(COLUMNS + 1), (full_row, PAD + title_h + 2 * row_h));
+
+        // A long title widens the sheet
+        let title = "t".repeat(100);
+        let sheet = layout(&title, &[block], COLUMNS);
+        assert_eq!(sheet.width(), 100 * char_width(LABEL_SCALE) + PAD);
+    }
+
+    #[test]
+    fn non_ascii_is_drawn_as_question_marks() {
+        let draw = |text: &str| {
+            let mut img = RgbImage::from_pixel(40, 10, BACKGROUND);
+            draw_text(&mut img, 0, 0, text, CAPTION_SCALE, TEXT);
+            img
+        };
+        assert_eq!(draw("é日"), draw("??"));
+        assert_ne!(draw("??"), draw("  "));
+
+        // Clipped at the edges instead of panicking
+        let mut img = RgbImage::from_pixel(8, 8, BACKGROUND);
+        draw_text(&mut img, 5, 5, "ünïcode 🎞", LABEL_SCALE, TEXT);
+    }
+}
@@ -0,0 +1,220 @@
+//! `diff`: hash two files and explain every distance between them, then whether `scan`
+//! would group them with the same settings.
+
+use img_hash::ImageHash;
+
+use crate::{
+    args::DiffArgs,
+    color,
+    errors::AppError,
+    report, run, tiles,
+    types::{AppConfig, MediaType, PipelineResult},
+    video::{align, audio},
+};
+
+pub fn run(args: &DiffArgs) -> Result<(), AppError> {
+    // Everything a comparison can use, whatever the matching options
+    let cfg = AppConfig {
+        color: true,
+        crop_resistant: true,
+        audio: true,
+        with_metadata: true,
+        ..run::config(
+            &args.hashing,
+            &args.video_opts,
+            vec![args.a.clone(), args.b.clone()],
+        )
+    };
+    // Failures are reported by the pipelines
+    let (results, _) = run::hash_paths(&cfg, &args.hashing.cache_file)?;
+    let find = |path| results.iter().find(|r| &r.path == path);
+    let (Some(a), Some(b)) = (find(&args.a), find(&args.b)) else {
+        return Ok(());
+    };
+
+    describe("a", a);
+    describe("b", b);
+    println!();
+    if a.blake3 == b.blake3 {
+        println!("Identical contents (same BLAKE3)");
+    }
+
+    let t = args.threshold;
+    let matched = match (a.media_type, b.media_type) {
+        (MediaType::Image, MediaType::Video) => image_in_video(a, b, t),
+        (MediaType::Video, MediaType::Image) => image_in_video(b, a, t),
+        (media_type, _) => {
+            hash_distances(a, b, &cfg, t);
+            match media_type {
+                MediaType::Image => image_checks(a, b, args),
+                MediaType::Video => video_checks(a, b, args),
+            }
+            // Same grouping as `scan`, on just these two
+            let pair = run::Hashed {
+                directory: ".".into(),
+                cfg: cfg.clone(),
+                media: vec![media_type],
+                files_found: 2,
+                results: vec![a.clone(), b.clone()],
+                errors: Vec::new(),
+            };
+            !run::group(t, &args.matching, &pair).is_empty()
+        }
+    };
+
+    println!();
+    match matched {
+        true => println!("Duplicates at threshold {t}"),
+        false => println!("Not duplicates at threshold {t}"),
+    }
+    Ok(())
+}
+
+fn describe(label: &str, pr: &PipelineResult) {
+    println!("{label}: {}", pr.path.display());
+    let mut facts = vec![match pr.media_type {
+        MediaType::Image => "image".to_string(),
+        MediaType::Video => "video".to_string(),
+    }];
+    if let Some(info) = &pr.info {
+        let mut kind = format!(
+            "{}x{} {}",
+            info.media.width, info.media.height, info.media.format
+        );
+        if let Some(codec) = &info.media.codec {
+            kind += &format!(" ({codec})");
+        }
+        facts.push(kind);
+        facts.push(report::human_size(info.size_bytes));
+    }
+    if let Some(d) = pr.meta.and_then(|m| m.duration_secs) {
+        facts.push(format!("{d:.2}s"));
+    }
+    println!("   {}", facts.join(", "));
+    println!("   blake3 {}", pr.blake3);
+}
+
+/// Whole-file hash distance per algorithm (aggregated over frames for videos).
+fn hash_distances(a: &PipelineResult, b: &PipelineResult, cfg: &AppConfig, threshold: u32) {
+    for ((alg, ha), hb) in cfg.hash_algs.iter().zip(&a.hashes).zip(&b.hashes) {
+        let (Ok(ha), Ok(hb)) = (
+            ImageHash::<Box<[u8]>>::from_base64(ha),
+            ImageHash::<Box<[u8]>>::from_base64(hb),
+        ) else {
+            continue;
+        };
+        let bits = ha.as_bytes().len() * 8;
+        let d = ha.dist(&hb);
+        println!(
+            "{:<16} {d} / {bits} bits ({:.1}%), {}",
+            format!("{}:", alg.name()),
+            d as f64 * 100.0 / bits as f64,
+            within(d, threshold)
+        );
+    }
+}
+
+fn image_checks(a: &PipelineResult, b: &PipelineResult, args: &DiffArgs) {
+    let (t, matching) = (args.threshold, &args.matching);
+    let colors = (
+        a.color.as_deref().and_then(color::decode),
+        b.color.as_deref().and_then(color::decode),
+    );
+    if let (Some(ca), Some(cb)) = colors {
+        let d = color::distance(&ca, &cb);
+        let verdict = match d > matching.color_threshold {
+            true => "different colours",
+            false => "same colours",
+        };
+        println!(
+            "{:<16} {d} ({verdict} at --color-threshold {})",
+            "colour:", matching.color_threshold
+        );
+    }
+    let ta = tiles::decode_tiles(a.tiles.as_deref().unwrap_or_default());
+    let tb = tiles::decode_tiles(b.tiles.as_deref().unwrap_or_default());
+    match tiles::match_tiles(&ta, &tb, t, 0.0) {
+        Some(m) => println!(
+            "{:<16} {}/{} of the smaller image's tiles within threshold (mean {:.1} bits; --min-tile-match {})",
+            "tiles:", m.matched, m.total, m.mean_dist_bits, matching.min_tile_match
+        ),
+        None => println!("{:<16} none within threshold", "tiles:"),
+    }
+}
+
+fn video_checks(a: &PipelineResult, b: &PipelineResult, args: &DiffArgs) {
+    let (t, matching) = (args.threshold, &args.matching);
+    let sa = align::decode_sequence(a.frames.as_deref().unwrap_or_default());
+    let sb = align::decode_sequence(b.frames.as_deref().unwrap_or_default());
+    match align::align(&sa, &sb, t) {
+        Some(al) => println!(
+            "{:<16} {:.0}% of overlapping frames within threshold at offset {:+.2}s (mean {:.1} bits; --min-similarity {})",
+            "frames:",
+            al.similarity * 100.0,
+            al.offset_secs,
+            al.mean_dist_bits,
+            matching.min_similarity
+        ),
+        None => println!("{:<16} no alignment within threshold", "frames:"),
+    }
+
+    let audio = |pr: &PipelineResult| {
+        pr.audio_hash
+            .as_deref()
+            .and_then(|h| ImageHash::<Box<[u8]>>::from_base64(h).ok())
+    };
+    match (audio(a), audio(b)) {
+        (Some(ha), Some(hb)) => {
+            let d = audio::distance(&ha, &hb);
+            println!(
+                "{:<16} {d} bits per 384, {}",
+                "audio:",
+                within(d, matching.audio_threshold)
+            );
+        }
+        _ => println!("{:<16} missing (no audio stream)", "audio:"),
+    }
+
+    if let (Some(ma), Some(mb)) = (a.meta, b.meta) {
+        if let (Some(da), Some(db)) = (ma.duration_secs, mb.duration_secs) {
+            println!(
+                "{:<16} {da:.2}s vs {db:.2}s (differ by {:.2}s)",
+                "duration:",
+                (da - db).abs()
+            );
+        }
+        if let (Some(ra), Some(rb)) = (ma.aspect(), mb.aspect()) {
+            println!("{:<16} {ra:.3} vs {rb:.3}", "aspect ratio:");
+        }
+    }
+}
+
+/// Image against each sampled frame of the video (primary algorithm); true if within threshold.
+fn image_in_video(image: &PipelineResult, video: &PipelineResult, threshold: u32) -> bool {
+    let Ok(hash) = ImageHash::from_base64(&image.hashes[0]) else {
+        return false;
+    };
+    let seq = align::decode_sequence(video.frames.as_deref().unwrap_or_default());
+    let Some((pts, d)) = seq
+        .iter()
+        .map(|(t, h)| (*t, hash.dist(h)))
+        .min_by_key(|(_, d)| *d)
+    else {
+        println!("{:<16} no sampled frames", "closest frame:");
+        return false;
+    };
+    println!(
+        "{:<16} {pts:.2}s, {d} / {} bits, {}",
+        "closest frame:",
+        hash.as_bytes().len() * 8,
+        within(d, threshold)
+    );
+    d <= threshold
+}
+
+fn within(d: u32, threshold: u32) -> String {
+    match d <= threshold {
+        true => format!("within {threshold}"),
+        false => format!("over {threshold}"),
+    }
+}
@@ -1,3 +1,4 @@
+use crate::types::FailureKind;
 use thiserror::Error;
 
 #[derive(Debug, Error)]
@@ -8,6 +9,9 @@ pub enum AppError {
     #[error(transparent)]
     Hash(#[from] HashError),
 
+    #[error("Saved hashes error: {0}")]
+    SavedHashes(#[from] SavedHashesError),
+
     #[error("IO error: {0}")]
     Io(#[from] std::io::Error),
 
@@ -16,6 +20,31 @@ pub enum AppError {
 
     #[error(transparent)]
     Video(#[from] VideoError),
+
+    #[error(transparent)]
+    Action(#[from] ActionError),
+
+    #[error(transparent)]
+    Review(#[from] ReviewError),
+
+    #[error(transparent)]
+    Query(#[from] QueryError),
+
+    #[error("failed in a previous run: {0} (skipped, use --retry-failed)")]
+    PreviouslyFailed(String),
+}
+
+impl AppError {
+    /// Kind recorded in the negative cache; `None` for transient errors (I/O) worth retrying.
+    pub fn failure_kind(&self) -> Option<FailureKind> {
+        match self {
+            AppError::Hash(HashError::Image(_)) => Some(FailureKind::Image),
+            AppError::Video(VideoError::Decode(_)) => Some(FailureKind::Decode),
+            AppError::Video(VideoError::NoSamples) => Some(FailureKind::NoSamples),
+            AppError::Video(VideoError::Timeout(_)) => Some(FailureKind::Timeout),
+            _ => None,
+        }
+    }
 }
 
 #[derive(Debug, Error)]
@@ -30,6 +59,24 @@ pub enum CacheError {
     VersionMismatch { expected: u32, found: u32 },
 }
 
+#[derive(Debug, Error)]
+pub enum SavedHashesError {
+    #[error("Failed to read file: {0}")]
+    Io(#[from] std::io::Error),
+
+    #[error("Failed to parse JSON: {0}")]
+    Json(#[from] serde_json::Error),
+
+    #[error("Unexpected version: expected {expected}, got {found}")]
+    VersionMismatch { expected: u32, found: u32 },
+
+    #[error("Invalid entry for {path}: {reason}")]
+    Invalid {
+        path: std::path::PathBuf,
+        reason: String,
+    },
+}
+
 #[derive(Debug, Error)]
 pub enum HashError {
     #[error(transparent)]
@@ -49,4 +96,77 @@ pub enum VideoError {
 
     #[error("Failed to decode frames")]
     NoSamples,
+
+    #[error("decoding timed out after {0}s")]
+    Timeout(f64),
+}
+
+#[derive(Debug, Error)]
+pub enum ActionError {
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+
+    #[error("Journal error: {0}")]
+    Json(#[from] serde_json::Error),
+
+    #[error("changed or missing since the scan: {0}")]
+    Changed(std::path::PathBuf),
+
+    #[error("already exists, not overwritten: {0}")]
+    Exists(std::path::PathBuf),
+
+    #[error("nothing to undo")]
+    NothingToUndo,
+
+    /// Files moved (absolute paths, journaled under `batch`) before a move failed.
+    #[error("moved {} file(s) to the trash (batch {batch}), then failed: {error}", moved.len())]
+    Partial {
+        batch: u64,
+        moved: Vec<std::path::PathBuf>,
+        error: Box<ActionError>,
+    },
+
+    /// Files restored (absolute paths) before an undo of `batch` failed; undo again to finish.
+    #[error("restored {} file(s) of batch {batch}, then failed: {error}", restored.len())]
+    PartialUndo {
+        batch: u64,
+        restored: Vec<std::path::PathBuf>,
+        error: Box<ActionError>,
+    },
+}
+
+#[derive(Debug, Error)]
+pub enum ReviewError {
+    #[error("Failed to read results file: {0}")]
+    Io(#[from] std::io::Error),
+
+    #[error("Failed to parse results file: {0}")]
+    Json(#[from] serde_json::Error),
+
+    #[error("Unexpected results schema version: expected {expected}, got {found}")]
+    VersionMismatch { expected: u32, found: u32 },
+
+    #[error("Results file has no groups (frame or clip matches can't be reviewed)")]
+    NoGroups,
+
+    #[error("Failed to read selection file {path}: {error}")]
+    Selection {
+        path: std::path::PathBuf,
+        error: String,
+    },
+}
+
+#[derive(Debug, Error)]
+pub enum QueryError {
+    #[error("not a file or a base64 hash: {0}")]
+    Target(String),
+
+    #[error("{path}: {error}")]
+    Hash {
+        path: std::path::PathBuf,
+        error: String,
+    },
+
+    #[error("hash has {found} bits, the searched hashes have {expected} (check --hash-w/--hash-h)")]
+    Length { expected: usize, found: usize },
 }
@@ -1,4 +1,13 @@
-use crate::{progress, types::PipelineResult};
+use crate::{
+    color, progress,
+    tiles::{self, TileMatch},
+    types::{ColorMode, Fusion, HashAlg, PipelineResult, Tolerance, VideoMeta},
+    video::{
+        align::{self, Alignment},
+        audio,
+    },
+};
+use std::cmp::Ordering;
 
 #[derive(Debug, Clone)]
 pub struct Group {
@@ -10,35 +19,358 @@ pub struct Group {
 pub struct GroupMember {
     pub index: usize,
     pub dist_bits: u32,
+    /// Video ~ Sequence comparison: matched offset and similarity relative to the group seed.
+    pub alignment: Option<Alignment>,
+    /// Video ~ Audio fingerprint distance to the group seed, when matching on audio.
+    pub audio_dist_bits: Option<u32>,
+    /// Each algorithm's distance to the group seed, when several are fused.
+    pub alg_dists: Vec<(HashAlg, u32)>,
+    /// Image ~ Colour signature distance to the group seed, with `--color`.
+    pub color_dist: Option<u32>,
+    /// Image ~ Same image, different colour treatment than the group seed.
+    pub color_variant: bool,
+    /// Image ~ Matched on tiles (a crop of / cropped from the group seed), with `--crop-resistant`.
+    pub tiles: Option<TileMatch>,
 }
 
-/// Group items whose perceptual-hash Hamming distance <= `threshold` (bits).
+/// Result of comparing two items that matched.
+#[derive(Debug, Clone)]
+struct Match {
+    dist_bits: u32,
+    alignment: Option<Alignment>,
+    audio_dist_bits: Option<u32>,
+    alg_dists: Vec<(HashAlg, u32)>,
+    color_dist: Option<u32>,
+    color_variant: bool,
+    tiles: Option<TileMatch>,
+}
+
+/// Image ~ Checks beyond the whole-image hashes; videos use `ImageChecks::OFF`.
+#[derive(Debug, Clone, Copy)]
+pub struct ImageChecks {
+    pub color: ColorCheck,
+    /// Crop-resistant: pairs that don't match whole match if this fraction of tiles does.
+    pub min_tile_match: Option<f64>,
+}
+
+impl ImageChecks {
+    pub const OFF: ImageChecks = ImageChecks {
+        color: ColorCheck::OFF,
+        min_tile_match: None,
+    };
+}
+
+/// Image ~ Colour signature check: pairs whose colours differ beyond `threshold`
+/// are flagged as colour variants, or don't match at all.
+#[derive(Debug, Clone, Copy)]
+pub struct ColorCheck {
+    pub mode: ColorMode,
+    pub threshold: u32,
+}
+
+impl ColorCheck {
+    pub const OFF: ColorCheck = ColorCheck {
+        mode: ColorMode::Off,
+        threshold: 0,
+    };
+}
+
+/// Multi-hash fusion over `PipelineResult::hashes` (one per `--hash-alg`, primary first).
+///
+/// Distances are normalised by each hash's length in bits, so `threshold` (in primary bits)
+/// applies to every algorithm alike.
+#[derive(Debug, Clone)]
+pub struct HashFusion {
+    pub algs: Vec<HashAlg>,
+    pub mode: Fusion,
+    /// One per algorithm; empty = equal weights.
+    pub weights: Vec<f64>,
+}
+
+impl HashFusion {
+    fn weight(&self, k: usize) -> f64 {
+        self.weights.get(k).copied().unwrap_or(1.0)
+    }
+
+    /// Fused distance (weighted mean, in primary bits) if the pair matches.
+    /// - All: every algorithm must be within threshold.
+    /// - Weighted: the weighted mean must be within threshold.
+    fn fuse(&self, dists: &[u32], bits: &[u32], threshold: u32) -> Option<u32> {
+        let n = dists.len();
+        // Normalised distance, in primary bits
+        let norm = |k: usize| dists[k] as f64 * bits[0] as f64 / bits[k] as f64;
+        let within = |d: f64| d <= threshold as f64 + 1e-9;
+        if self.mode == Fusion::All && !(0..n).all(|k| within(norm(k))) {
+            return None;
+        }
+        let total: f64 = (0..n).map(|k| self.weight(k)).sum();
+        let mean = (0..n).map(|k| self.weight(k) * norm(k)).sum::<f64>() / total;
+        if self.mode == Fusion::Weighted && !within(mean) {
+            return None;
+        }
+        Some(mean.round() as u32)
+    }
+}
+
+/// Video ~ Metadata pre-filter: pairs outside these bounds are never hash-compared.
+/// Items without metadata (images, older cache entries) always pass.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct MetaGate {
+    pub max_duration_diff: Option<Tolerance>,
+    pub max_aspect_diff: Option<Tolerance>,
+}
+
+enum Gate {
+    Compare,
+    Skip,
+    /// Duration out of range; with items ordered by duration, so is every later item.
+    Beyond,
+}
+
+impl MetaGate {
+    /// Order by duration (unknown first) so candidates past `max_duration_diff` can be cut off.
+    fn order(&self, items: &[PipelineResult], indices: &[usize]) -> Vec<usize> {
+        let mut ordered = indices.to_vec();
+        if self.max_duration_diff.is_some() {
+            let duration = |i: usize| items[i].meta.and_then(|m| m.duration_secs);
+            ordered.sort_by(|&a, &b| {
+                duration(a)
+                    .partial_cmp(&duration(b))
+                    .unwrap_or(Ordering::Equal)
+            });
+        }
+        ordered
+    }
+
+    fn check(&self, a: Option<VideoMeta>, b: Option<VideoMeta>) -> Gate {
+        let (Some(a), Some(b)) = (a, b) else {
+            return Gate::Compare;
+        };
+        if let (Some(t), Some(da), Some(db)) =
+            (self.max_duration_diff, a.duration_secs, b.duration_secs)
+            && !t.allows(da, db)
+        {
+            return Gate::Beyond;
+        }
+        if let (Some(t), Some(ra), Some(rb)) = (self.max_aspect_diff, a.aspect(), b.aspect())
+            && !t.allows(ra, rb)
+        {
+            return Gate::Skip;
+        }
+        Gate::Compare
+    }
+}
+
+/// Group `items[indices]` whose perceptual-hash Hamming distance <= `threshold` (bits),
+/// fusing the distances of every algorithm in `fusion`.
+/// With `audio_threshold`, pairs must also match on their audio fingerprint.
+/// Images are also compared on colour and tiles per `checks`.
+pub fn group_duplicates(
+    items: &[PipelineResult],
+    indices: &[usize],
+    threshold: u32,
+    fusion: &HashFusion,
+    checks: &ImageChecks,
+    audio_threshold: Option<u32>,
+    gate: &MetaGate,
+) -> Vec<Group> {
+    let indices = &gate.order(items, indices);
+
+    // Decode Image pHash (one per algorithm)
+    let decoded: Vec<Vec<img_hash::ImageHash>> = indices
+        .iter()
+        .map(|&i| {
+            items[i]
+                .hashes
+                .iter()
+                .map(|h| img_hash::ImageHash::from_base64(h).expect("valid base64 pHash"))
+                .collect()
+        })
+        .collect();
+    let bits: Vec<u32> = decoded
+        .first()
+        .map(|hs| hs.iter().map(|h| h.as_bytes().len() as u32 * 8).collect())
+        .unwrap_or_default();
+    let color = checks.color;
+    let colors: Vec<Option<Vec<u8>>> = indices
+        .iter()
+        .map(|&i| match color.mode {
+            ColorMode::Off => None,
+            _ => items[i].color.as_deref().and_then(color::decode),
+        })
+        .collect();
+    let tile_sets: Vec<Vec<_>> = indices
+        .iter()
+        .map(|&i| match checks.min_tile_match {
+            Some(_) => tiles::decode_tiles(items[i].tiles.as_deref().unwrap_or_default()),
+            None => Vec::new(),
+        })
+        .collect();
+    let audio = audio_threshold.map(|t| (decode_audio(items, indices), t));
+
+    group_greedy(items, indices, gate, |i, j| {
+        // Hamming distance in bits between two perceptual hashes, per algorithm
+        let dists: Vec<u32> = decoded[i]
+            .iter()
+            .zip(&decoded[j])
+            .map(|(a, b)| a.dist(b))
+            .collect();
+        let (dist, tile_match) = match fusion.fuse(&dists, &bits, threshold) {
+            Some(dist) => (dist, None),
+            // Crop-resistant: fall back to the tiles (primary algorithm)
+            None => {
+                let t = tiles::match_tiles(
+                    &tile_sets[i],
+                    &tile_sets[j],
+                    threshold,
+                    checks.min_tile_match?,
+                )?;
+                (t.mean_dist_bits.round() as u32, Some(t))
+            }
+        };
+        // Colour layouts only line up for whole-image matches
+        let color_dist = match (&colors[i], &colors[j], &tile_match) {
+            (Some(a), Some(b), None) => Some(color::distance(a, b)),
+            _ => None,
+        };
+        let color_variant = color_dist.is_some_and(|d| d > color.threshold);
+        if color_variant && color.mode == ColorMode::Exclude {
+            return None;
+        }
+        let audio_dist_bits = match &audio {
+            Some((hashes, t)) => Some(audio_match(hashes, i, j, *t)?),
+            None => None,
+        };
+        let alg_dists = match (dists.len(), &tile_match) {
+            (1, _) | (_, Some(_)) => Vec::new(),
+            _ => fusion.algs.iter().copied().zip(dists).collect(),
+        };
+        Some(Match {
+            dist_bits: dist,
+            alignment: None,
+            audio_dist_bits,
+            alg_dists,
+            color_dist,
+            color_variant,
+            tiles: tile_match,
+        })
+    })
+}
+
+/// Video ~ Group videos whose per-frame hash sequences align.
 ///
+/// Two videos match when, at the best time offset, at least `min_similarity` of the
+/// overlapping frames are within `threshold` bits. `dist_bits` is the mean distance
+/// of the matched frames.
+pub fn group_sequences(
+    items: &[PipelineResult],
+    indices: &[usize],
+    threshold: u32,
+    min_similarity: f64,
+    audio_threshold: Option<u32>,
+    gate: &MetaGate,
+) -> Vec<Group> {
+    let indices = &gate.order(items, indices);
+    let sequences: Vec<align::Sequence> = indices
+        .iter()
+        .map(|&i| align::decode_sequence(items[i].frames.as_deref().unwrap_or_default()))
+        .collect();
+    let audio = audio_threshold.map(|t| (decode_audio(items, indices), t));
+
+    group_greedy(items, indices, gate, |i, j| {
+        let a = align::align(&sequences[i], &sequences[j], threshold)
+            .filter(|a| a.similarity >= min_similarity)?;
+        let audio_dist_bits = match &audio {
+            Some((hashes, t)) => Some(audio_match(hashes, i, j, *t)?),
+            None => None,
+        };
+        Some(Match {
+            dist_bits: a.mean_dist_bits.round() as u32,
+            alignment: Some(a),
+            audio_dist_bits,
+            alg_dists: Vec::new(),
+            color_dist: None,
+            color_variant: false,
+            tiles: None,
+        })
+    })
+}
+
+/// Video ~ Group videos on their audio fingerprint alone (`dist_bits` is the audio distance).
+/// Videos without an audio stream never match.
+pub fn group_audio(
+    items: &[PipelineResult],
+    indices: &[usize],
+    audio_threshold: u32,
+    gate: &MetaGate,
+) -> Vec<Group> {
+    let indices = &gate.order(items, indices);
+    let hashes = decode_audio(items, indices);
+
+    group_greedy(items, indices, gate, |i, j| {
+        let dist = audio_match(&hashes, i, j, audio_threshold)?;
+        Some(Match {
+            dist_bits: dist,
+            alignment: None,
+            audio_dist_bits: Some(dist),
+            alg_dists: Vec::new(),
+            color_dist: None,
+            color_variant: false,
+            tiles: None,
+        })
+    })
+}
+
+fn decode_audio(items: &[PipelineResult], indices: &[usize]) -> Vec<Option<img_hash::ImageHash>> {
+    indices
+        .iter()
+        .map(|&i| {
+            items[i].audio_hash.as_deref().map(|h| {
+                img_hash::ImageHash::from_base64(h).expect("valid base64 audio fingerprint")
+            })
+        })
+        .collect()
+}
+
+/// Audio distance (at the best offset) if both have a fingerprint and it's within `threshold`.
+fn audio_match(
+    hashes: &[Option<img_hash::ImageHash>],
+    i: usize,
+    j: usize,
+    threshold: u32,
+) -> Option<u32> {
+    let dist = audio::distance(hashes[i].as_ref()?, hashes[j].as_ref()?);
+    (dist <= threshold).then_some(dist)
+}
+
 /// Greedy Clustering:
 /// - Complexity: Worst case O(n²).
 //  - Keep a boolean `visited` list.
-//  - For each unvisited image i, create a new group seeded with i.
-//  - Compare i to j>i and add j if Hamming distance <= threshold.
+//  - For each unvisited item i, create a new group seeded with i.
+//  - Compare i to j>i with `compare(i, j)` (positions in `indices`),
+//    adding j if it passes the `MetaGate` and `compare` returns a `Match`.
 //  - Mark added items visited so they don’t seed new groups.
 //
 // Sorting:
 /// - Members in each group are sorted by ascending distance (most similar first)
-/// - Groups are sorted by size desc, then by avg distance asc
-pub fn group_duplicates(items: &[PipelineResult], threshold: u32) -> Vec<Group> {
-    let n = items.len();
+/// - Member indices refer to the full item list (`indices[i]`)
+fn group_greedy<F>(
+    items: &[PipelineResult],
+    indices: &[usize],
+    gate: &MetaGate,
+    compare: F,
+) -> Vec<Group>
+where
+    F: Fn(usize, usize) -> Option<Match>,
+{
+    let n = indices.len();
     if n < 2 {
         return Vec::new();
     }
 
     // Progress Start
-    let total_pairs = (n as u64) * ((n.saturating_sub(1)) as u64) / 2;
-    let comparing_pb = progress::bar(total_pairs as u64, "Comparing");
-
-    // Decode Image pHash
-    let decoded: Vec<img_hash::ImageHash> = items
-        .iter()
-        .map(|r| img_hash::ImageHash::from_base64(&r.perceptual_hash).expect("valid base64 pHash"))
-        .collect();
+    let total_pairs = (n as u64) * (n.saturating_sub(1) as u64) / 2;
+    let comparing_pb = progress::bar(total_pairs, "Comparing");
 
     let mut visited = vec![false; n];
     let mut groups: Vec<Group> = Vec::new();
@@ -52,29 +384,47 @@ pub fn group_duplicates(items: &[PipelineResult], threshold: u32) -> Vec<Group>
 
         // Create new Group
         let mut members = vec![GroupMember {
-            index: i,
+            index: indices[i],
             dist_bits: 0,
+            alignment: None,
+            audio_dist_bits: None,
+            alg_dists: Vec::new(),
+            color_dist: None,
+            color_variant: false,
+            tiles: None,
         }];
 
         // Avoid duplicate and reverse comparisons by
         // comparing the current item i with every later item j
-        for j in (i + 1)..n {
+        for (j, seen) in visited.iter_mut().enumerate().skip(i + 1) {
             comparing_pb.inc(1);
 
             // Skip if visited
-            if visited[j] {
+            if *seen {
                 continue;
             }
 
-            // Hamming distance in bits between two perceptual hashes
-            let dist = decoded[i].dist(&decoded[j]);
+            // Metadata pre-filter
+            match gate.check(items[indices[i]].meta, items[indices[j]].meta) {
+                Gate::Compare => {}
+                Gate::Skip => continue,
+                Gate::Beyond => {
+                    comparing_pb.inc((n - j - 1) as u64);
+                    break;
+                }
+            }
 
-            // Compare against threshold
-            if dist <= threshold {
-                visited[j] = true;
+            if let Some(m) = compare(i, j) {
+                *seen = true;
                 members.push(GroupMember {
-                    index: j,
-                    dist_bits: dist,
+                    index: indices[j],
+                    dist_bits: m.dist_bits,
+                    alignment: m.alignment,
+                    audio_dist_bits: m.audio_dist_bits,
+                    alg_dists: m.alg_dists,
+                    color_dist: m.color_dist,
+                    color_variant: m.color_variant,
+                    tiles: m.tiles,
                 });
             }
         }
@@ -93,6 +443,12 @@ pub fn group_duplicates(items: &[PipelineResult], threshold: u32) -> Vec<Group>
     // Clear Progress Bar
     comparing_pb.finish_and_clear();
 
+    sort_groups(&mut groups);
+    groups
+}
+
+/// Groups are sorted by avg distance asc
+pub fn sort_groups(groups: &mut [Group]) {
     // Sort Groups By Avg
     groups.sort_by(|a, b| a.avg_dist_bits.partial_cmp(&b.avg_dist_bits).unwrap());
 
@@ -103,8 +459,6 @@ pub fn group_duplicates(items: &[PipelineResult], threshold: u32) -> Vec<Group>
     //         .cmp(&a.members.len())
     //         .then_with(|| a.avg_dist_bits.partial_cmp(&b.avg_dist_bits).unwrap())
     // });
-
-    groups
 }
 
 // Calculate average hamming distance, excluding the first members 0
@@ -115,3 +469,61 @@ pub fn avg_dist(members: &[GroupMember]) -> f64 {
     let sum: u128 = members.iter().skip(1).map(|m| m.dist_bits as u128).sum();
     (sum as f64) / ((members.len() - 1) as f64)
 }
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+
+    use super::*;
+    use crate::types::MediaType;
+
+    fn video(duration_secs: Option<f64>) -> PipelineResult {
+        PipelineResult {
+            path: format!("{duration_secs:?}.mp4").into(),
+            blake3: String::new(),
+            hashes: Vec::new(),
+            media_type: MediaType::Video,
+            frames: None,
+            audio_hash: None,
+            meta: Some(VideoMeta {
+                duration_secs,
+                width: 1920,
+                height: 1080,
+                fps: None,
+            }),
+            color: None,
+            tiles: None,
+            info: None,
+        }
+    }
+
+    #[test]
+    fn duration_gate_stops_at_the_first_longer_video() {
+        let items = [
+            video(Some(31.0)),
+            video(Some(10.0)),
+            video(None),
+            video(Some(30.0)),
+            video(Some(10.5)),
+        ];
+        let gate = MetaGate {
+            max_duration_diff: Some(Tolerance::Absolute(1.0)),
+            max_aspect_diff: None,
+        };
+        let indices = gate.order(&items, &[0, 1, 2, 3, 4]);
+        // Unknown duration first, then shortest to longest
+        assert_eq!(indices, [2, 1, 4, 3, 0]);
+
+        let compared = RefCell::new(Vec::new());
+        let groups = group_greedy(&items, &indices, &gate, |i, j| {
+            compared.borrow_mut().push((indices[i], indices[j]));
+            None
+        });
+        assert!(groups.is_empty());
+        // 10s and 10.5s never reach 30s or 31s; the unknown duration is compared with all
+        assert_eq!(
+            compared.into_inner(),
+            [(2, 1), (2, 4), (2, 3), (2, 0), (1, 4), (3, 0)]
+        );
+    }
+}
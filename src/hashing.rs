@@ -1,4 +1,5 @@
-use crate::{errors::HashError, types as T};
+use crate::{errors::HashError, preprocess::Preprocess, types as T};
+use img_hash::image::{DynamicImage, GenericImageView, ImageFormat, io::Reader};
 use img_hash::{HashAlg as ImgAlg, Hasher, HasherConfig};
 use std::{fs, io::Read, path::Path};
 
@@ -37,8 +38,116 @@ pub fn build_hasher(alg: T::HashAlg, w: u32, h: u32) -> Hasher {
         .to_hasher()
 }
 
-/// Perceptual Hash Image
-pub fn compute_perceptual_hash(path: &Path, hasher: &Hasher) -> Result<String, HashError> {
-    let img = img_hash::image::open(path)?; // ImageError -> HashError
-    Ok(hasher.hash_image(&img).to_base64())
+/// One re-usable Hasher per configured algorithm, in `cfg.hash_algs` order.
+pub fn build_hashers(cfg: &T::AppConfig) -> Vec<Hasher> {
+    cfg.hash_algs
+        .iter()
+        .map(|&alg| build_hasher(alg, cfg.hash_w, cfg.hash_h))
+        .collect()
+}
+
+/// Open an image and pre-process it for hashing (e.g. border auto-crop),
+/// with its original dimensions and format.
+pub fn open_image(
+    path: &Path,
+    preprocess: &Preprocess,
+) -> Result<(DynamicImage, T::MediaInfo), HashError> {
+    let reader = Reader::open(path)?.with_guessed_format()?;
+    let format = format_name(reader.format());
+    let img = reader.decode()?; // ImageError -> HashError
+    let (width, height) = img.dimensions();
+    let info = T::MediaInfo {
+        width,
+        height,
+        format,
+        codec: None,
+    };
+    Ok((preprocess.apply(img), info))
+}
+
+/// Dimensions and format from the image header only.
+pub fn image_info(path: &Path) -> Result<T::MediaInfo, HashError> {
+    let reader = Reader::open(path)?.with_guessed_format()?;
+    let format = format_name(reader.format());
+    let (width, height) = reader.into_dimensions()?;
+    Ok(T::MediaInfo {
+        width,
+        height,
+        format,
+        codec: None,
+    })
+}
+
+/// e.g. "jpeg", "png"; empty if unknown.
+fn format_name(format: Option<ImageFormat>) -> String {
+    format
+        .map(|f| format!("{f:?}").to_lowercase())
+        .unwrap_or_default()
+}
+
+/// Perceptual Hash a pre-processed image; one hash per hasher.
+pub fn compute_perceptual_hashes(img: &DynamicImage, hashers: &[Hasher]) -> Vec<String> {
+    hashers
+        .iter()
+        .map(|h| h.hash_image(img).to_base64())
+        .collect()
+}
+
+/// Hash bytes (as stored, base64-decoded) as lowercase hex.
+pub fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{b:02x}")).collect()
+}
+
+/// Hash bytes as a string of '0'/'1'. `img_hash` packs bit `i` of a hash into byte `i / 8`,
+/// least significant bit first; `LsbFirst` keeps that order, `MsbFirst` reads like the hex.
+pub fn to_bit_string(bytes: &[u8], order: T::BitOrder) -> String {
+    bytes
+        .iter()
+        .flat_map(|&b| {
+            (0..8).map(move |i| match order {
+                T::BitOrder::LsbFirst => (b >> i) & 1,
+                T::BitOrder::MsbFirst => (b >> (7 - i)) & 1,
+            })
+        })
+        .map(|bit| if bit == 1 { '1' } else { '0' })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BYTES: [u8; 3] = [0x01, 0xa0, 0x3c];
+
+    #[test]
+    fn lsb_first_follows_img_hash_bit_order() {
+        let bits = to_bit_string(&BYTES, T::BitOrder::LsbFirst);
+        assert_eq!(bits, "100000000000010100111100");
+        // Bit i of the hash is byte i / 8, bit i % 8
+        for (i, c) in bits.chars().enumerate() {
+            let bit = (BYTES[i / 8] >> (i % 8)) & 1;
+            assert_eq!(c, if bit == 1 { '1' } else { '0' });
+        }
+    }
+
+    #[test]
+    fn msb_first_reads_like_the_hex() {
+        let bits = to_bit_string(&BYTES, T::BitOrder::MsbFirst);
+        assert_eq!(bits, "000000011010000000111100");
+        let hex = to_hex(&BYTES);
+        assert_eq!(hex, "01a03c");
+        let from_hex: String = hex
+            .chars()
+            .map(|c| format!("{:04b}", c.to_digit(16).unwrap()))
+            .collect();
+        assert_eq!(bits, from_hex);
+    }
+
+    #[test]
+    fn every_bit_is_written() {
+        for order in [T::BitOrder::LsbFirst, T::BitOrder::MsbFirst] {
+            assert_eq!(to_bit_string(&[], order), "");
+            assert_eq!(to_bit_string(&[0xff; 4], order), "1".repeat(32));
+        }
+    }
 }
@@ -1,4 +1,10 @@
-use crate::{cache, errors::AppError, hashing, progress, types};
+use crate::{
+    animation, cache, color,
+    errors::{AppError, HashError},
+    hashing,
+    preprocess::Preprocess,
+    progress, tiles, types,
+};
 use indicatif::ParallelProgressIterator;
 use rayon::prelude::*;
 use std::{
@@ -6,49 +12,32 @@ use std::{
     sync::{Arc, Mutex},
 };
 
-/// Run Image Pipeline in parallel using Rayon
+/// Run Image Pipeline in parallel using Rayon (current pool).
+/// Returns the hashed files and the files that failed.
 pub fn run(
     cfg: types::AppConfig,
     cache: &mut types::CacheFile,
-) -> Result<Vec<types::PipelineResult>, AppError> {
+) -> Result<(Vec<types::PipelineResult>, Vec<types::FileError>), AppError> {
     // Progress Start
     let hashing_pb = progress::bar(cfg.media_paths.len() as u64, "Hashing");
 
     // Wrap the caller-owned cache in Arc<Mutex<_>> for thread-safe mutation during parallel work.
     let cache_arc = Arc::new(Mutex::new(std::mem::take(cache)));
 
-    // Drive the parallel work with an optional fixed-size pool using your helper.
-    let results: Vec<Result<types::PipelineResult, (PathBuf, AppError)>> = {
-        if cfg.parallelism > 0 {
-            // Configure Rayon
-            let pool = rayon::ThreadPoolBuilder::new()
-                .num_threads(cfg.parallelism)
-                .build()?;
-            // Run
-            pool.install(|| {
-                cfg.media_paths
-                    .par_iter()
-                    .progress_with(hashing_pb.clone())
-                    .map(|p| process_path(p, &cfg, &cache_arc).map_err(|e| (p.clone(), e)))
-                    .collect()
-            })
-        } else {
-            // Let Rayon decide
-            cfg.media_paths
-                .par_iter()
-                .progress_with(hashing_pb.clone())
-                .map(|p| process_path(p, &cfg, &cache_arc).map_err(|e| (p.clone(), e)))
-                .collect()
-        }
-    };
-
-    // Collect
-    let collected: Vec<types::PipelineResult> =
-        results.into_iter().filter_map(|r| r.ok()).collect();
+    // Parallel work runs on the caller's Rayon pool (see `main::run_in_pool`).
+    let results: Vec<Result<types::PipelineResult, (PathBuf, AppError)>> = cfg
+        .media_paths
+        .par_iter()
+        .progress_with(hashing_pb.clone())
+        .map(|p| process_path(p, &cfg, &cache_arc).map_err(|e| (p.clone(), e)))
+        .collect();
 
     // Clear Progress
     hashing_pb.finish_and_clear();
 
+    // Collect Successes and Failures (reported as they're split)
+    let (collected, errors) = progress::collect(results);
+
     // Write the updated cache back into the caller-owned value (Arc Unwrap).
     let updated = Arc::into_inner(cache_arc)
         .expect("no other Arc clones left")
@@ -56,7 +45,7 @@ pub fn run(
         .unwrap();
     *cache = updated;
 
-    Ok(collected)
+    Ok((collected, errors))
 }
 
 /// Process a single file (fileHash + perceptualHash). Called by image_pipeline::run
@@ -71,43 +60,118 @@ fn process_path(
     // Cache Hit? Return Early - Single Thread (Read Lock)
     {
         let cm = cache_arc.lock().unwrap();
-        if let Some(entry) = cache::lookup(&cm, &key, cfg, /*is_video=*/ false) {
+        if let Some(entries) = cache::lookup(&cm, &key, cfg, types::MediaType::Image) {
             // Return PipelineResult
-            return Ok(types::PipelineResult {
-                path: p.to_path_buf(),
-                blake3: key.clone(),
-                perceptual_hash: entry.perceptual_hash.clone(),
-            });
+            return Ok(to_result(p, key, entries, cfg));
+        }
+        // Failed in a previous run? Skip unless retrying.
+        if let Some(failure) = cache::lookup_failure(&cm, &key, cfg, types::MediaType::Image) {
+            return Err(AppError::PreviouslyFailed(failure.error));
         }
     }
 
-    // Compute Perceptual Hash - Parallel
-    let hasher = hashing::build_hasher(cfg.hash_alg, cfg.hash_w, cfg.hash_h);
-    let phash_b64 = hashing::compute_perceptual_hash(p, &hasher)?;
+    // Compute Perceptual Hash - Parallel (failures are recorded in the cache)
+    let entries = match hash_image(p, cfg) {
+        Ok(entries) => entries,
+        Err(e) => {
+            let e = AppError::from(e);
+            let mut cm = cache_arc.lock().unwrap();
+            cache::record_failure(&mut cm, key, cfg, types::MediaType::Image, &e);
+            return Err(e);
+        }
+    };
 
     // Upsert - Single Thread (Write Lock)
     {
         let mut cm = cache_arc.lock().unwrap();
-        cache::upsert(
-            &mut cm,
-            key.clone(),
-            types::CacheEntry {
-                hash_alg: cfg.hash_alg,
-                hash_w: cfg.hash_w,
-                hash_h: cfg.hash_h,
-                perceptual_hash: phash_b64.clone(),
-                sample_start: None,
-                sample_count: None,
-                sample_window: None,
-                aggregation: None,
-            },
-        );
+        for entry in &entries {
+            cache::upsert(&mut cm, key.clone(), entry.clone());
+        }
     }
 
     // Return
-    Ok(types::PipelineResult {
+    Ok(to_result(p, key, entries, cfg))
+}
+
+/// `entries` are one per `--hash-alg`; the primary's frames are kept.
+fn to_result(
+    p: &Path,
+    key: String,
+    entries: Vec<types::CacheEntry>,
+    cfg: &types::AppConfig,
+) -> types::PipelineResult {
+    let frames = entries[0].frames.clone();
+    let color = entries[0].color.clone();
+    let tiles = entries[0].tiles.clone();
+    let info = match (cfg.with_metadata, &entries[0].media) {
+        (true, Some(media)) => types::FileInfo::stat(p, media.clone()).ok(),
+        _ => None,
+    };
+    types::PipelineResult {
         path: p.to_path_buf(),
         blake3: key,
-        perceptual_hash: phash_b64,
+        hashes: entries.into_iter().map(|e| e.perceptual_hash).collect(),
+        media_type: types::MediaType::Image,
+        frames,
+        audio_hash: None,
+        meta: None,
+        color,
+        tiles,
+        info,
+    }
+}
+
+/// Animated GIF/APNG/WebP: sampled frames aggregated like a video. Anything else: one hash
+/// (plus tile hashes with `--crop-resistant`).
+/// Returns one entry per `--hash-alg`, from a single decode.
+fn hash_image(p: &Path, cfg: &types::AppConfig) -> Result<Vec<types::CacheEntry>, HashError> {
+    let hashers = hashing::build_hashers(cfg);
+    let entry = |alg, perceptual_hash, media| types::CacheEntry {
+        params: types::HashParams::from_cfg(cfg, alg, types::MediaType::Image),
+        perceptual_hash,
+        animated: false,
+        frames: None,
+        audio: false,
+        audio_hash: None,
+        meta: None,
+        color: None,
+        tiles: None,
+        media,
+    };
+
+    Ok(match animation::hash_animated(p, cfg, &hashers)? {
+        Some(anim) => {
+            let media = hashing::image_info(p).ok();
+            cfg.hash_algs
+                .iter()
+                .zip(anim.hashes)
+                .map(|(&alg, h)| types::CacheEntry {
+                    params: types::HashParams::for_animation(cfg, alg),
+                    animated: true,
+                    frames: Some(h.frames),
+                    color: anim.color.clone(),
+                    ..entry(alg, h.hash.to_base64(), media.clone())
+                })
+                .collect()
+        }
+        None => {
+            let (img, media) = hashing::open_image(p, &Preprocess::from_cfg(cfg))?;
+            let color = match cfg.color {
+                true => color::signature(std::slice::from_ref(&img)),
+                false => None,
+            };
+            cfg.hash_algs
+                .iter()
+                .zip(&hashers)
+                .zip(hashing::compute_perceptual_hashes(&img, &hashers))
+                .map(|((&alg, hasher), hash)| types::CacheEntry {
+                    color: color.clone(),
+                    tiles: cfg
+                        .crop_resistant
+                        .then(|| tiles::tile_hashes(&img, hasher, cfg.hash_w, cfg.hash_h)),
+                    ..entry(alg, hash, Some(media.clone()))
+                })
+                .collect()
+        }
     })
 }
@@ -1,74 +1,203 @@
 // Declare .rs files as the module crate::files
+mod actions;
+mod animation;
 mod args;
 mod cache;
+mod color;
+mod commands;
+mod contact_sheet;
+mod diff;
 mod errors;
 mod grouping;
 mod hashing;
 mod image_pipeline;
 mod output;
+mod preprocess;
 mod progress;
+mod query;
+mod report;
+mod review;
+mod run;
 mod scan;
+mod script;
+mod tiles;
 mod types;
 mod video;
 
-use crate::args::Args;
-use crate::scan::scan_files;
-use clap::Parser;
+use crate::args::{Cli, Command, GroupArgs, MatchOpts, OutputOpts, ScanArgs};
+use clap::CommandFactory;
+use std::time::SystemTime;
 
 fn main() -> Result<(), errors::AppError> {
-    let args = Args::parse();
+    let started = SystemTime::now();
+    let cli = Cli::parse_checked();
+    match (cli.command, cli.scan) {
+        (Some(Command::Scan(args)), _) | (None, Some(args)) => scan(&args, started),
+        (Some(Command::Hash(args)), _) => commands::hash(&args),
+        (Some(Command::Group(args)), _) => group(&args, started),
+        (Some(Command::Diff(args)), _) => diff::run(&args),
+        (Some(Command::Query(args)), _) => query::run(&args),
+        (Some(Command::Cache(args)), _) => commands::cache(&args),
+        (Some(Command::Review(args)), _) => review::run(&args),
+        // clap requires DIRECTORY when there is no subcommand
+        (None, None) => unreachable!(),
+    }
+}
 
-    // Scan
-    let extensions: &[&str] = match args.video {
-        true => types::VIDEO_EXTENSIONS,
-        false => types::IMAGE_EXTENSIONS,
-    };
-    let media_paths = scan_files(&args.directory, extensions);
-    eprintln!(
-        "Found {} file(s) under \"{}\"",
-        media_paths.len(),
-        args.directory.display()
-    );
-    if media_paths.is_empty() {
+/// `scan`: hash a directory, then write the requested outputs.
+fn scan(args: &ScanArgs, started: SystemTime) -> Result<(), errors::AppError> {
+    let Some(hashed) = run::hash_files(args)? else {
         return Ok(());
+    };
+    if let Some(path) = &args.save_hashes {
+        run::save_hashes(path, &hashed)?;
+        eprintln!("\nHashes saved to \"{}\"", path.display());
     }
+    let scan = Results {
+        threshold: args.threshold,
+        match_frames: args.match_frames,
+        matching: &args.matching,
+        output: &args.output,
+    };
+    scan.write(&hashed, started)
+}
 
-    // Build AppConfig
-    let app_cfg = types::AppConfig {
-        media_paths,
-        hash_alg: args.hash_alg,
-        hash_w: args.hash_w,
-        hash_h: args.hash_h,
-        parallelism: args.parallel,
-        sample_start: args.sample_start,
-        sample_count: args.sample_count,
-        sample_window: args.sample_window,
-        aggregation: args.aggregation,
+/// `group`: regroup saved hashes, then write the requested outputs.
+fn group(args: &GroupArgs, started: SystemTime) -> Result<(), errors::AppError> {
+    let hashed = run::load_hashes(&args.hashes)?;
+    // Matching on hashes the scan didn't compute would silently match nothing
+    let (cfg, matching) = (&hashed.cfg, &args.matching);
+    let missing = [
+        (
+            matching.color != types::ColorMode::Off && !cfg.color,
+            "--color",
+        ),
+        (
+            matching.crop_resistant && !cfg.crop_resistant,
+            "--crop-resistant",
+        ),
+        (
+            matching.match_on != types::MatchOn::Visual && !cfg.audio,
+            "--match-on",
+        ),
+        // Clips need dense frame hashes (a sample interval), not the few of even sampling
+        (
+            matching.video_compare == types::VideoCompare::Clips && cfg.sample_interval <= 0.0,
+            "--video-compare clips",
+        ),
+    ];
+    if let Some((_, flag)) = missing.iter().find(|(missing, _)| *missing) {
+        Cli::command()
+            .error(
+                clap::error::ErrorKind::ArgumentConflict,
+                format!("{flag} needs hashes saved by a scan with {flag}"),
+            )
+            .exit();
+    }
+    eprintln!(
+        "Loaded {} file(s) hashed under \"{}\"",
+        hashed.results.len(),
+        hashed.directory.display()
+    );
+    let regroup = Results {
+        threshold: args.threshold,
+        match_frames: args.match_frames,
+        matching: &args.matching,
+        output: &args.output,
     };
+    regroup.write(&hashed, started)
+}
 
-    // Cache
-    let cache_path = std::env::current_exe()?.with_file_name(&args.cache_file);
-    let mut cache = cache::load_cache(&cache_path)?;
+/// How hashed files are matched and where the results go.
+struct Results<'a> {
+    threshold: u32,
+    match_frames: bool,
+    matching: &'a MatchOpts,
+    output: &'a OutputOpts,
+}
 
-    // Run Image Pipeline (mutates `cache` in place)
-    let pipeline_results: Vec<types::PipelineResult> = match args.video {
-        true => video::pipeline::run(app_cfg, &mut cache)?,
-        false => image_pipeline::run(app_cfg, &mut cache)?,
-    };
+impl Results<'_> {
+    fn write(&self, hashed: &run::Hashed, started: SystemTime) -> Result<(), errors::AppError> {
+        let (threshold, matching, out) = (self.threshold, self.matching, self.output);
+        let pipeline_results = &hashed.results;
+        let app_cfg = &hashed.cfg;
+        let format = out.format();
+        // Run metadata for the JSON envelope
+        let run = || output::RunInfo {
+            config: output::JsonConfig::new(hashed, threshold, matching),
+            started,
+            files_found: hashed.files_found,
+            errors: &hashed.errors,
+        };
 
-    // Group Near Duplicates (Calculate Hamming Distance)
-    let groups = grouping::group_duplicates(&pipeline_results, args.threshold);
+        // Frame Matching reports image -> video frame matches instead of groups
+        if self.match_frames {
+            let (images, videos) = hashed.by_media_type();
+            let matches = video::frame_match::match_images_to_frames(
+                pipeline_results,
+                &images,
+                &videos,
+                threshold,
+            );
+            let mut sink = output::open_sink(out.output.as_deref())?;
+            output::write_frames(&mut sink, &matches, pipeline_results, format, run())?;
+            report_written(out);
+            return Ok(());
+        }
 
-    // Output or Print
-    if let Some(output_path) = &args.output {
-        output::write_json_file(&groups, &pipeline_results, output_path)?;
-        eprintln!("\nResults written to \"{}\"", output_path.display());
-    } else {
-        output::print(&groups, &pipeline_results, args.json);
-    }
+        // Clip Containment reports clip -> source matches instead of groups
+        if matching.video_compare == types::VideoCompare::Clips {
+            let (_, videos) = hashed.by_media_type();
+            let matches = video::clips::find_clips(
+                pipeline_results,
+                &videos,
+                threshold,
+                matching.min_similarity,
+            );
+            let mut sink = output::open_sink(out.output.as_deref())?;
+            output::write_clips(&mut sink, &matches, pipeline_results, format, run())?;
+            report_written(out);
+            return Ok(());
+        }
+
+        // Group Near Duplicates within each media type
+        let groups = run::group(threshold, matching, hashed);
+
+        // HTML Report
+        if let Some(report_path) = &out.report {
+            report::write_report(&groups, pipeline_results, app_cfg, report_path)?;
+            eprintln!("\nReport written to \"{}\"", report_path.display());
+        }
+
+        // Contact Sheets
+        if let Some(sheets_dir) = &out.contact_sheets {
+            contact_sheet::write_contact_sheets(&groups, pipeline_results, app_cfg, sheets_dir)?;
+            eprintln!("\nContact sheets written to \"{}\"", sheets_dir.display());
+        }
 
-    // Save Cache
-    cache::save_cache(&cache_path, &cache)?;
+        // Cleanup Script
+        if let Some(script_path) = &out.emit_script {
+            let opts = script::ScriptOptions {
+                keep: out.keep,
+                quarantine: out.quarantine.as_deref(),
+                root: &hashed.directory,
+            };
+            script::write_script(&groups, pipeline_results, &opts, script_path)?;
+            eprintln!("\nCleanup script written to \"{}\"", script_path.display());
+        }
 
-    Ok(())
+        // Output or Print
+        let mut sink = output::open_sink(out.output.as_deref())?;
+        output::write_groups(&mut sink, &groups, pipeline_results, format, run())?;
+        report_written(out);
+
+        Ok(())
+    }
+}
+
+/// Note where `--output` went (stderr, so stdout stays clean).
+fn report_written(out: &OutputOpts) {
+    if let Some(output_path) = &out.output {
+        eprintln!("\nResults written to \"{}\"", output_path.display());
+    }
 }
@@ -1,79 +1,993 @@
-use crate::{grouping::Group, types::PipelineResult};
-use serde::Serialize;
+use crate::{
+    args::MatchOpts,
+    grouping::{Group, GroupMember},
+    run::Hashed,
+    tiles::Region,
+    types::{FileError, MediaType, OutputFormat, PipelineResult, TOOL_VERSION},
+    video::{clips::ClipMatch, frame_match::FrameMatch},
+};
+use clap::ValueEnum;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 use std::fs;
+use std::io::{self, BufWriter, Write};
 use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
 
-#[derive(Serialize)]
-struct JsonGroup {
-    avg_distance_bits: f64,
-    files: Vec<JsonFile>,
+/// Bumped on breaking changes to the JSON output (see `schema/output.schema.json`).
+pub const SCHEMA_VERSION: u32 = 1;
+
+/// JSON output (`--format json`): run metadata wrapped around the results.
+/// Exactly one of `groups`, `clips` or `frames` is present, depending on the mode.
+#[derive(Serialize, JsonSchema)]
+#[schemars(title = "rust-phash output")]
+struct Envelope {
+    schema_version: u32,
+    tool_version: String,
+    config: JsonConfig,
+    /// RFC 3339, UTC
+    started_at: String,
+    stats: JsonStats,
+    /// Duplicate groups; the first file of each group is the seed distances are measured to.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    groups: Option<Vec<JsonGroup>>,
+    /// `--video-compare clips`: short videos found inside longer ones.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    clips: Option<Vec<JsonClip>>,
+    /// `--match-frames`: images found in video frames.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    frames: Option<Vec<JsonFrameMatch>>,
+    /// Files that could not be hashed (and so are missing from the results).
+    errors: Vec<JsonError>,
+}
+
+/// The parts of a JSON results file that `review` reads back, with the same group types.
+#[derive(Deserialize)]
+pub struct ResultsFile {
+    pub schema_version: u32,
+    pub groups: Option<Vec<JsonGroup>>,
+}
+
+/// Settings that affect the results.
+#[derive(Serialize, JsonSchema)]
+pub struct JsonConfig {
+    directory: String,
+    media: Vec<MediaType>,
+    /// First is primary
+    hash_algs: Vec<String>,
+    hash_w: u32,
+    hash_h: u32,
+    threshold: u32,
+    fusion: String,
+    autocrop: bool,
+    alpha: String,
+    color: String,
+    crop_resistant: bool,
+    video_compare: String,
+    aggregation: String,
+    match_on: String,
+    sample_count: usize,
+    sample_interval: f64,
 }
 
-#[derive(Serialize)]
-struct JsonFile {
+impl JsonConfig {
+    pub fn new(hashed: &Hashed, threshold: u32, matching: &MatchOpts) -> Self {
+        let cfg = &hashed.cfg;
+        JsonConfig {
+            directory: hashed.directory.display().to_string(),
+            media: hashed.media.clone(),
+            hash_algs: cfg.hash_algs.iter().map(|a| a.name()).collect(),
+            hash_w: cfg.hash_w,
+            hash_h: cfg.hash_h,
+            threshold,
+            fusion: value_name(&matching.fusion),
+            autocrop: cfg.autocrop,
+            alpha: value_name(&cfg.alpha),
+            color: value_name(&matching.color),
+            crop_resistant: cfg.crop_resistant,
+            video_compare: value_name(&matching.video_compare),
+            aggregation: value_name(&cfg.aggregation),
+            match_on: value_name(&matching.match_on),
+            sample_count: cfg.sample_count,
+            sample_interval: cfg.sample_interval,
+        }
+    }
+}
+
+fn value_name<V: ValueEnum>(v: &V) -> String {
+    v.to_possible_value()
+        .map(|p| p.get_name().to_string())
+        .unwrap_or_default()
+}
+
+#[derive(Serialize, JsonSchema)]
+struct JsonStats {
+    files_found: usize,
+    files_hashed: usize,
+    files_failed: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    groups: Option<usize>,
+    /// Files in groups beyond each group's seed.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    duplicate_files: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    matches: Option<usize>,
+    elapsed_secs: f64,
+}
+
+#[derive(Serialize, JsonSchema)]
+struct JsonError {
     path: String,
-    blake3: String,
+    error: String,
+}
+
+/// What the run was and how it went, for the JSON envelope.
+pub struct RunInfo<'a> {
+    pub config: JsonConfig,
+    pub started: SystemTime,
+    pub files_found: usize,
+    pub errors: &'a [FileError],
+}
+
+/// Results of one mode, placed in the envelope.
+enum Results {
+    Groups(Vec<JsonGroup>),
+    Clips(Vec<JsonClip>),
+    Frames(Vec<JsonFrameMatch>),
+}
+
+impl RunInfo<'_> {
+    fn envelope(self, items: &[PipelineResult], results: Results) -> Envelope {
+        let elapsed_secs = self.started.elapsed().map_or(0.0, |d| d.as_secs_f64());
+        let mut stats = JsonStats {
+            files_found: self.files_found,
+            files_hashed: items.len(),
+            files_failed: self.errors.len(),
+            groups: None,
+            duplicate_files: None,
+            matches: None,
+            elapsed_secs,
+        };
+        let (mut groups, mut clips, mut frames) = (None, None, None);
+        match results {
+            Results::Groups(g) => {
+                stats.groups = Some(g.len());
+                stats.duplicate_files = Some(g.iter().map(|g| g.files.len() - 1).sum());
+                groups = Some(g);
+            }
+            Results::Clips(c) => {
+                stats.matches = Some(c.len());
+                clips = Some(c);
+            }
+            Results::Frames(f) => {
+                stats.matches = Some(f.len());
+                frames = Some(f);
+            }
+        }
+        Envelope {
+            schema_version: SCHEMA_VERSION,
+            tool_version: TOOL_VERSION.to_string(),
+            config: self.config,
+            started_at: rfc3339(self.started),
+            stats,
+            groups,
+            clips,
+            frames,
+            errors: self
+                .errors
+                .iter()
+                .map(|e| JsonError {
+                    path: e.path.display().to_string(),
+                    error: e.error.clone(),
+                })
+                .collect(),
+        }
+    }
+}
+
+/// `YYYY-MM-DDTHH:MM:SSZ` (UTC, civil-from-days).
+pub fn rfc3339(t: SystemTime) -> String {
+    let secs = t.duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs());
+    let (days, rem) = (secs / 86_400, secs % 86_400);
+    let z = days as i64 + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1_460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    format!(
+        "{year:04}-{month:02}-{day:02}T{:02}:{:02}:{:02}Z",
+        rem / 3_600,
+        rem % 3_600 / 60,
+        rem % 60
+    )
+}
+
+#[derive(Serialize, Deserialize, JsonSchema)]
+pub struct JsonGroup {
+    pub avg_distance_bits: f64,
+    pub files: Vec<JsonFile>,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema)]
+pub struct JsonFile {
+    pub path: String,
+    pub media_type: MediaType,
+    pub blake3: String,
+    /// Hamming distance to the group seed (primary `--hash-alg`).
+    pub dist_bits: u32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub offset_secs: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub similarity: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub audio_dist_bits: Option<u32>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub alg_dist_bits: BTreeMap<String, u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub color_dist: Option<u32>,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub color_variant: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tiles: Option<JsonTiles>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub metadata: Option<JsonMetadata>,
+}
+
+/// `--with-metadata`: collected while hashing, so consumers need not re-stat or re-decode.
+#[derive(Serialize, Deserialize, JsonSchema)]
+pub struct JsonMetadata {
+    pub size_bytes: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub modified_secs: Option<u64>,
+    pub width: u32,
+    pub height: u32,
+    pub format: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub codec: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub duration_secs: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub fps: Option<f64>,
+    /// Base64, primary `--hash-alg`
+    pub perceptual_hash: String,
+}
+
+/// Crop-resistant match; regions are `[x, y, w, h]` in original pixels.
+#[derive(Serialize, Deserialize, JsonSchema)]
+pub struct JsonTiles {
+    pub matched: usize,
+    pub total: usize,
+    pub seed_region: [u32; 4],
+    pub member_region: [u32; 4],
+    pub pairs: Vec<JsonTilePair>,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema)]
+pub struct JsonTilePair {
+    pub seed: [u32; 4],
+    pub member: [u32; 4],
+    pub dist_bits: u32,
+}
+
+#[derive(Serialize, JsonSchema)]
+struct JsonClip {
+    clip: String,
+    source: String,
+    start_secs: f64,
+    end_secs: f64,
+    confidence: f64,
+    dist_bits: u32,
+}
+
+#[derive(Serialize, JsonSchema)]
+struct JsonFrameMatch {
+    image: String,
+    video: String,
+    timestamp_secs: f64,
     dist_bits: u32,
 }
 
-pub fn print(groups: &[Group], items: &[PipelineResult], json: bool) {
-    match json {
-        true => print_json(groups, items),
-        false => print_pretty(groups, items),
+/// Stdout, or the `--output` file.
+pub fn open_sink(path: Option<&Path>) -> io::Result<Box<dyn Write>> {
+    Ok(match path {
+        Some(path) => Box::new(BufWriter::new(fs::File::create(path)?)),
+        None => Box::new(io::stdout().lock()),
+    })
+}
+
+/// Write groups as pretty text, JSON (in the run envelope), one JSON group per line (NDJSON),
+/// or CSV with one row per group member.
+pub fn write_groups<W: Write>(
+    w: &mut W,
+    groups: &[Group],
+    items: &[PipelineResult],
+    format: OutputFormat,
+    run: RunInfo,
+) -> io::Result<()> {
+    match format {
+        OutputFormat::Pretty => write_pretty(w, groups, items)?,
+        OutputFormat::Json => {
+            let payload: Vec<JsonGroup> = groups.iter().map(|g| json_group(g, items)).collect();
+            write_json(w, &run.envelope(items, Results::Groups(payload)))?
+        }
+        OutputFormat::Ndjson => {
+            for g in groups {
+                write_json_line(w, &json_group(g, items))?;
+            }
+        }
+        OutputFormat::Csv => {
+            write_csv_record(
+                w,
+                &["group", "path", "blake3", "dist_bits", "avg_dist_bits"],
+            )?;
+            for (idx, g) in groups.iter().enumerate() {
+                for m in &g.members {
+                    let pr = &items[m.index];
+                    write_csv_record(
+                        w,
+                        &[
+                            &(idx + 1).to_string(),
+                            &pr.path.display().to_string(),
+                            &pr.blake3,
+                            &m.dist_bits.to_string(),
+                            &g.avg_dist_bits.to_string(),
+                        ],
+                    )?;
+                }
+            }
+        }
     }
+    w.flush()
 }
 
-fn print_pretty(groups: &[Group], items: &[PipelineResult]) {
+fn write_pretty<W: Write>(w: &mut W, groups: &[Group], items: &[PipelineResult]) -> io::Result<()> {
     if groups.is_empty() {
-        println!("No likely duplicates found.");
+        writeln!(w, "No likely duplicates found.")?;
     }
 
     for (idx, g) in groups.iter().enumerate() {
-        println!(
-            "Group {} ({} files) - avg dist: {:.2} bits",
+        let kind = match items[g.members[0].index].media_type {
+            MediaType::Image => "images",
+            MediaType::Video => "videos",
+        };
+        let variants = g.members.iter().filter(|m| m.color_variant).count();
+        let variants = match variants {
+            0 => String::new(),
+            n => format!(", {n} color variant(s)"),
+        };
+        writeln!(
+            w,
+            "Group {} ({} {}{variants}) - avg dist: {:.2} bits",
             idx + 1,
             g.members.len(),
+            kind,
             g.avg_dist_bits
-        );
+        )?;
         for m in &g.members {
             let pr = &items[m.index];
-            println!("  - {} (dist: {} bits)", pr.path.display(), m.dist_bits);
+            writeln!(w, "  - {} ({})", pr.path.display(), member_detail(m, pr))?;
+        }
+    }
+    Ok(())
+}
+
+/// One-line summary of a member's distances to the group seed.
+pub fn member_detail(m: &GroupMember, pr: &PipelineResult) -> String {
+    json_file(m, pr).detail()
+}
+
+impl JsonFile {
+    /// One-line summary of the distances to the group seed, as printed by `scan`.
+    pub fn detail(&self) -> String {
+        let mut detail = format!("dist: {} bits", self.dist_bits);
+        if let (Some(similarity), Some(offset)) = (self.similarity, self.offset_secs) {
+            detail += &format!(
+                ", similarity: {:.0}%, offset: {offset:+.2}s",
+                similarity * 100.0
+            );
+        }
+        if let Some(audio) = self.audio_dist_bits {
+            detail += &format!(", audio dist: {audio} bits");
+        }
+        for (alg, d) in &self.alg_dist_bits {
+            detail += &format!(", {alg}: {d}");
+        }
+        if let Some(color) = self.color_dist {
+            detail += &format!(", color dist: {color}");
+        }
+        if self.color_variant {
+            detail += ", color variant";
+        }
+        if let Some(t) = &self.tiles {
+            let [sx, sy, sw, sh] = t.seed_region;
+            let [mx, my, mw, mh] = t.member_region;
+            detail += &format!(
+                ", tiles: {}/{} matched, region {mw}x{mh}+{mx}+{my} ~ {sw}x{sh}+{sx}+{sy} in first",
+                t.matched, t.total
+            );
+        }
+        detail
+    }
+}
+
+fn json_group(g: &Group, items: &[PipelineResult]) -> JsonGroup {
+    JsonGroup {
+        avg_distance_bits: g.avg_dist_bits,
+        files: g
+            .members
+            .iter()
+            .map(|m| json_file(m, &items[m.index]))
+            .collect(),
+    }
+}
+
+fn json_file(m: &GroupMember, pr: &PipelineResult) -> JsonFile {
+    JsonFile {
+        path: pr.path.display().to_string(),
+        media_type: pr.media_type,
+        blake3: pr.blake3.clone(),
+        dist_bits: m.dist_bits,
+        offset_secs: m.alignment.map(|a| a.offset_secs),
+        similarity: m.alignment.map(|a| a.similarity),
+        audio_dist_bits: m.audio_dist_bits,
+        alg_dist_bits: m
+            .alg_dists
+            .iter()
+            .map(|(alg, d)| (alg.name(), *d))
+            .collect(),
+        color_dist: m.color_dist,
+        color_variant: m.color_variant,
+        tiles: m.tiles.as_ref().map(|t| {
+            let region = |r: Region| [r.x, r.y, r.size, r.size];
+            let bounds = |(x, y, w, h)| [x, y, w, h];
+            JsonTiles {
+                matched: t.matched,
+                total: t.total,
+                seed_region: bounds(t.seed_bounds()),
+                member_region: bounds(t.member_bounds()),
+                pairs: t
+                    .pairs
+                    .iter()
+                    .map(|p| JsonTilePair {
+                        seed: region(p.seed),
+                        member: region(p.member),
+                        dist_bits: p.dist_bits,
+                    })
+                    .collect(),
+            }
+        }),
+        metadata: pr.info.as_ref().map(|info| JsonMetadata {
+            size_bytes: info.size_bytes,
+            modified_secs: info.modified_secs,
+            width: info.media.width,
+            height: info.media.height,
+            format: info.media.format.clone(),
+            codec: info.media.codec.clone(),
+            duration_secs: pr.meta.and_then(|m| m.duration_secs),
+            fps: pr.meta.and_then(|m| m.fps),
+            perceptual_hash: pr.hashes[0].clone(),
+        }),
+    }
+}
+
+/// Write clip matches as pretty text, JSON (in the run envelope), NDJSON or CSV (one row per match).
+pub fn write_clips<W: Write>(
+    w: &mut W,
+    matches: &[ClipMatch],
+    items: &[PipelineResult],
+    format: OutputFormat,
+    run: RunInfo,
+) -> io::Result<()> {
+    let rows = build_clips_json(matches, items);
+    match format {
+        OutputFormat::Pretty => write_clips_pretty(w, matches, items)?,
+        OutputFormat::Json => write_json(w, &run.envelope(items, Results::Clips(rows)))?,
+        OutputFormat::Ndjson => {
+            for row in &rows {
+                write_json_line(w, row)?;
+            }
+        }
+        OutputFormat::Csv => {
+            let header = [
+                "clip",
+                "source",
+                "start_secs",
+                "end_secs",
+                "confidence",
+                "dist_bits",
+            ];
+            write_csv_record(w, &header)?;
+            for r in &rows {
+                write_csv_record(
+                    w,
+                    &[
+                        &r.clip,
+                        &r.source,
+                        &r.start_secs.to_string(),
+                        &r.end_secs.to_string(),
+                        &r.confidence.to_string(),
+                        &r.dist_bits.to_string(),
+                    ],
+                )?;
+            }
         }
     }
+    w.flush()
 }
 
-fn build_json(groups: &[Group], items: &[PipelineResult]) -> Vec<JsonGroup> {
-    groups
+fn write_clips_pretty<W: Write>(
+    w: &mut W,
+    matches: &[ClipMatch],
+    items: &[PipelineResult],
+) -> io::Result<()> {
+    if matches.is_empty() {
+        writeln!(w, "No contained clips found.")?;
+    }
+
+    for m in matches {
+        writeln!(
+            w,
+            "{}\n  in {} [{:.2}s - {:.2}s] (confidence: {:.0}%, dist: {} bits)",
+            items[m.clip].path.display(),
+            items[m.source].path.display(),
+            m.start_secs,
+            m.end_secs,
+            m.confidence * 100.0,
+            m.dist_bits
+        )?;
+    }
+    Ok(())
+}
+
+fn build_clips_json(matches: &[ClipMatch], items: &[PipelineResult]) -> Vec<JsonClip> {
+    matches
         .iter()
-        .map(|g| JsonGroup {
-            avg_distance_bits: g.avg_dist_bits,
-            files: g
-                .members
-                .iter()
-                .map(|m| {
-                    let pr = &items[m.index];
-                    JsonFile {
-                        path: pr.path.display().to_string(),
-                        blake3: pr.blake3.clone(),
-                        dist_bits: m.dist_bits,
-                    }
-                })
-                .collect(),
+        .map(|m| JsonClip {
+            clip: items[m.clip].path.display().to_string(),
+            source: items[m.source].path.display().to_string(),
+            start_secs: m.start_secs,
+            end_secs: m.end_secs,
+            confidence: m.confidence,
+            dist_bits: m.dist_bits,
         })
         .collect()
 }
 
-fn print_json(groups: &[Group], items: &[PipelineResult]) {
-    let payload = build_json(groups, items);
-    println!("{}", serde_json::to_string_pretty(&payload).unwrap());
+/// Write image -> video frame matches as pretty text, JSON (in the run envelope), NDJSON or CSV.
+pub fn write_frames<W: Write>(
+    w: &mut W,
+    matches: &[FrameMatch],
+    items: &[PipelineResult],
+    format: OutputFormat,
+    run: RunInfo,
+) -> io::Result<()> {
+    let rows = build_frames_json(matches, items);
+    match format {
+        OutputFormat::Pretty => write_frames_pretty(w, matches, items)?,
+        OutputFormat::Json => write_json(w, &run.envelope(items, Results::Frames(rows)))?,
+        OutputFormat::Ndjson => {
+            for row in &rows {
+                write_json_line(w, row)?;
+            }
+        }
+        OutputFormat::Csv => {
+            write_csv_record(w, &["image", "video", "timestamp_secs", "dist_bits"])?;
+            for r in &rows {
+                write_csv_record(
+                    w,
+                    &[
+                        &r.image,
+                        &r.video,
+                        &r.timestamp_secs.to_string(),
+                        &r.dist_bits.to_string(),
+                    ],
+                )?;
+            }
+        }
+    }
+    w.flush()
 }
 
-pub fn write_json_file<P: AsRef<Path>>(
-    groups: &[Group],
+fn write_frames_pretty<W: Write>(
+    w: &mut W,
+    matches: &[FrameMatch],
     items: &[PipelineResult],
-    path: P,
-) -> std::io::Result<()> {
-    let payload = build_json(groups, items);
-    let json = serde_json::to_string_pretty(&payload).unwrap();
-    fs::write(path, json)
+) -> io::Result<()> {
+    if matches.is_empty() {
+        writeln!(w, "No images matched a video frame.")?;
+    }
+
+    for (idx, m) in matches.iter().enumerate() {
+        // Group consecutive matches of the same image
+        if idx == 0 || matches[idx - 1].image != m.image {
+            writeln!(w, "{}", items[m.image].path.display())?;
+        }
+        writeln!(
+            w,
+            "  - {} @ {:.2}s (dist: {} bits)",
+            items[m.video].path.display(),
+            m.pts_secs,
+            m.dist_bits
+        )?;
+    }
+    Ok(())
+}
+
+fn build_frames_json(matches: &[FrameMatch], items: &[PipelineResult]) -> Vec<JsonFrameMatch> {
+    matches
+        .iter()
+        .map(|m| JsonFrameMatch {
+            image: items[m.image].path.display().to_string(),
+            video: items[m.video].path.display().to_string(),
+            timestamp_secs: m.pts_secs,
+            dist_bits: m.dist_bits,
+        })
+        .collect()
+}
+
+fn write_json<W: Write, T: Serialize>(w: &mut W, value: &T) -> io::Result<()> {
+    serde_json::to_writer_pretty(&mut *w, value)?;
+    writeln!(w)
+}
+
+/// NDJSON: one compact JSON value per line.
+fn write_json_line<W: Write, T: Serialize>(w: &mut W, row: &T) -> io::Result<()> {
+    serde_json::to_writer(&mut *w, row)?;
+    writeln!(w)
+}
+
+/// RFC 4180: fields with commas, quotes or line breaks are quoted, quotes doubled.
+fn write_csv_record<W: Write>(w: &mut W, fields: &[&str]) -> io::Result<()> {
+    let line: Vec<String> = fields
+        .iter()
+        .map(|f| match f.contains([',', '"', '\n', '\r']) {
+            true => format!("\"{}\"", f.replace('"', "\"\"")),
+            false => f.to_string(),
+        })
+        .collect();
+    write!(w, "{}\r\n", line.join(","))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        grouping::GroupMember,
+        types::{AppConfig, FileInfo, MediaInfo, VideoMeta},
+    };
+    use std::path::PathBuf;
+
+    const SCHEMA_PATH: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/schema/output.schema.json");
+
+    fn result(path: &str, media_type: MediaType) -> PipelineResult {
+        PipelineResult {
+            path: PathBuf::from(path),
+            blake3: "00".repeat(32),
+            hashes: vec!["JCQkJCQkJCQk".into()],
+            media_type,
+            frames: None,
+            audio_hash: None,
+            meta: (media_type == MediaType::Video).then_some(VideoMeta {
+                duration_secs: Some(12.5),
+                width: 1920,
+                height: 1080,
+                fps: Some(25.0),
+            }),
+            color: None,
+            tiles: None,
+            info: Some(FileInfo {
+                size_bytes: 1024,
+                modified_secs: Some(1_700_000_000),
+                media: MediaInfo {
+                    width: 1920,
+                    height: 1080,
+                    format: "png".into(),
+                    codec: None,
+                },
+            }),
+        }
+    }
+
+    fn member(index: usize, dist_bits: u32) -> GroupMember {
+        GroupMember {
+            index,
+            dist_bits,
+            alignment: None,
+            audio_dist_bits: None,
+            alg_dists: Vec::new(),
+            color_dist: None,
+            color_variant: false,
+            tiles: None,
+        }
+    }
+
+    fn run<'a>(errors: &'a [FileError]) -> RunInfo<'a> {
+        let args = crate::args::ScanArgs::defaults(std::path::Path::new("./photos"));
+        let hashed = Hashed {
+            directory: args.directory.clone(),
+            cfg: AppConfig {
+                with_metadata: true,
+                ..crate::run::config(&args.hashing, &args.video_opts, Vec::new())
+            },
+            media: vec![MediaType::Image],
+            files_found: 3,
+            results: Vec::new(),
+            errors: Vec::new(),
+        };
+        RunInfo {
+            config: JsonConfig::new(&hashed, args.threshold, &args.matching),
+            started: UNIX_EPOCH,
+            files_found: 3,
+            errors,
+        }
+    }
+
+    /// The published schema is the one generated from the serde types.
+    /// Regenerate with `UPDATE_SCHEMA=1 cargo test`.
+    #[test]
+    fn published_schema_is_current() {
+        let generated =
+            serde_json::to_string_pretty(&schemars::schema_for!(Envelope)).unwrap() + "\n";
+        if std::env::var_os("UPDATE_SCHEMA").is_some() {
+            fs::write(SCHEMA_PATH, &generated).unwrap();
+        }
+        let published = fs::read_to_string(SCHEMA_PATH).unwrap_or_default();
+        assert_eq!(
+            published, generated,
+            "schema is stale; run UPDATE_SCHEMA=1 cargo test"
+        );
+    }
+
+    #[test]
+    fn json_output_matches_schema() {
+        let schema: serde_json::Value =
+            serde_json::from_str(&fs::read_to_string(SCHEMA_PATH).unwrap()).unwrap();
+        let validator = jsonschema::validator_for(&schema).unwrap();
+
+        let items = vec![
+            result("a.png", MediaType::Image),
+            result("b.png", MediaType::Image),
+            result("c.mp4", MediaType::Video),
+        ];
+        let groups = vec![Group {
+            members: vec![member(0, 0), member(1, 4)],
+            avg_dist_bits: 2.0,
+        }];
+        let errors = vec![FileError {
+            path: PathBuf::from("broken.png"),
+            error: "decode failed".into(),
+        }];
+
+        let mut buf = Vec::new();
+        write_groups(&mut buf, &groups, &items, OutputFormat::Json, run(&errors)).unwrap();
+        let out: serde_json::Value = serde_json::from_slice(&buf).unwrap();
+        let problems: Vec<String> = validator.iter_errors(&out).map(|e| e.to_string()).collect();
+        assert!(problems.is_empty(), "{problems:#?}");
+        assert_eq!(out["schema_version"], SCHEMA_VERSION);
+        assert_eq!(out["started_at"], "1970-01-01T00:00:00Z");
+        assert_eq!(out["stats"]["duplicate_files"], 1);
+
+        let clips = vec![ClipMatch {
+            clip: 2,
+            source: 2,
+            start_secs: 1.0,
+            end_secs: 3.0,
+            confidence: 0.9,
+            dist_bits: 3,
+        }];
+        let mut buf = Vec::new();
+        write_clips(&mut buf, &clips, &items, OutputFormat::Json, run(&[])).unwrap();
+        let out: serde_json::Value = serde_json::from_slice(&buf).unwrap();
+        assert!(validator.is_valid(&out));
+    }
+
+    /// `review` reads groups back with the same types and prints the same detail as `scan`.
+    #[test]
+    fn json_groups_read_back_with_the_printed_detail() {
+        use crate::{
+            tiles::{Region, TileMatch, TilePair},
+            types::HashAlg,
+            video::align::Alignment,
+        };
+        let items = vec![
+            result("a.png", MediaType::Image),
+            result("b.png", MediaType::Image),
+            result("c.mp4", MediaType::Video),
+            result("d.mp4", MediaType::Video),
+        ];
+        let region = |x, y| Region { x, y, size: 100 };
+        let cropped = GroupMember {
+            alg_dists: vec![(HashAlg::Mean, 6), (HashAlg::Gradient, 3)],
+            color_dist: Some(12),
+            color_variant: true,
+            tiles: Some(TileMatch {
+                matched: 2,
+                total: 3,
+                mean_dist_bits: 4.0,
+                pairs: vec![
+                    TilePair {
+                        seed: region(50, 0),
+                        member: region(0, 0),
+                        dist_bits: 3,
+                    },
+                    TilePair {
+                        seed: region(100, 50),
+                        member: region(50, 50),
+                        dist_bits: 5,
+                    },
+                ],
+            }),
+            ..member(1, 7)
+        };
+        let shifted = GroupMember {
+            alignment: Some(Alignment {
+                offset_secs: -1.5,
+                similarity: 0.875,
+                matched: 7,
+                mean_dist_bits: 2.0,
+            }),
+            audio_dist_bits: Some(20),
+            ..member(3, 2)
+        };
+        let groups = vec![
+            Group {
+                members: vec![member(0, 0), cropped],
+                avg_dist_bits: 7.0,
+            },
+            Group {
+                members: vec![member(2, 0), shifted],
+                avg_dist_bits: 2.0,
+            },
+        ];
+
+        let mut buf = Vec::new();
+        write_groups(&mut buf, &groups, &items, OutputFormat::Json, run(&[])).unwrap();
+        let read: ResultsFile = serde_json::from_slice(&buf).unwrap();
+        assert_eq!(read.schema_version, SCHEMA_VERSION);
+        let read = read.groups.expect("groups");
+        assert_eq!(read.len(), groups.len());
+        for (g, jg) in groups.iter().zip(&read) {
+            assert_eq!(jg.files.len(), g.members.len());
+            for (m, f) in g.members.iter().zip(&jg.files) {
+                assert_eq!(f.detail(), member_detail(m, &items[m.index]));
+            }
+        }
+        let detail = read[0].files[1].detail();
+        assert!(detail.contains("tiles: 2/3 matched, region 150x150+0+0 ~ 150x150+50+0"));
+        assert!(detail.contains("gradient: 3, mean: 6, color dist: 12, color variant"));
+        assert_eq!(
+            read[1].files[1].detail(),
+            "dist: 2 bits, similarity: 88%, offset: -1.50s, audio dist: 20 bits"
+        );
+    }
+
+    #[test]
+    fn csv_fields_are_quoted_per_rfc_4180() {
+        let mut buf = Vec::new();
+        write_csv_record(
+            &mut buf,
+            &["plain", "a,b", "say \"hi\"", "two\nlines", "cr\rhere", ""],
+        )
+        .unwrap();
+        assert_eq!(
+            String::from_utf8(buf).unwrap(),
+            "plain,\"a,b\",\"say \"\"hi\"\"\",\"two\nlines\",\"cr\rhere\",\r\n"
+        );
+    }
+
+    #[test]
+    fn csv_groups_have_a_header_and_crlf_rows() {
+        let items = vec![
+            result("/p/a,b.png", MediaType::Image),
+            result("/p/\"q\".png", MediaType::Image),
+            result("/p/new\nline.png", MediaType::Image),
+        ];
+        let groups = vec![Group {
+            members: vec![member(0, 0), member(1, 3), member(2, 5)],
+            avg_dist_bits: 2.5,
+        }];
+        let mut buf = Vec::new();
+        write_groups(&mut buf, &groups, &items, OutputFormat::Csv, run(&[])).unwrap();
+        let b3 = "00".repeat(32);
+        assert_eq!(
+            String::from_utf8(buf).unwrap(),
+            format!(
+                "group,path,blake3,dist_bits,avg_dist_bits\r\n\
+                 1,\"/p/a,b.png\",{b3},0,2.5\r\n\
+                 1,\"/p/\"\"q\"\".png\",{b3},3,2.5\r\n\
+                 1,\"/p/new\nline.png\",{b3},5,2.5\r\n"
+            )
+        );
+    }
+
+    #[test]
+    fn csv_matches_have_a_header() {
+        let items = vec![
+            result("a.png", MediaType::Image),
+            result("b.mp4", MediaType::Video),
+            result("c.mp4", MediaType::Video),
+        ];
+        let frames = [FrameMatch {
+            image: 0,
+            video: 1,
+            pts_secs: 1.5,
+            dist_bits: 2,
+        }];
+        let mut buf = Vec::new();
+        write_frames(&mut buf, &frames, &items, OutputFormat::Csv, run(&[])).unwrap();
+        assert_eq!(
+            String::from_utf8(buf).unwrap(),
+            "image,video,timestamp_secs,dist_bits\r\na.png,b.mp4,1.5,2\r\n"
+        );
+
+        let clips = [ClipMatch {
+            clip: 1,
+            source: 2,
+            start_secs: 1.0,
+            end_secs: 3.0,
+            confidence: 0.5,
+            dist_bits: 4,
+        }];
+        let mut buf = Vec::new();
+        write_clips(&mut buf, &clips, &items, OutputFormat::Csv, run(&[])).unwrap();
+        assert_eq!(
+            String::from_utf8(buf).unwrap(),
+            "clip,source,start_secs,end_secs,confidence,dist_bits\r\nb.mp4,c.mp4,1,3,0.5,4\r\n"
+        );
+    }
+
+    #[test]
+    fn ndjson_writes_one_object_per_line() {
+        let items = vec![
+            result("a.png", MediaType::Image),
+            result("multi\nline.png", MediaType::Image),
+            result("c.mp4", MediaType::Video),
+            result("d.mp4", MediaType::Video),
+        ];
+        let groups = vec![
+            Group {
+                members: vec![member(0, 0), member(1, 4)],
+                avg_dist_bits: 4.0,
+            },
+            Group {
+                members: vec![member(2, 0), member(3, 1)],
+                avg_dist_bits: 1.0,
+            },
+        ];
+        let mut buf = Vec::new();
+        write_groups(&mut buf, &groups, &items, OutputFormat::Ndjson, run(&[])).unwrap();
+        let out = String::from_utf8(buf).unwrap();
+        assert!(out.ends_with('\n'));
+        let lines: Vec<&str> = out.lines().collect();
+        assert_eq!(lines.len(), groups.len());
+        for (line, g) in lines.iter().zip(&groups) {
+            let group: JsonGroup = serde_json::from_str(line).unwrap();
+            assert_eq!(group.files.len(), g.members.len());
+            assert_eq!(group.avg_distance_bits, g.avg_dist_bits);
+        }
+        assert_eq!(
+            serde_json::from_str::<JsonGroup>(lines[0]).unwrap().files[1].path,
+            "multi\nline.png"
+        );
+
+        let clips = [ClipMatch {
+            clip: 2,
+            source: 3,
+            start_secs: 1.0,
+            end_secs: 3.0,
+            confidence: 0.5,
+            dist_bits: 4,
+        }];
+        let mut buf = Vec::new();
+        write_clips(&mut buf, &clips, &items, OutputFormat::Ndjson, run(&[])).unwrap();
+        let out = String::from_utf8(buf).unwrap();
+        assert_eq!(out.lines().count(), 1);
+        let clip: serde_json::Value = serde_json::from_str(out.trim_end()).unwrap();
+        assert_eq!(clip["source"], "d.mp4");
+    }
 }
@@ -0,0 +1,303 @@
+use crate::types as T;
+use img_hash::image::{DynamicImage, GenericImageView, GrayImage, Luma, Rgb, RgbImage};
+
+/// Max luma difference from the edge colour for a pixel to count as border.
+const BORDER_TOLERANCE: u8 = 24;
+
+/// Fraction of pixels in a border row/column allowed to differ (noise, watermarks).
+const BORDER_NOISE: f64 = 0.02;
+
+/// Never crop more than this fraction of either axis from one side.
+const MAX_CROP_PER_SIDE: f64 = 0.4;
+
+/// Edge luma at or below which a border counts as black bars, and at or above which as white
+/// (cropdetect only crops black; white covers scans and screenshot padding).
+const BAR_BLACK_MAX: u8 = 32;
+const BAR_WHITE_MIN: u8 = 224;
+
+/// Checkerboard square size (px) and shades for `AlphaPolicy::Checker`.
+const CHECKER_SIZE: u32 = 8;
+const CHECKER_LIGHT: u8 = 255;
+const CHECKER_DARK: u8 = 204;
+
+/// Pre-processing applied to every image/frame before hashing.
+#[derive(Debug, Clone, Copy)]
+pub struct Preprocess {
+    pub autocrop: bool,
+    pub alpha: T::AlphaPolicy,
+}
+
+impl Preprocess {
+    pub fn from_cfg(cfg: &T::AppConfig) -> Self {
+        Preprocess {
+            autocrop: cfg.autocrop,
+            alpha: cfg.alpha,
+        }
+    }
+
+    /// Apply to a single image (crop detected on the image itself).
+    pub fn apply(&self, img: DynamicImage) -> DynamicImage {
+        let img = flatten_alpha(img, self.alpha);
+        match self.autocrop {
+            true => match detect_content(&img.to_luma8()) {
+                Some(rect) => crop(&img, rect),
+                None => img,
+            },
+            false => img,
+        }
+    }
+
+    /// Apply to a sequence of same-sized frames; one crop (union of content areas) for all.
+    pub fn apply_all(&self, imgs: Vec<DynamicImage>) -> Vec<DynamicImage> {
+        let imgs: Vec<DynamicImage> = imgs
+            .into_iter()
+            .map(|img| flatten_alpha(img, self.alpha))
+            .collect();
+        if !self.autocrop {
+            return imgs;
+        }
+        let rect = imgs
+            .iter()
+            .filter_map(|img| detect_content(&img.to_luma8()))
+            .reduce(CropRect::union);
+        match rect {
+            Some(rect) => imgs.iter().map(|img| crop(img, rect)).collect(),
+            None => imgs,
+        }
+    }
+}
+
+/// Remove transparency per `policy`; images without an alpha channel are returned as-is.
+pub fn flatten_alpha(img: DynamicImage, policy: T::AlphaPolicy) -> DynamicImage {
+    if policy == T::AlphaPolicy::None || !img.color().has_alpha() {
+        return img;
+    }
+    let rgba = img.to_rgba8();
+    let (w, h) = rgba.dimensions();
+
+    let background = |x: u32, y: u32| match policy {
+        T::AlphaPolicy::Black => 0u8,
+        T::AlphaPolicy::Checker if ((x / CHECKER_SIZE) + (y / CHECKER_SIZE)) % 2 == 1 => {
+            CHECKER_DARK
+        }
+        T::AlphaPolicy::Checker => CHECKER_LIGHT,
+        _ => 255u8,
+    };
+
+    match policy {
+        T::AlphaPolicy::Luma => DynamicImage::ImageLuma8(GrayImage::from_fn(w, h, |x, y| {
+            Luma([rgba.get_pixel(x, y).0[3]])
+        })),
+        _ => DynamicImage::ImageRgb8(RgbImage::from_fn(w, h, |x, y| {
+            let [r, g, b, a] = rgba.get_pixel(x, y).0;
+            let (a, bg) = (a as u32, background(x, y) as u32);
+            let blend = |c: u8| ((c as u32 * a + bg * (255 - a) + 127) / 255) as u8;
+            Rgb([blend(r), blend(g), blend(b)])
+        })),
+    }
+}
+
+/// Content area left after removing uniform borders.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CropRect {
+    pub x: u32,
+    pub y: u32,
+    pub w: u32,
+    pub h: u32,
+}
+
+impl CropRect {
+    /// Smallest rect containing both (cropdetect-style accumulation across frames).
+    pub fn union(self, other: CropRect) -> CropRect {
+        let (x0, y0) = (self.x.min(other.x), self.y.min(other.y));
+        let x1 = (self.x + self.w).max(other.x + other.w);
+        let y1 = (self.y + self.h).max(other.y + other.h);
+        CropRect {
+            x: x0,
+            y: y0,
+            w: x1 - x0,
+            h: y1 - y0,
+        }
+    }
+}
+
+/// Detect bars (letterbox, pillarbox, frames) by scanning inward from each edge.
+///
+/// - Each edge's reference colour is the median luma of its outermost row/column.
+/// - A row/column is border while >= 98% of its pixels are within tolerance of that colour.
+/// - Only near-black or near-white borders are bars, and only in pairs: top with bottom,
+///   left with right, of the same shade. A plain sky, wall or backdrop on one side stays.
+/// - Returns `None` for uniform images (e.g. black frames) so they don't drive the crop.
+pub fn detect_content(luma: &GrayImage) -> Option<CropRect> {
+    let (w, h) = luma.dimensions();
+    if w < 4 || h < 4 {
+        return None;
+    }
+    let px = |x: u32, y: u32| luma.get_pixel(x, y).0[0];
+
+    let max_x = (w as f64 * MAX_CROP_PER_SIDE) as u32;
+    let max_y = (h as f64 * MAX_CROP_PER_SIDE) as u32;
+
+    let row = |y: u32| (0..w).map(move |x| px(x, y));
+    let col = |x: u32| (0..h).map(move |y| px(x, y));
+
+    let top = count_border(row(0), max_y, row);
+    let bottom = count_border(row(h - 1), max_y, |i| row(h - 1 - i));
+    let left = count_border(col(0), max_x, col);
+    let right = count_border(col(w - 1), max_x, |i| col(w - 1 - i));
+
+    // Whole image uniform: nothing to anchor a crop on.
+    if top.1 >= max_y && bottom.1 >= max_y && left.1 >= max_x && right.1 >= max_x {
+        return None;
+    }
+    let (top, bottom) = bars(top, bottom);
+    let (left, right) = bars(left, right);
+
+    Some(CropRect {
+        x: left,
+        y: top,
+        w: w - left - right,
+        h: h - top - bottom,
+    })
+}
+
+/// Crop to `rect`, clamped to the image.
+pub fn crop(img: &DynamicImage, rect: CropRect) -> DynamicImage {
+    let (w, h) = img.dimensions();
+    let x = rect.x.min(w.saturating_sub(1));
+    let y = rect.y.min(h.saturating_sub(1));
+    let cw = rect.w.min(w - x).max(1);
+    let ch = rect.h.min(h - y).max(1);
+    if (x, y, cw, ch) == (0, 0, w, h) {
+        return img.clone();
+    }
+    img.crop_imm(x, y, cw, ch)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Bar {
+    Black,
+    White,
+}
+
+fn bar_shade(reference: u8) -> Option<Bar> {
+    match reference {
+        ..=BAR_BLACK_MAX => Some(Bar::Black),
+        BAR_WHITE_MIN.. => Some(Bar::White),
+        _ => None,
+    }
+}
+
+/// Border widths of two opposite edges, kept only if both are bars of the same shade.
+fn bars((ref_a, a): (u8, u32), (ref_b, b): (u8, u32)) -> (u32, u32) {
+    match (bar_shade(ref_a), bar_shade(ref_b)) {
+        (Some(sa), Some(sb)) if sa == sb => (a, b),
+        _ => (0, 0),
+    }
+}
+
+/// Edge reference colour and number of consecutive border lines from that edge, capped at `max`.
+fn count_border<I, F>(edge: I, max: u32, line: F) -> (u8, u32)
+where
+    I: Iterator<Item = u8>,
+    F: Fn(u32) -> I,
+{
+    let mut edge: Vec<u8> = edge.collect();
+    edge.sort_unstable();
+    let reference = edge[edge.len() / 2];
+
+    let count = (0..max)
+        .take_while(|&i| {
+            let mut total = 0usize;
+            let mut off = 0usize;
+            for v in line(i) {
+                total += 1;
+                if v.abs_diff(reference) > BORDER_TOLERANCE {
+                    off += 1;
+                }
+            }
+            (off as f64) <= (total as f64) * BORDER_NOISE
+        })
+        .count() as u32;
+    (reference, count)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const W: u32 = 160;
+    const H: u32 = 90;
+
+    /// High-contrast texture: no row or column of it is uniform.
+    fn content(x: u32, y: u32) -> u8 {
+        if (x / 3 + y / 3).is_multiple_of(2) {
+            60
+        } else {
+            200
+        }
+    }
+
+    fn image(f: impl Fn(u32, u32) -> u8) -> GrayImage {
+        GrayImage::from_fn(W, H, |x, y| Luma([f(x, y)]))
+    }
+
+    #[test]
+    fn letterbox_is_cropped() {
+        let img = image(|x, y| match y {
+            ..12 | 78.. => 8,
+            _ => content(x, y),
+        });
+        let rect = CropRect {
+            x: 0,
+            y: 12,
+            w: W,
+            h: 66,
+        };
+        assert_eq!(detect_content(&img), Some(rect));
+    }
+
+    #[test]
+    fn pillarbox_is_cropped() {
+        let img = image(|x, y| match x {
+            ..20 | 140.. => 250,
+            _ => content(x, y),
+        });
+        let rect = CropRect {
+            x: 20,
+            y: 0,
+            w: 120,
+            h: H,
+        };
+        assert_eq!(detect_content(&img), Some(rect));
+    }
+
+    #[test]
+    fn sky_and_one_sided_borders_are_kept() {
+        let full = Some(CropRect {
+            x: 0,
+            y: 0,
+            w: W,
+            h: H,
+        });
+        // Plain sky above textured ground
+        let sky = image(|x, y| if y < 30 { 170 } else { content(x, y) });
+        assert_eq!(detect_content(&sky), full);
+        // White backdrop at the bottom only
+        let floor = image(|x, y| if y >= 60 { 255 } else { content(x, y) });
+        assert_eq!(detect_content(&floor), full);
+        // Black top bar with a white bottom bar: different shades aren't a letterbox
+        let mixed = image(|x, y| match y {
+            ..10 => 0,
+            80.. => 255,
+            _ => content(x, y),
+        });
+        assert_eq!(detect_content(&mixed), full);
+    }
+
+    #[test]
+    fn uniform_image_has_no_content() {
+        assert_eq!(detect_content(&image(|_, _| 0)), None);
+        assert_eq!(detect_content(&image(|_, _| 128)), None);
+    }
+}
@@ -1,5 +1,9 @@
+use std::path::PathBuf;
+
 use indicatif::{ProgressBar, ProgressStyle};
 
+use crate::{errors::AppError, types::FileError};
+
 pub fn bar(len: u64, msg: &str) -> ProgressBar {
     let pb = ProgressBar::new(len);
     pb.set_style(
@@ -11,3 +15,45 @@ pub fn bar(len: u64, msg: &str) -> ProgressBar {
     pb.set_message(msg.to_string());
     pb
 }
+
+/// Split a pipeline's per-file results into successes and failures, warning about each
+/// failure and noting how many were skipped as failed in a previous run (listed last).
+pub fn collect<T>(results: Vec<Result<T, (PathBuf, AppError)>>) -> (Vec<T>, Vec<FileError>) {
+    let mut oks: Vec<T> = Vec::new();
+    let mut errs: Vec<(PathBuf, AppError)> = Vec::new();
+    let mut skipped: Vec<FileError> = Vec::new();
+    for r in results {
+        match r {
+            Ok(ok) => oks.push(ok),
+            Err((path, e @ AppError::PreviouslyFailed(_))) => skipped.push(FileError {
+                path,
+                error: e.to_string(),
+            }),
+            Err((p, e)) => errs.push((p, e)),
+        }
+    }
+
+    // Error Report
+    if !errs.is_empty() {
+        errs.iter().for_each(|(p, e)| {
+            eprintln!("warn: {} -> {}", p.display(), e);
+        });
+        eprintln!("note: {} file(s) failed", errs.len());
+    }
+    if !skipped.is_empty() {
+        eprintln!(
+            "note: {} file(s) skipped, failed in a previous run (use --retry-failed)",
+            skipped.len()
+        );
+    }
+
+    let errors = errs
+        .into_iter()
+        .map(|(path, e)| FileError {
+            path,
+            error: e.to_string(),
+        })
+        .chain(skipped)
+        .collect();
+    (oks, errors)
+}
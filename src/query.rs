@@ -0,0 +1,219 @@
+//! `query`: find files near a given file or hash, in hashes saved with `scan --save-hashes`
+//! or in the cache (which knows contents by BLAKE3 only, not paths).
+
+use std::{
+    fs,
+    io::{self, Write},
+    path::{Path, PathBuf},
+};
+
+use img_hash::ImageHash;
+use serde::Serialize;
+
+use crate::{
+    args::QueryArgs,
+    cache,
+    errors::{AppError, QueryError},
+    run,
+    types::{AppConfig, HashAlg, HashParams, MediaType},
+};
+
+type Hash = ImageHash<Box<[u8]>>;
+
+pub fn run(args: &QueryArgs) -> Result<(), AppError> {
+    let saved = args.hashes.as_deref().map(run::load_hashes).transpose()?;
+    // Hash settings of the saved scan, else from the command line
+    let cfg = match &saved {
+        Some(hashed) => hashed.cfg.clone(),
+        None => run::config(&args.hashing, &args.video_opts, Vec::new()),
+    };
+    // Compared on the primary algorithm
+    let alg = cfg.hash_algs[0];
+    let target = Target::resolve(&args.target, &cfg, &args.hashing.cache_file)?;
+
+    let candidates = match &saved {
+        Some(hashed) => hashed
+            .results
+            .iter()
+            .map(|r| Candidate {
+                path: Some(r.path.clone()),
+                blake3: r.blake3.clone(),
+                media_type: r.media_type,
+                hash: r.hashes[0].clone(),
+            })
+            .collect(),
+        None => cached(&cfg, alg, &args.hashing.cache_file)?,
+    };
+
+    let mut matches = Vec::new();
+    for c in &candidates {
+        if target.is(c) {
+            continue;
+        }
+        let Ok(hash) = Hash::from_base64(&c.hash) else {
+            continue;
+        };
+        if hash.as_bytes().len() != target.hash.as_bytes().len() {
+            return Err(QueryError::Length {
+                expected: hash.as_bytes().len() * 8,
+                found: target.hash.as_bytes().len() * 8,
+            }
+            .into());
+        }
+        let dist_bits = target.hash.dist(&hash);
+        if dist_bits <= args.threshold {
+            matches.push(Match {
+                path: c.path.as_deref(),
+                blake3: &c.blake3,
+                media_type: c.media_type,
+                dist_bits,
+            });
+        }
+    }
+    matches.sort_by(|a, b| a.dist_bits.cmp(&b.dist_bits).then(a.path.cmp(&b.path)));
+
+    if args.json {
+        let out = QueryOutput {
+            alg: alg.name(),
+            threshold: args.threshold,
+            matches,
+        };
+        let mut stdout = io::stdout().lock();
+        serde_json::to_writer_pretty(&mut stdout, &out).map_err(io::Error::from)?;
+        writeln!(stdout)?;
+        return Ok(());
+    }
+    println!(
+        "{} file(s) within {} bits ({})",
+        matches.len(),
+        args.threshold,
+        alg.name()
+    );
+    for m in &matches {
+        match m.path {
+            Some(path) => println!("  {:>3} bits  {}", m.dist_bits, path.display()),
+            None => println!(
+                "  {:>3} bits  blake3 {} ({})",
+                m.dist_bits,
+                m.blake3,
+                media_name(m.media_type)
+            ),
+        }
+    }
+    Ok(())
+}
+
+/// The file or hash looked up.
+struct Target {
+    hash: Hash,
+    /// File target: its canonical path and BLAKE3, to leave it out of its own matches
+    file: Option<(Option<PathBuf>, String)>,
+}
+
+impl Target {
+    /// An existing file is hashed with `cfg` (through the cache); anything else must be a hash.
+    fn resolve(target: &Path, cfg: &AppConfig, cache_file: &Path) -> Result<Self, AppError> {
+        if !target.is_file() {
+            let hash = target
+                .to_str()
+                .and_then(|s| Hash::from_base64(s).ok())
+                .ok_or_else(|| QueryError::Target(target.display().to_string()))?;
+            return Ok(Target { hash, file: None });
+        }
+        let cfg = AppConfig {
+            media_paths: vec![target.to_path_buf()],
+            ..cfg.clone()
+        };
+        let (results, errors) = run::hash_paths(&cfg, cache_file)?;
+        let Some(pr) = results.into_iter().next() else {
+            let error = errors
+                .into_iter()
+                .next()
+                .map(|e| e.error)
+                .unwrap_or_default();
+            return Err(QueryError::Hash {
+                path: target.to_path_buf(),
+                error,
+            }
+            .into());
+        };
+        let hash = Hash::from_base64(&pr.hashes[0]).map_err(|_| QueryError::Hash {
+            path: target.to_path_buf(),
+            error: "invalid hash".into(),
+        })?;
+        Ok(Target {
+            hash,
+            file: Some((fs::canonicalize(target).ok(), pr.blake3)),
+        })
+    }
+
+    /// The target file itself: same path in saved hashes, same contents in the cache.
+    fn is(&self, c: &Candidate) -> bool {
+        let Some((path, blake3)) = &self.file else {
+            return false;
+        };
+        match &c.path {
+            Some(p) => path.is_some() && fs::canonicalize(p).ok() == *path,
+            None => c.blake3 == *blake3,
+        }
+    }
+}
+
+struct Candidate {
+    /// `None` for cache entries
+    path: Option<PathBuf>,
+    blake3: String,
+    media_type: MediaType,
+    hash: String,
+}
+
+/// Cache entries hashed with exactly `cfg`'s settings for `alg` (image, animated image or video).
+fn cached(cfg: &AppConfig, alg: HashAlg, cache_file: &Path) -> Result<Vec<Candidate>, AppError> {
+    let cache = cache::load_cache(&cache::cache_path(cache_file)?)?;
+    let image = HashParams::from_cfg(cfg, alg, MediaType::Image);
+    let animation = HashParams::for_animation(cfg, alg);
+    let video = HashParams::from_cfg(cfg, alg, MediaType::Video);
+    Ok(cache
+        .by_blake3
+        .iter()
+        .filter_map(|(blake3, entries)| {
+            let e = entries
+                .iter()
+                .find(|e| [&image, &animation, &video].contains(&&e.params))?;
+            Some(Candidate {
+                path: None,
+                blake3: blake3.clone(),
+                media_type: match e.params == video {
+                    true => MediaType::Video,
+                    false => MediaType::Image,
+                },
+                hash: e.perceptual_hash.clone(),
+            })
+        })
+        .collect())
+}
+
+fn media_name(media_type: MediaType) -> &'static str {
+    match media_type {
+        MediaType::Image => "image",
+        MediaType::Video => "video",
+    }
+}
+
+/// `query --json`
+#[derive(Serialize)]
+struct QueryOutput<'a> {
+    alg: String,
+    threshold: u32,
+    matches: Vec<Match<'a>>,
+}
+
+#[derive(Serialize)]
+struct Match<'a> {
+    /// Absent for cache entries
+    #[serde(skip_serializing_if = "Option::is_none")]
+    path: Option<&'a Path>,
+    blake3: &'a str,
+    media_type: MediaType,
+    dist_bits: u32,
+}
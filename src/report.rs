@@ -0,0 +1,228 @@
+//! Self-contained HTML report (`--report`): inline thumbnails, sizes, resolutions and
+//! per-member distances, with checkboxes that export the files to trash as JSON.
+
+use std::{fmt::Write as _, fs, io::Cursor, path::Path};
+
+use img_hash::image::{DynamicImage, GenericImageView, ImageOutputFormat, RgbImage};
+use indicatif::ParallelProgressIterator;
+use rayon::prelude::*;
+
+use crate::{
+    errors::AppError,
+    grouping::Group,
+    output, progress,
+    types::{AppConfig, MediaType, PipelineResult},
+    video,
+};
+
+/// Thumbnail long side (px).
+const THUMB_SIZE: u32 = 200;
+
+/// Video ~ Frames shown in the strip, evenly spaced over the sampling window.
+const STRIP_FRAMES: usize = 8;
+
+/// Video ~ Strip frame height (px).
+const STRIP_HEIGHT: u32 = 72;
+
+const JPEG_QUALITY: u8 = 80;
+
+/// Previews of one file: a thumbnail (images) or a frame strip (videos).
+struct Preview {
+    /// Base64 JPEG
+    thumb: Option<String>,
+    /// Video ~ (timestamp, base64 JPEG) per strip frame.
+    strip: Vec<(f64, String)>,
+    size_bytes: Option<u64>,
+    resolution: Option<(u32, u32)>,
+}
+
+/// Write the report for `groups`. Video frames are re-decoded with the same sampling as hashing.
+pub fn write_report(
+    groups: &[Group],
+    items: &[PipelineResult],
+    cfg: &AppConfig,
+    path: &Path,
+) -> Result<(), AppError> {
+    let members: Vec<usize> = groups
+        .iter()
+        .flat_map(|g| g.members.iter().map(|m| m.index))
+        .collect();
+
+    // Previews - Parallel
+    let pb = progress::bar(members.len() as u64, "Report");
+    let previews: Vec<Preview> = members
+        .par_iter()
+        .progress_with(pb.clone())
+        .map(|&i| preview(&items[i], cfg))
+        .collect();
+    pb.finish_and_clear();
+
+    let mut html = String::new();
+    html += HEAD;
+    let _ = writeln!(html, "<h1>{} duplicate group(s)</h1>", groups.len());
+    let mut previews = previews.into_iter();
+    for (gi, g) in groups.iter().enumerate() {
+        let _ = writeln!(
+            html,
+            "<section><h2>Group {} ({} files) &middot; avg dist {:.2} bits</h2>",
+            gi + 1,
+            g.members.len(),
+            g.avg_dist_bits
+        );
+        for m in &g.members {
+            let pr = &items[m.index];
+            let p = previews.next().expect("one preview per member");
+            let path_str = pr.path.display().to_string();
+            let _ = writeln!(
+                html,
+                "<div class=\"member\"><label><input type=\"checkbox\" data-group=\"{}\" data-path=\"{}\" data-blake3=\"{}\"> trash</label>",
+                gi + 1,
+                escape(&path_str),
+                pr.blake3
+            );
+            if let Some(thumb) = &p.thumb {
+                let _ = writeln!(
+                    html,
+                    "<img class=\"thumb\" src=\"data:image/jpeg;base64,{thumb}\">"
+                );
+            }
+            if !p.strip.is_empty() {
+                html += "<div class=\"strip\">";
+                for (secs, frame) in &p.strip {
+                    let _ = write!(
+                        html,
+                        "<img src=\"data:image/jpeg;base64,{frame}\" title=\"{secs:.2}s\">"
+                    );
+                }
+                html += "</div>\n";
+            }
+            let size = p.size_bytes.map_or("?".into(), human_size);
+            let res = p.resolution.map_or("?".into(), |(w, h)| format!("{w}x{h}"));
+            let _ = writeln!(
+                html,
+                "<div class=\"path\">{}</div><div class=\"info\">{size} &middot; {res} &middot; {}</div></div>",
+                escape(&path_str),
+                escape(&output::member_detail(m, pr))
+            );
+        }
+        html += "</section>\n";
+    }
+    html += TAIL;
+
+    fs::write(path, html)?;
+    Ok(())
+}
+
+/// Build a file's previews; anything that fails to decode is simply left out.
+fn preview(pr: &PipelineResult, cfg: &AppConfig) -> Preview {
+    let size_bytes = fs::metadata(&pr.path).ok().map(|m| m.len());
+    match pr.media_type {
+        MediaType::Image => {
+            let img = img_hash::image::open(&pr.path).ok();
+            Preview {
+                thumb: img
+                    .as_ref()
+                    .and_then(|img| jpeg_base64(&img.thumbnail(THUMB_SIZE, THUMB_SIZE))),
+                strip: Vec::new(),
+                size_bytes,
+                resolution: img.map(|img| img.dimensions()),
+            }
+        }
+        MediaType::Video => {
+            let frames = video::decode::decode_sample_frames(&pr.path, cfg, STRIP_FRAMES)
+                .unwrap_or_default();
+            let strip = frames
+                .into_iter()
+                .filter_map(|f| {
+                    let (w, h) = f.image.dimensions();
+                    let width = (w * STRIP_HEIGHT / h.max(1)).max(1);
+                    let jpeg = jpeg_base64(&f.image.thumbnail(width, STRIP_HEIGHT))?;
+                    Some((f.pts_secs, jpeg))
+                })
+                .collect();
+            Preview {
+                thumb: None,
+                strip,
+                size_bytes,
+                resolution: pr.meta.map(|m| (m.width, m.height)),
+            }
+        }
+    }
+}
+
+fn jpeg_base64(img: &DynamicImage) -> Option<String> {
+    // JPEG has no alpha; flatten to RGB
+    let rgb: RgbImage = img.to_rgb8();
+    let mut bytes = Vec::new();
+    DynamicImage::ImageRgb8(rgb)
+        .write_to(
+            &mut Cursor::new(&mut bytes),
+            ImageOutputFormat::Jpeg(JPEG_QUALITY),
+        )
+        .ok()?;
+    Some(base64::encode(bytes))
+}
+
+pub fn human_size(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["B", "KB", "MB", "GB"];
+    let mut size = bytes as f64;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    match unit {
+        0 => format!("{bytes} B"),
+        _ => format!("{size:.1} {}", UNITS[unit]),
+    }
+}
+
+fn escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+const HEAD: &str = r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+<meta charset="utf-8">
+<title>rust-phash report</title>
+<style>
+body { font-family: sans-serif; margin: 1rem 1rem 4rem; background: #fafafa; }
+section { background: #fff; border: 1px solid #ddd; border-radius: 6px; padding: .5rem 1rem; margin: 1rem 0; }
+h2 { font-size: 1rem; }
+.member { display: inline-block; vertical-align: top; margin: .5rem; max-width: 640px; }
+.thumb { display: block; max-width: 200px; max-height: 200px; }
+.strip img { height: 72px; margin-right: 2px; }
+.path { font-family: monospace; word-break: break-all; }
+.info { color: #555; font-size: .85rem; }
+#bar { position: fixed; bottom: 0; left: 0; right: 0; background: #222; color: #fff; padding: .5rem 1rem; }
+</style>
+</head>
+<body>
+"#;
+
+/// Files ticked for the trash: `{"selected": [{"group", "path", "blake3"}, ...]}`, read by
+/// `review --selection`.
+const TAIL: &str = r#"<div id="bar"><span id="count">0</span> file(s) to trash <button id="export">Export files to trash</button></div>
+<script>
+const boxes = () => [...document.querySelectorAll('input[type=checkbox]')];
+document.addEventListener('change', () => {
+  document.getElementById('count').textContent = boxes().filter(b => b.checked).length;
+});
+document.getElementById('export').addEventListener('click', () => {
+  const selected = boxes().filter(b => b.checked).map(b => ({
+    group: Number(b.dataset.group), path: b.dataset.path, blake3: b.dataset.blake3,
+  }));
+  const blob = new Blob([JSON.stringify({ selected }, null, 2)], { type: 'application/json' });
+  const a = document.createElement('a');
+  a.href = URL.createObjectURL(blob);
+  a.download = 'selection.json';
+  a.click();
+});
+</script>
+</body>
+</html>
+"#;
@@ -0,0 +1,472 @@
+//! `review` subcommand: step through duplicate groups in the terminal, mark the files to keep,
+//! and move the rest to the trash through the undo journal ([`crate::actions`]).
+
+mod preview;
+mod ui;
+
+use std::{
+    fs, io,
+    path::{self, Path, PathBuf},
+};
+
+use ratatui::{
+    DefaultTerminal,
+    crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind},
+};
+use serde::Deserialize;
+
+use crate::{
+    actions::{Journal, TrashFile},
+    args::{ReviewArgs, ScanArgs},
+    errors::{ActionError, AppError, ReviewError},
+    hashing, output, run,
+    types::{MediaType, PipelineResult},
+};
+
+/// One file of a group under review.
+pub struct Entry {
+    pub path: PathBuf,
+    pub media_type: MediaType,
+    pub blake3: String,
+    /// Distances to the group seed, as printed by the default command.
+    pub detail: String,
+    pub size_bytes: Option<u64>,
+    pub modified_secs: Option<u64>,
+    pub resolution: Option<(u32, u32)>,
+    pub duration_secs: Option<f64>,
+    pub keep: bool,
+    /// Moved to the trash in this session (and not restored).
+    pub trashed: bool,
+}
+
+pub struct ReviewGroup {
+    pub avg_dist_bits: f64,
+    pub files: Vec<Entry>,
+}
+
+pub fn run(args: &ReviewArgs) -> Result<(), AppError> {
+    let selected = args.selection.as_deref().map(load_selection).transpose()?;
+    let mut groups = if args.input.is_dir() {
+        scan(&args.input)?
+    } else {
+        load(&args.input)?
+    };
+    if let Some(selected) = selected {
+        for entry in groups.iter_mut().flat_map(|g| &mut g.files) {
+            entry.keep = !selected
+                .iter()
+                .any(|s| s.path == entry.path && s.blake3 == entry.blake3);
+        }
+    }
+    if groups.is_empty() {
+        eprintln!("No duplicate groups to review");
+        return Ok(());
+    }
+    let journal = Journal::new(&args.journal, &args.trash)?;
+    let mut app = App {
+        groups,
+        group: 0,
+        row: 0,
+        journal,
+        protocol: preview::Protocol::detect(args.preview),
+        preview_on: true,
+        shown: None,
+        status: String::new(),
+        moved: 0,
+    };
+
+    let mut terminal = ratatui::init();
+    let result = app.event_loop(&mut terminal);
+    ratatui::restore();
+    result?;
+
+    if app.moved > 0 {
+        eprintln!(
+            "{} file(s) moved to \"{}\" (journal: \"{}\"); review again and press u to undo",
+            app.moved,
+            args.trash.display(),
+            args.journal.display()
+        );
+    }
+    Ok(())
+}
+
+/// Scan `dir` with default settings, as `rust-phash <DIR>` would.
+fn scan(dir: &Path) -> Result<Vec<ReviewGroup>, AppError> {
+    let args = ScanArgs::defaults(dir);
+    let Some(hashed) = run::hash_files(&args)? else {
+        return Ok(Vec::new());
+    };
+    let groups = run::group(args.threshold, &args.matching, &hashed);
+    Ok(groups
+        .iter()
+        .map(|g| ReviewGroup {
+            avg_dist_bits: g.avg_dist_bits,
+            files: g
+                .members
+                .iter()
+                .map(|m| {
+                    let pr = &hashed.results[m.index];
+                    Entry::from_result(pr, output::member_detail(m, pr))
+                })
+                .collect(),
+        })
+        .collect())
+}
+
+/// Read a results file written with `--output` (JSON).
+fn load(path: &Path) -> Result<Vec<ReviewGroup>, ReviewError> {
+    let results: output::ResultsFile = serde_json::from_slice(&fs::read(path)?)?;
+    if results.schema_version != output::SCHEMA_VERSION {
+        return Err(ReviewError::VersionMismatch {
+            expected: output::SCHEMA_VERSION,
+            found: results.schema_version,
+        });
+    }
+    let groups = results.groups.ok_or(ReviewError::NoGroups)?;
+    Ok(groups
+        .into_iter()
+        .map(|g| ReviewGroup {
+            avg_dist_bits: g.avg_distance_bits,
+            files: g.files.into_iter().map(Entry::from_json).collect(),
+        })
+        .collect())
+}
+
+// Selection exported from the HTML report: `{"selected": [{"group", "path", "blake3"}, ...]}`
+#[derive(Deserialize)]
+struct SelectionFile {
+    selected: Vec<Selected>,
+}
+
+#[derive(Deserialize)]
+struct Selected {
+    path: PathBuf,
+    blake3: String,
+}
+
+/// Files ticked in the report; matched on path and BLAKE3, whatever group they were in.
+fn load_selection(path: &Path) -> Result<Vec<Selected>, ReviewError> {
+    let error = |error: String| ReviewError::Selection {
+        path: path.to_path_buf(),
+        error,
+    };
+    let bytes = fs::read(path).map_err(|e| error(e.to_string()))?;
+    let selection: SelectionFile =
+        serde_json::from_slice(&bytes).map_err(|e| error(e.to_string()))?;
+    Ok(selection.selected)
+}
+
+impl Entry {
+    fn from_result(pr: &PipelineResult, detail: String) -> Self {
+        let info = pr.info.as_ref();
+        let mut entry = Entry {
+            path: pr.path.clone(),
+            media_type: pr.media_type,
+            blake3: pr.blake3.clone(),
+            detail,
+            size_bytes: info.map(|i| i.size_bytes),
+            modified_secs: info.and_then(|i| i.modified_secs),
+            resolution: info
+                .map(|i| (i.media.width, i.media.height))
+                .or(pr.meta.map(|m| (m.width, m.height))),
+            duration_secs: pr.meta.and_then(|m| m.duration_secs),
+            keep: true,
+            trashed: false,
+        };
+        entry.stat();
+        entry
+    }
+
+    fn from_json(f: output::JsonFile) -> Self {
+        let detail = f.detail();
+        let meta = f.metadata;
+        let mut entry = Entry {
+            detail,
+            size_bytes: meta.as_ref().map(|m| m.size_bytes),
+            modified_secs: meta.as_ref().and_then(|m| m.modified_secs),
+            resolution: meta.as_ref().map(|m| (m.width, m.height)),
+            duration_secs: meta.and_then(|m| m.duration_secs),
+            path: PathBuf::from(f.path),
+            media_type: f.media_type,
+            blake3: f.blake3,
+            keep: true,
+            trashed: false,
+        };
+        entry.stat();
+        entry
+    }
+
+    /// Fill in what the results left out from the file system (and image header).
+    fn stat(&mut self) {
+        if self.size_bytes.is_none()
+            && let Ok(md) = fs::metadata(&self.path)
+        {
+            self.size_bytes = Some(md.len());
+            self.modified_secs = md
+                .modified()
+                .ok()
+                .and_then(|t| t.duration_since(std::time::UNIX_EPOCH).ok())
+                .map(|d| d.as_secs());
+        }
+        if self.resolution.is_none() && self.media_type == MediaType::Image {
+            self.resolution = hashing::image_info(&self.path)
+                .ok()
+                .map(|m| (m.width, m.height));
+        }
+    }
+}
+
+/// Review state.
+pub struct App {
+    pub groups: Vec<ReviewGroup>,
+    /// Current group and row within it
+    pub group: usize,
+    pub row: usize,
+    journal: Journal,
+    pub protocol: Option<preview::Protocol>,
+    pub preview_on: bool,
+    /// Image currently drawn in the preview pane, and where
+    shown: Option<(PathBuf, ratatui::layout::Rect)>,
+    pub status: String,
+    moved: usize,
+}
+
+impl App {
+    fn event_loop(&mut self, terminal: &mut DefaultTerminal) -> io::Result<()> {
+        loop {
+            let want = self.preview_path();
+            let changed = self.shown.as_ref().map(|(p, _)| p) != want.as_ref();
+            if changed && self.protocol == Some(preview::Protocol::Sixel) {
+                // Sixel pixels are only erased by redrawing the cells under them
+                terminal.clear()?;
+                self.shown = None;
+            }
+            let mut pane = None;
+            terminal.draw(|frame| pane = ui::draw(frame, self))?;
+            match (&self.protocol, want, pane) {
+                (Some(protocol), Some(path), Some(area))
+                    if self.shown.as_ref() != Some(&(path.clone(), area)) =>
+                {
+                    preview::clear(*protocol)?;
+                    if let Err(e) = preview::show(*protocol, &path, area) {
+                        self.status = format!("Preview failed: {e}");
+                    }
+                    self.shown = Some((path, area));
+                }
+                (Some(_), Some(_), Some(_)) => {}
+                (Some(protocol), _, _) if self.shown.is_some() => {
+                    preview::clear(*protocol)?;
+                    self.shown = None;
+                }
+                _ => {}
+            }
+
+            match event::read()? {
+                Event::Key(key) if key.kind == KeyEventKind::Press && !self.on_key(key) => {
+                    if let Some(protocol) = self.protocol {
+                        preview::clear(protocol)?;
+                    }
+                    return Ok(());
+                }
+                Event::Resize(..) => self.shown = None,
+                _ => {}
+            }
+        }
+    }
+
+    /// Image to preview: the selected file, when previews are on.
+    fn preview_path(&self) -> Option<PathBuf> {
+        let entry = &self.groups[self.group].files[self.row];
+        (self.protocol.is_some()
+            && self.preview_on
+            && entry.media_type == MediaType::Image
+            && !entry.trashed)
+            .then(|| entry.path.clone())
+    }
+
+    /// Handle a key; `false` to quit.
+    fn on_key(&mut self, key: KeyEvent) -> bool {
+        let files = self.groups[self.group].files.len();
+        match key.code {
+            KeyCode::Char('q') | KeyCode::Esc => return false,
+            KeyCode::Up | KeyCode::Char('k') => self.row = self.row.saturating_sub(1),
+            KeyCode::Down | KeyCode::Char('j') => self.row = (self.row + 1).min(files - 1),
+            KeyCode::Left | KeyCode::Char('p') => self.select_group(self.group.saturating_sub(1)),
+            KeyCode::Right | KeyCode::Char('n') => self.select_group(self.group + 1),
+            KeyCode::Char(' ') => {
+                let entry = &mut self.groups[self.group].files[self.row];
+                entry.keep = !entry.keep;
+            }
+            KeyCode::Char(c @ '1'..='9') => {
+                let n = c as usize - '1' as usize;
+                if n < files {
+                    for (i, entry) in self.groups[self.group].files.iter_mut().enumerate() {
+                        entry.keep = i == n;
+                    }
+                    self.row = n;
+                }
+            }
+            KeyCode::Char('a') => {
+                for entry in &mut self.groups[self.group].files {
+                    entry.keep = true;
+                }
+            }
+            KeyCode::Char('v') => self.preview_on = !self.preview_on,
+            KeyCode::Enter => self.apply(),
+            KeyCode::Char('u') => self.undo(),
+            _ => {}
+        }
+        true
+    }
+
+    fn select_group(&mut self, group: usize) {
+        self.group = group.min(self.groups.len() - 1);
+        self.row = 0;
+    }
+
+    /// Move the current group's unmarked files to the trash, then go to the next group.
+    fn apply(&mut self) {
+        let files = &mut self.groups[self.group].files;
+        if !files.iter().any(|f| f.keep && !f.trashed) {
+            self.status = "Mark at least one file to keep".into();
+            return;
+        }
+        let to_trash: Vec<TrashFile> = files
+            .iter()
+            .filter(|f| !f.keep && !f.trashed)
+            .map(|f| TrashFile {
+                path: &f.path,
+                blake3: &f.blake3,
+            })
+            .collect();
+        let n = to_trash.len();
+        if n == 0 {
+            self.status = "Nothing to move (all files kept)".into();
+            self.select_group(self.group + 1);
+            return;
+        }
+        match self.journal.trash(&to_trash) {
+            Ok(batch) => {
+                for f in files.iter_mut().filter(|f| !f.keep) {
+                    f.trashed = true;
+                }
+                self.moved += n;
+                self.status = format!("Moved {n} file(s) to the trash (batch {batch}); u to undo");
+                self.select_group(self.group + 1);
+            }
+            // Stay on the group, with what did move marked
+            Err(ActionError::Partial {
+                batch,
+                moved,
+                error,
+            }) => {
+                for f in files.iter_mut().filter(|f| !f.keep && !f.trashed) {
+                    let abs = path::absolute(&f.path).unwrap_or_else(|_| f.path.clone());
+                    f.trashed = moved.contains(&abs);
+                }
+                self.moved += moved.len();
+                self.status = format!(
+                    "Moved {} of {n} file(s) to the trash (batch {batch}), then failed: {error}; u to undo",
+                    moved.len()
+                );
+            }
+            Err(e) => self.status = format!("Nothing moved: {e}"),
+        }
+    }
+
+    /// Restore the last batch in the journal (possibly from an earlier session).
+    fn undo(&mut self) {
+        match self.journal.undo_last() {
+            Ok(restored) => {
+                self.mark_restored(&restored);
+                self.status = format!("Restored {} file(s)", restored.len());
+            }
+            Err(ActionError::PartialUndo {
+                batch,
+                restored,
+                error,
+            }) => {
+                self.mark_restored(&restored);
+                self.status = format!(
+                    "Restored {} file(s) of batch {batch}, then failed: {error}; u to finish",
+                    restored.len()
+                );
+            }
+            Err(e) => self.status = format!("Undo failed: {e}"),
+        }
+    }
+
+    /// Unmark restored files (and keep them) wherever they are in the session.
+    fn mark_restored(&mut self, restored: &[PathBuf]) {
+        let mut here = 0;
+        for entry in self.groups.iter_mut().flat_map(|g| &mut g.files) {
+            let abs = path::absolute(&entry.path).unwrap_or_else(|_| entry.path.clone());
+            if entry.trashed && restored.contains(&abs) {
+                entry.trashed = false;
+                entry.keep = true;
+                here += 1;
+            }
+        }
+        self.moved = self.moved.saturating_sub(here);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn load_json(name: &str, json: &str) -> Result<Vec<ReviewGroup>, ReviewError> {
+        let path = std::env::temp_dir().join(format!(
+            "rust-phash-review-{name}-{}.json",
+            std::process::id()
+        ));
+        fs::write(&path, json).unwrap();
+        let groups = load(&path);
+        let _ = fs::remove_file(&path);
+        groups
+    }
+
+    #[test]
+    fn loads_groups_with_metadata_and_detail() {
+        let version = output::SCHEMA_VERSION;
+        let json = format!(
+            r#"{{"schema_version": {version}, "groups": [{{"avg_distance_bits": 3.0, "files": [
+                {{"path": "/x/a.png", "media_type": "image", "blake3": "aa", "dist_bits": 0,
+                  "metadata": {{"size_bytes": 10, "width": 4, "height": 3, "format": "png",
+                               "perceptual_hash": "AA"}}}},
+                {{"path": "/x/b.png", "media_type": "image", "blake3": "bb", "dist_bits": 3,
+                  "color_dist": 9, "metadata": {{"size_bytes": 20, "modified_secs": 5,
+                  "width": 8, "height": 6, "format": "png", "perceptual_hash": "AA"}}}}
+            ]}}]}}"#
+        );
+        let groups = load_json("ok", &json).unwrap();
+        assert_eq!(groups.len(), 1);
+        let [a, b] = &groups[0].files[..] else {
+            panic!("expected two files");
+        };
+        assert_eq!(a.path, Path::new("/x/a.png"));
+        assert_eq!((a.size_bytes, a.resolution), (Some(10), Some((4, 3))));
+        assert_eq!(b.modified_secs, Some(5));
+        assert_eq!(b.detail, "dist: 3 bits, color dist: 9");
+        assert!(a.keep && b.keep && !a.trashed);
+    }
+
+    #[test]
+    fn rejects_other_versions_and_match_results() {
+        let other = output::SCHEMA_VERSION + 1;
+        let json = format!(r#"{{"schema_version": {other}, "groups": []}}"#);
+        assert!(matches!(
+            load_json("version", &json),
+            Err(ReviewError::VersionMismatch { found, .. }) if found == other
+        ));
+        let json = format!(
+            r#"{{"schema_version": {}, "clips": []}}"#,
+            output::SCHEMA_VERSION
+        );
+        assert!(matches!(
+            load_json("clips", &json),
+            Err(ReviewError::NoGroups)
+        ));
+    }
+}
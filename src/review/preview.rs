@@ -0,0 +1,173 @@
+//! Image previews drawn with terminal graphics: the kitty graphics protocol (kitty, WezTerm,
+//! Ghostty) or sixel (foot, mlterm, xterm -ti vt340, ...). Written straight to stdout at the
+//! preview pane, after ratatui has drawn the frame.
+
+use std::{
+    env,
+    io::{self, Cursor, Write},
+};
+
+use img_hash::image::{self, DynamicImage, ImageOutputFormat};
+use ratatui::{
+    crossterm::{cursor::MoveTo, queue, terminal},
+    layout::Rect,
+};
+
+use crate::{errors::HashError, types::PreviewMode};
+
+/// Kitty payload chunk size (the protocol's limit).
+const KITTY_CHUNK: usize = 4096;
+
+/// Cell size (px) assumed when the terminal doesn't report its pixel size.
+const FALLBACK_CELL: (u32, u32) = (8, 16);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Protocol {
+    Kitty,
+    Sixel,
+}
+
+impl Protocol {
+    /// Protocol for `mode`; `auto` goes by the environment the terminal sets.
+    pub fn detect(mode: PreviewMode) -> Option<Self> {
+        match mode {
+            PreviewMode::Kitty => Some(Protocol::Kitty),
+            PreviewMode::Sixel => Some(Protocol::Sixel),
+            PreviewMode::Off => None,
+            PreviewMode::Auto => {
+                let term = env::var("TERM").unwrap_or_default();
+                let program = env::var("TERM_PROGRAM").unwrap_or_default();
+                if env::var_os("KITTY_WINDOW_ID").is_some()
+                    || term == "xterm-kitty"
+                    || term == "xterm-ghostty"
+                    || matches!(program.as_str(), "WezTerm" | "ghostty")
+                {
+                    Some(Protocol::Kitty)
+                } else if term.starts_with("foot")
+                    || term.starts_with("mlterm")
+                    || term.contains("sixel")
+                {
+                    Some(Protocol::Sixel)
+                } else {
+                    None
+                }
+            }
+        }
+    }
+}
+
+/// Draw the image at `path`, scaled to fit `area` (cells).
+pub fn show(protocol: Protocol, path: &std::path::Path, area: Rect) -> Result<(), HashError> {
+    let (cell_w, cell_h) = cell_size();
+    let img = image::open(path)?.thumbnail(area.width as u32 * cell_w, area.height as u32 * cell_h);
+    let bytes = match protocol {
+        Protocol::Kitty => kitty(&img)?,
+        Protocol::Sixel => sixel(&img),
+    };
+    let mut out = io::stdout().lock();
+    queue!(out, MoveTo(area.x, area.y))?;
+    out.write_all(&bytes)?;
+    out.flush()?;
+    Ok(())
+}
+
+/// Remove a kitty preview (sixel pixels go when the cells are redrawn).
+pub fn clear(protocol: Protocol) -> io::Result<()> {
+    if protocol == Protocol::Kitty {
+        let mut out = io::stdout().lock();
+        out.write_all(b"\x1b_Ga=d,d=A,q=2\x1b\\")?;
+        out.flush()?;
+    }
+    Ok(())
+}
+
+fn cell_size() -> (u32, u32) {
+    match terminal::window_size() {
+        Ok(s) if s.width > 0 && s.height > 0 && s.columns > 0 && s.rows > 0 => {
+            ((s.width / s.columns) as u32, (s.height / s.rows) as u32)
+        }
+        _ => FALLBACK_CELL,
+    }
+}
+
+/// Transmit and display a PNG at the cursor, in base64 chunks; the cursor doesn't move.
+fn kitty(img: &DynamicImage) -> Result<Vec<u8>, HashError> {
+    let mut png = Vec::new();
+    img.write_to(&mut Cursor::new(&mut png), ImageOutputFormat::Png)?;
+    let payload = base64::encode(png);
+    let chunks: Vec<&[u8]> = payload.as_bytes().chunks(KITTY_CHUNK).collect();
+    let mut out = Vec::with_capacity(payload.len() + chunks.len() * 32);
+    for (i, chunk) in chunks.iter().enumerate() {
+        let more = u8::from(i + 1 < chunks.len());
+        match i {
+            0 => out.extend(format!("\x1b_Ga=T,f=100,q=2,C=1,m={more};").as_bytes()),
+            _ => out.extend(format!("\x1b_Gm={more};").as_bytes()),
+        }
+        out.extend(*chunk);
+        out.extend(b"\x1b\\");
+    }
+    Ok(out)
+}
+
+/// Sixel with a fixed 6x6x6 colour cube; each band of six rows is drawn once per colour used.
+fn sixel(img: &DynamicImage) -> Vec<u8> {
+    let rgb = img.to_rgb8();
+    let (w, h) = rgb.dimensions();
+    let level = |c: u8| (c as u32 * 5 + 127) / 255;
+    let index: Vec<u8> = rgb
+        .pixels()
+        .map(|p| (level(p[0]) * 36 + level(p[1]) * 6 + level(p[2])) as u8)
+        .collect();
+
+    let mut out = format!("\x1bPq\"1;1;{w};{h}").into_bytes();
+    for i in 0..216u32 {
+        let pct = |l: u32| l * 100 / 5;
+        out.extend(format!("#{i};2;{};{};{}", pct(i / 36), pct(i / 6 % 6), pct(i % 6)).as_bytes());
+    }
+    for band in (0..h).step_by(6) {
+        let rows = (h - band).min(6);
+        let mut used = [false; 216];
+        for y in band..band + rows {
+            for x in 0..w {
+                used[index[(y * w + x) as usize] as usize] = true;
+            }
+        }
+        for colour in (0..216).filter(|&c| used[c]) {
+            out.extend(format!("#{colour}").as_bytes());
+            // Run-length encode the column bits: `!<n><char>`
+            let mut run: Option<(u8, u32)> = None;
+            for x in 0..w {
+                let mut bits = 0u8;
+                for dy in 0..rows {
+                    if index[((band + dy) * w + x) as usize] as usize == colour {
+                        bits |= 1 << dy;
+                    }
+                }
+                let ch = b'?' + bits;
+                run = match run {
+                    Some((c, n)) if c == ch => Some((c, n + 1)),
+                    Some((c, n)) => {
+                        push_run(&mut out, c, n);
+                        Some((ch, 1))
+                    }
+                    None => Some((ch, 1)),
+                };
+            }
+            if let Some((c, n)) = run {
+                push_run(&mut out, c, n);
+            }
+            // Back to the start of the band for the next colour
+            out.push(b'$');
+        }
+        out.push(b'-');
+    }
+    out.extend(b"\x1b\\");
+    out
+}
+
+fn push_run(out: &mut Vec<u8>, ch: u8, n: u32) {
+    match n {
+        1..=3 => out.extend(std::iter::repeat_n(ch, n as usize)),
+        _ => out.extend(format!("!{n}{}", ch as char).as_bytes()),
+    }
+}
@@ -0,0 +1,131 @@
+//! Review screen: group header, member table, details of the selected file (with the preview
+//! pane) and a key help line.
+
+use std::time::{Duration, UNIX_EPOCH};
+
+use ratatui::{
+    Frame,
+    layout::{Constraint, Layout, Rect},
+    style::{Style, Stylize},
+    text::Line,
+    widgets::{Block, Cell, Paragraph, Row, Table, TableState, Wrap},
+};
+
+use super::{App, Entry};
+use crate::{output, report, types::MediaType};
+
+const HELP: &str = "↑↓ select  ←→ group  space keep  1-9 keep only  a keep all  enter apply  u undo  v preview  q quit";
+
+/// Draw the screen; returns the preview pane, if there is one.
+pub fn draw(frame: &mut Frame, app: &App) -> Option<Rect> {
+    let group = &app.groups[app.group];
+    let [header, table_area, details_area, status_area, help_area] = Layout::vertical([
+        Constraint::Length(1),
+        Constraint::Length(group.files.len() as u16 + 3),
+        Constraint::Min(3),
+        Constraint::Length(1),
+        Constraint::Length(1),
+    ])
+    .areas(frame.area());
+
+    // Header
+    let to_trash = group.files.iter().filter(|f| !f.keep && !f.trashed).count();
+    frame.render_widget(
+        Line::from(format!(
+            " Group {}/{}  ·  {} files  ·  avg dist {:.2} bits  ·  {to_trash} to trash",
+            app.group + 1,
+            app.groups.len(),
+            group.files.len(),
+            group.avg_dist_bits
+        ))
+        .bold(),
+        header,
+    );
+
+    // Members
+    let rows = group.files.iter().enumerate().map(|(i, f)| {
+        let mark = match (f.trashed, f.keep) {
+            (true, _) => "trashed",
+            (false, true) => "keep",
+            (false, false) => "trash",
+        };
+        let row = Row::new([
+            Cell::from(format!("{}", i + 1)),
+            Cell::from(mark),
+            Cell::from(f.path.display().to_string()),
+            Cell::from(f.size_bytes.map_or("-".into(), report::human_size)),
+            Cell::from(resolution(f)),
+            Cell::from(f.modified_secs.map_or("-".into(), date)),
+        ]);
+        match (f.trashed, f.keep) {
+            (true, _) => row.dim().crossed_out(),
+            (false, true) => row.green(),
+            (false, false) => row.red(),
+        }
+    });
+    let table = Table::new(
+        rows,
+        [
+            Constraint::Length(2),
+            Constraint::Length(7),
+            Constraint::Fill(1),
+            Constraint::Length(10),
+            Constraint::Length(11),
+            Constraint::Length(10),
+        ],
+    )
+    .header(Row::new(["#", "", "Path", "Size", "Resolution", "Modified"]).bold())
+    .row_highlight_style(Style::new().reversed())
+    .block(Block::bordered().title(" Files "));
+    let mut state = TableState::new().with_selected(Some(app.row));
+    frame.render_stateful_widget(table, table_area, &mut state);
+
+    // Selected file: details beside the preview pane
+    let entry = &group.files[app.row];
+    let previewing = app.protocol.is_some() && app.preview_on;
+    let [text_area, preview_area] = if previewing {
+        Layout::horizontal([Constraint::Fill(1), Constraint::Fill(1)]).areas(details_area)
+    } else {
+        Layout::horizontal([Constraint::Fill(1), Constraint::Length(0)]).areas(details_area)
+    };
+    let mut lines = vec![
+        Line::from(entry.path.display().to_string()).bold(),
+        Line::from(entry.detail.clone()),
+        Line::from(format!("blake3: {}", entry.blake3)),
+    ];
+    if let Some(d) = entry.duration_secs {
+        lines.push(Line::from(format!("duration: {d:.2}s")));
+    }
+    frame.render_widget(
+        Paragraph::new(lines)
+            .wrap(Wrap { trim: false })
+            .block(Block::bordered().title(" Details ")),
+        text_area,
+    );
+    let pane = previewing.then(|| {
+        let block = Block::bordered().title(" Preview ");
+        let inner = block.inner(preview_area);
+        frame.render_widget(block, preview_area);
+        inner
+    });
+
+    frame.render_widget(Line::from(app.status.as_str()).yellow(), status_area);
+    frame.render_widget(Line::from(HELP).dim(), help_area);
+
+    pane.filter(|a| a.width > 0 && a.height > 0)
+}
+
+fn resolution(f: &Entry) -> String {
+    match (f.resolution, f.media_type) {
+        (Some((w, h)), _) => format!("{w}x{h}"),
+        (None, MediaType::Video) => "video".into(),
+        (None, MediaType::Image) => "-".into(),
+    }
+}
+
+/// `YYYY-MM-DD` (UTC).
+fn date(secs: u64) -> String {
+    let mut s = output::rfc3339(UNIX_EPOCH + Duration::from_secs(secs));
+    s.truncate(10);
+    s
+}
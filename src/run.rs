@@ -0,0 +1,321 @@
+//! Scan, hash (through the cache) and group: shared by the subcommands.
+
+use std::{
+    fs,
+    io::Write,
+    path::{Path, PathBuf},
+};
+
+use clap::CommandFactory;
+use img_hash::ImageHash;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    args::{Cli, HashOpts, MatchOpts, ScanArgs, VideoOpts},
+    cache,
+    errors::{AppError, SavedHashesError},
+    grouping, image_pipeline,
+    scan::{self, scan_files},
+    types::{self, MediaType},
+    video,
+};
+
+/// Hashed files of one scan, with the settings they were hashed with.
+/// Saved as is by `scan --save-hashes`.
+#[derive(Serialize, Deserialize)]
+pub struct Hashed {
+    /// Scanned directory
+    pub directory: PathBuf,
+    pub cfg: types::AppConfig,
+    pub media: Vec<MediaType>,
+    pub files_found: usize,
+    pub results: Vec<types::PipelineResult>,
+    pub errors: Vec<types::FileError>,
+}
+
+impl Hashed {
+    /// Indices of images and of videos within `results`.
+    pub fn by_media_type(&self) -> (Vec<usize>, Vec<usize>) {
+        (0..self.results.len()).partition(|&i| self.results[i].media_type == MediaType::Image)
+    }
+}
+
+/// Hashing settings for `media_paths`; features needed for matching (colour, tiles, audio,
+/// metadata) are off, for the caller to switch on.
+pub fn config(
+    hashing: &HashOpts,
+    video_opts: &VideoOpts,
+    media_paths: Vec<PathBuf>,
+) -> types::AppConfig {
+    types::AppConfig {
+        media_paths,
+        hash_algs: hashing.hash_algs(),
+        hash_w: hashing.hash_w,
+        hash_h: hashing.hash_h,
+        parallelism: hashing.parallel,
+        autocrop: hashing.autocrop,
+        alpha: hashing.alpha,
+        color: false,
+        crop_resistant: false,
+        with_metadata: false,
+        sample_start: video_opts.sample_start,
+        sample_count: video_opts.sample_count,
+        sample_window: video_opts.sample_window,
+        sample_interval: video_opts.sample_interval.max(0.0),
+        aggregation: video_opts.aggregation,
+        audio: false,
+        decode_timeout: video_opts.decode_timeout.max(0.0),
+        retry_failed: hashing.retry_failed,
+    }
+}
+
+/// Scan `args.directory` and hash every file; `None` if no files were found.
+pub fn hash_files(args: &ScanArgs) -> Result<Option<Hashed>, AppError> {
+    let matching = &args.matching;
+    let media = args.media_types();
+
+    // Scan
+    let extensions: Vec<&str> = media.iter().flat_map(|m| m.extensions()).copied().collect();
+    let media_paths = scan_files(&args.directory, &extensions);
+    let files_found = media_paths.len();
+    eprintln!(
+        "Found {} file(s) under \"{}\"",
+        media_paths.len(),
+        args.directory.display()
+    );
+    if media_paths.is_empty() {
+        return Ok(None);
+    }
+
+    // Build AppConfig
+    let app_cfg = types::AppConfig {
+        color: matching.color != types::ColorMode::Off,
+        crop_resistant: matching.crop_resistant,
+        with_metadata: args.with_metadata,
+        sample_interval: match (matching.video_compare, args.video_opts.sample_interval) {
+            // Clip detection needs a dense fingerprint
+            (types::VideoCompare::Clips, i) if i <= 0.0 => types::DEFAULT_CLIP_SAMPLE_INTERVAL,
+            (_, i) => i.max(0.0),
+        },
+        audio: matching.match_on != types::MatchOn::Visual,
+        ..config(&args.hashing, &args.video_opts, media_paths)
+    };
+    // Fail on bad weights before hashing, not after
+    fusion(&app_cfg, matching);
+
+    let (results, errors) = hash_paths(&app_cfg, &args.hashing.cache_file)?;
+    Ok(Some(Hashed {
+        directory: args.directory.clone(),
+        cfg: app_cfg,
+        media,
+        files_found,
+        results,
+        errors,
+    }))
+}
+
+/// Hash `cfg.media_paths` through the cache at `cache_file` (next to the executable).
+pub fn hash_paths(
+    app_cfg: &types::AppConfig,
+    cache_file: &Path,
+) -> Result<(Vec<types::PipelineResult>, Vec<types::FileError>), AppError> {
+    // Cache
+    let cache_path = cache::cache_path(cache_file)?;
+    let mut cache = cache::load_cache(&cache_path)?;
+
+    // Run Pipelines on one shared Rayon pool (each mutates `cache` in place)
+    let (video_paths, image_paths): (Vec<_>, Vec<_>) = app_cfg
+        .media_paths
+        .iter()
+        .cloned()
+        .partition(|p| scan::has_allowed_extension(p, types::VIDEO_EXTENSIONS));
+    let hashed = run_in_pool(app_cfg.parallelism, || {
+        let mut results: Vec<types::PipelineResult> = Vec::new();
+        let mut errors: Vec<types::FileError> = Vec::new();
+        if !image_paths.is_empty() {
+            let cfg = types::AppConfig {
+                media_paths: image_paths,
+                ..app_cfg.clone()
+            };
+            let (ok, failed) = image_pipeline::run(cfg, &mut cache)?;
+            results.extend(ok);
+            errors.extend(failed);
+        }
+        if !video_paths.is_empty() {
+            let cfg = types::AppConfig {
+                media_paths: video_paths,
+                ..app_cfg.clone()
+            };
+            let (ok, failed) = video::pipeline::run(cfg, &mut cache)?;
+            results.extend(ok);
+            errors.extend(failed);
+        }
+        Ok((results, errors))
+    })?;
+
+    // Save Cache
+    cache::save_cache(&cache_path, &cache)?;
+    Ok(hashed)
+}
+
+/// Multi-hash fusion for `cfg.hash_algs`; exits with a usage error on bad `--fusion-weights`.
+pub fn fusion(cfg: &types::AppConfig, matching: &MatchOpts) -> grouping::HashFusion {
+    let weights = &matching.fusion_weights;
+    let weights_error = match weights.len() {
+        0 => None,
+        n if n != cfg.hash_algs.len() => Some(format!(
+            "--fusion-weights needs one weight per --hash-alg ({n} given, {} expected)",
+            cfg.hash_algs.len()
+        )),
+        _ if weights.iter().any(|w| !w.is_finite() || *w < 0.0)
+            || weights.iter().sum::<f64>() <= 0.0 =>
+        {
+            Some("--fusion-weights must be non-negative and not all zero".into())
+        }
+        _ => None,
+    };
+    if let Some(msg) = weights_error {
+        Cli::command()
+            .error(clap::error::ErrorKind::ValueValidation, msg)
+            .exit();
+    }
+    grouping::HashFusion {
+        algs: cfg.hash_algs.clone(),
+        mode: matching.fusion,
+        weights: weights.clone(),
+    }
+}
+
+/// Group near duplicates within each media type (Calculate Hamming Distance), sorted.
+pub fn group(threshold: u32, matching: &MatchOpts, hashed: &Hashed) -> Vec<grouping::Group> {
+    let fusion = fusion(&hashed.cfg, matching);
+    let (images, videos) = hashed.by_media_type();
+    let results = &hashed.results;
+    let mut groups = grouping::group_duplicates(
+        results,
+        &images,
+        threshold,
+        &fusion,
+        &grouping::ImageChecks {
+            color: grouping::ColorCheck {
+                mode: matching.color,
+                threshold: matching.color_threshold,
+            },
+            min_tile_match: matching.crop_resistant.then_some(matching.min_tile_match),
+        },
+        None,
+        &grouping::MetaGate::default(),
+    );
+    // Videos: skip pairs whose duration / aspect ratio differ too much
+    let gate = grouping::MetaGate {
+        max_duration_diff: matching.max_duration_diff,
+        max_aspect_diff: matching.max_aspect_diff,
+    };
+    // Videos: `--match-on both` also requires the audio fingerprints to match
+    let audio_threshold =
+        (matching.match_on == types::MatchOn::Both).then_some(matching.audio_threshold);
+    groups.extend(match (matching.match_on, matching.video_compare) {
+        (types::MatchOn::Audio, _) => {
+            grouping::group_audio(results, &videos, matching.audio_threshold, &gate)
+        }
+        (_, types::VideoCompare::Sequence) => grouping::group_sequences(
+            results,
+            &videos,
+            threshold,
+            matching.min_similarity,
+            audio_threshold,
+            &gate,
+        ),
+        _ => grouping::group_duplicates(
+            results,
+            &videos,
+            threshold,
+            &fusion,
+            &grouping::ImageChecks::OFF,
+            audio_threshold,
+            &gate,
+        ),
+    });
+    grouping::sort_groups(&mut groups);
+    groups
+}
+
+/// Saved hashes file: `Hashed` plus versions.
+#[derive(Serialize, Deserialize)]
+struct SavedHashes<H> {
+    version: u32,
+    tool_version: String,
+    #[serde(flatten)]
+    hashed: H,
+}
+
+/// Write `hashed` for `rust-phash group`.
+pub fn save_hashes(path: &Path, hashed: &Hashed) -> Result<(), SavedHashesError> {
+    let saved = SavedHashes {
+        version: types::SAVED_HASHES_VERSION,
+        tool_version: types::TOOL_VERSION.to_string(),
+        hashed,
+    };
+    let mut file = fs::File::create(path)?;
+    file.write_all(&serde_json::to_vec(&saved)?)?;
+    Ok(())
+}
+
+/// Read hashes written by `scan --save-hashes`.
+pub fn load_hashes(path: &Path) -> Result<Hashed, SavedHashesError> {
+    let saved: SavedHashes<Hashed> = serde_json::from_slice(&fs::read(path)?)?;
+    if saved.version != types::SAVED_HASHES_VERSION {
+        return Err(SavedHashesError::VersionMismatch {
+            expected: types::SAVED_HASHES_VERSION,
+            found: saved.version,
+        });
+    }
+    let mut hashed = saved.hashed;
+    if hashed.cfg.hash_algs.is_empty() {
+        return Err(SavedHashesError::Invalid {
+            path: path.to_path_buf(),
+            reason: "no hash algorithms".into(),
+        });
+    }
+    for r in &hashed.results {
+        validate(r, hashed.cfg.hash_algs.len()).map_err(|reason| SavedHashesError::Invalid {
+            path: r.path.clone(),
+            reason,
+        })?;
+    }
+    hashed.cfg.media_paths = hashed.results.iter().map(|r| r.path.clone()).collect();
+    Ok(hashed)
+}
+
+/// A saved result is used as is by grouping: one valid hash per algorithm, valid frames and audio.
+fn validate(r: &types::PipelineResult, hash_algs: usize) -> Result<(), String> {
+    if r.hashes.len() != hash_algs {
+        return Err(format!(
+            "{} hash(es), expected one per algorithm ({hash_algs})",
+            r.hashes.len()
+        ));
+    }
+    let frames = r.frames.iter().flatten().map(|f| &f.hash);
+    for hash in r.hashes.iter().chain(frames).chain(&r.audio_hash) {
+        if ImageHash::<Box<[u8]>>::from_base64(hash).is_err() {
+            return Err(format!("invalid base64 hash {hash:?}"));
+        }
+    }
+    Ok(())
+}
+
+/// Run `f` on a fixed-size Rayon pool, or the global pool if `parallelism` is 0.
+fn run_in_pool<T, F>(parallelism: usize, f: F) -> Result<T, AppError>
+where
+    F: FnOnce() -> Result<T, AppError> + Send,
+    T: Send,
+{
+    if parallelism == 0 {
+        // Let Rayon decide
+        return f();
+    }
+    let pool = rayon::ThreadPoolBuilder::new()
+        .num_threads(parallelism)
+        .build()?;
+    pool.install(f)
+}
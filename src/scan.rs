@@ -2,10 +2,10 @@ use std::path::{Path, PathBuf};
 use walkdir::WalkDir;
 
 /// Checks if a path has an allowed file extension (case-insensitive).
-fn has_allowed_extension(path: &Path, allowed_exts: &[&str]) -> bool {
+pub fn has_allowed_extension(path: &Path, allowed_exts: &[&str]) -> bool {
     path.extension()
         .and_then(|ext| ext.to_str())
-        .map_or(false, |ext_str| {
+        .is_some_and(|ext_str| {
             allowed_exts
                 .iter()
                 .any(|&allowed| ext_str.eq_ignore_ascii_case(allowed))
@@ -0,0 +1,372 @@
+//! Cleanup script (`--emit-script`): per group, the kept file and `rm` (or `mv` to `--quarantine`)
+//! lines for the rest, to review before running. The script first checks that every listed
+//! file still has the BLAKE3 it had when scanned, and does nothing otherwise.
+
+use std::{
+    cmp::Reverse,
+    fs, io,
+    path::{self, Path, PathBuf},
+};
+
+use clap::ValueEnum;
+
+use crate::{
+    grouping::Group,
+    hashing,
+    types::{KeepPolicy, PipelineResult, TOOL_VERSION},
+};
+
+/// Where to get `b3sum`, which the script uses to check hashes.
+const B3SUM_URL: &str = "https://github.com/BLAKE3-team/BLAKE3";
+
+pub struct ScriptOptions<'a> {
+    pub keep: KeepPolicy,
+    /// Move duplicates here (mirroring their path under `root`) instead of deleting them.
+    pub quarantine: Option<&'a Path>,
+    /// Scanned directory
+    pub root: &'a Path,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Shell {
+    Sh,
+    PowerShell,
+}
+
+impl Shell {
+    fn for_path(path: &Path) -> Self {
+        match path.extension() {
+            Some(ext) if ext.eq_ignore_ascii_case("ps1") => Shell::PowerShell,
+            _ => Shell::Sh,
+        }
+    }
+}
+
+/// Write the cleanup script for `groups` to `path`; the shell follows the extension.
+pub fn write_script(
+    groups: &[Group],
+    items: &[PipelineResult],
+    opts: &ScriptOptions,
+    path: &Path,
+) -> io::Result<()> {
+    let shell = Shell::for_path(path);
+    let quarantine = opts.quarantine.map(path::absolute).transpose()?;
+    let mut out: Vec<u8> = Vec::new();
+
+    // Header
+    let policy = opts
+        .keep
+        .to_possible_value()
+        .map(|v| v.get_name().to_string())
+        .unwrap_or_default();
+    let lines = [
+        format!(
+            "rust-phash {TOOL_VERSION} cleanup script: {} group(s), keep: {policy}",
+            groups.len()
+        ),
+        "Review before running. Kept files are commented; the other lines act on duplicates."
+            .into(),
+    ];
+    match shell {
+        Shell::Sh => {
+            out.extend(b"#!/bin/sh\n");
+            for line in &lines {
+                out.extend(format!("# {line}\n").as_bytes());
+            }
+            out.extend(SH_HEADER.replace("{B3SUM_URL}", B3SUM_URL).as_bytes());
+        }
+        Shell::PowerShell => {
+            // BOM: Windows PowerShell 5.1 reads BOM-less scripts as ANSI
+            out.extend("\u{feff}".as_bytes());
+            for line in &lines {
+                out.extend(format!("# {line}\n").as_bytes());
+            }
+            out.extend(PS_HEADER.replace("{B3SUM_URL}", B3SUM_URL).as_bytes());
+        }
+    }
+
+    // Verify every listed file (kept ones too: a changed keeper makes the group stale)
+    for g in groups {
+        for m in &g.members {
+            let pr = &items[m.index];
+            let file = quote(shell, &path::absolute(&pr.path)?);
+            let command = match shell {
+                Shell::Sh => "verify",
+                Shell::PowerShell => "Test-Blake3",
+            };
+            out.extend(format!("{command} '{}' ", pr.blake3).as_bytes());
+            out.extend(file);
+            out.push(b'\n');
+        }
+    }
+
+    // Actions
+    for (gi, g) in groups.iter().enumerate() {
+        let keep = keeper(g, items, opts.keep);
+        out.extend(
+            format!(
+                "\n# Group {} ({} files, avg dist {:.2} bits)\n",
+                gi + 1,
+                g.members.len(),
+                g.avg_dist_bits
+            )
+            .as_bytes(),
+        );
+        for (mi, m) in g.members.iter().enumerate() {
+            let file = path::absolute(&items[m.index].path)?;
+            if mi == keep {
+                // Escaped, so a newline in the name can't end the comment
+                out.extend(format!("# keep {:?}\n", file.display().to_string()).as_bytes());
+                continue;
+            }
+            let action = match &quarantine {
+                None => remove(shell, &file),
+                Some(dir) => move_to(shell, &file, &quarantine_path(dir, opts.root, &file)),
+            };
+            out.extend(action);
+            out.extend(format!("  # dist {} bits\n", m.dist_bits).as_bytes());
+        }
+    }
+
+    fs::write(path, out)
+}
+
+/// Position within the group of the file to keep; ties go to the earlier member.
+fn keeper(group: &Group, items: &[PipelineResult], policy: KeepPolicy) -> usize {
+    let files = group.members.iter().map(|m| &items[m.index]);
+    match policy {
+        KeepPolicy::First => 0,
+        KeepPolicy::Largest => best_by(files, size),
+        KeepPolicy::Smallest => best_by(files, |pr| Reverse(size(pr))),
+        KeepPolicy::Newest => best_by(files, modified),
+        KeepPolicy::Oldest => best_by(files, |pr| Reverse(modified(pr).unwrap_or(u64::MAX))),
+        KeepPolicy::Resolution => best_by(files, pixels),
+        KeepPolicy::ShortestPath => best_by(files, |pr| Reverse(pr.path.as_os_str().len())),
+    }
+}
+
+/// Index of the first file with the greatest key.
+fn best_by<'a, K: Ord>(
+    files: impl Iterator<Item = &'a PipelineResult>,
+    key: impl Fn(&PipelineResult) -> K,
+) -> usize {
+    let mut best: Option<(usize, K)> = None;
+    for (i, pr) in files.enumerate() {
+        let k = key(pr);
+        if best.as_ref().is_none_or(|(_, b)| k > *b) {
+            best = Some((i, k));
+        }
+    }
+    best.map_or(0, |(i, _)| i)
+}
+
+// File facts come from `--with-metadata` when collected, otherwise from the file system.
+fn size(pr: &PipelineResult) -> u64 {
+    match &pr.info {
+        Some(info) => info.size_bytes,
+        None => fs::metadata(&pr.path).map_or(0, |m| m.len()),
+    }
+}
+
+fn modified(pr: &PipelineResult) -> Option<u64> {
+    match &pr.info {
+        Some(info) => info.modified_secs,
+        None => fs::metadata(&pr.path)
+            .and_then(|m| m.modified())
+            .ok()
+            .and_then(|t| t.duration_since(std::time::UNIX_EPOCH).ok())
+            .map(|d| d.as_secs()),
+    }
+}
+
+fn pixels(pr: &PipelineResult) -> u64 {
+    let (w, h) = match (&pr.info, &pr.meta) {
+        (Some(info), _) => (info.media.width, info.media.height),
+        (None, Some(meta)) => (meta.width, meta.height),
+        (None, None) => hashing::image_info(&pr.path).map_or((0, 0), |m| (m.width, m.height)),
+    };
+    w as u64 * h as u64
+}
+
+/// `dir` + the file's path under the scanned directory (or just its name).
+fn quarantine_path(dir: &Path, root: &Path, file: &Path) -> PathBuf {
+    let root = path::absolute(root).unwrap_or_else(|_| root.to_path_buf());
+    match file.strip_prefix(&root) {
+        Ok(rel) => dir.join(rel),
+        Err(_) => dir.join(file.file_name().unwrap_or(file.as_os_str())),
+    }
+}
+
+fn remove(shell: Shell, file: &Path) -> Vec<u8> {
+    let mut line = Vec::new();
+    match shell {
+        Shell::Sh => line.extend(b"rm -- "),
+        Shell::PowerShell => line.extend(b"Remove-Item -LiteralPath "),
+    }
+    line.extend(quote(shell, file));
+    line
+}
+
+fn move_to(shell: Shell, file: &Path, dest: &Path) -> Vec<u8> {
+    let parent = dest.parent().unwrap_or(dest);
+    let mut line = Vec::new();
+    match shell {
+        Shell::Sh => {
+            // `mv -n` alone would silently leave the file in place if the destination exists
+            line.extend(b"absent ");
+            line.extend(quote(shell, dest));
+            line.extend(b" && mkdir -p -- ");
+            line.extend(quote(shell, parent));
+            line.extend(b" && mv -n -- ");
+            line.extend(quote(shell, file));
+            line.push(b' ');
+            line.extend(quote(shell, dest));
+        }
+        Shell::PowerShell => {
+            // Move-Item fails (and stops the script) if the destination exists
+            line.extend(b"New-Item -ItemType Directory -Force -Path ");
+            line.extend(quote(shell, parent));
+            line.extend(b" | Out-Null; Move-Item -LiteralPath ");
+            line.extend(quote(shell, file));
+            line.extend(b" -Destination ");
+            line.extend(quote(shell, dest));
+        }
+    }
+    line
+}
+
+/// Single-quoted literal; nothing inside is expanded by either shell.
+/// - sh: `'` becomes `'\''`. Raw bytes are kept, so non-UTF-8 names survive.
+/// - PowerShell: `'` (and the typographic quotes it also accepts) are doubled.
+fn quote(shell: Shell, path: &Path) -> Vec<u8> {
+    match shell {
+        Shell::Sh => {
+            let mut out = vec![b'\''];
+            for &b in path_bytes(path).iter() {
+                match b {
+                    b'\'' => out.extend(b"'\\''"),
+                    b => out.push(b),
+                }
+            }
+            out.push(b'\'');
+            out
+        }
+        Shell::PowerShell => {
+            let mut out = String::from("'");
+            for c in path.to_string_lossy().chars() {
+                if matches!(c, '\'' | '\u{2018}' | '\u{2019}' | '\u{201a}' | '\u{201b}') {
+                    out.push(c);
+                }
+                out.push(c);
+            }
+            out.push('\'');
+            out.into_bytes()
+        }
+    }
+}
+
+#[cfg(unix)]
+fn path_bytes(path: &Path) -> std::borrow::Cow<'_, [u8]> {
+    use std::os::unix::ffi::OsStrExt;
+    std::borrow::Cow::Borrowed(path.as_os_str().as_bytes())
+}
+
+#[cfg(not(unix))]
+fn path_bytes(path: &Path) -> std::borrow::Cow<'_, [u8]> {
+    std::borrow::Cow::Owned(path.to_string_lossy().into_owned().into_bytes())
+}
+
+const SH_HEADER: &str = r#"set -eu
+
+command -v b3sum >/dev/null 2>&1 || { echo "b3sum not found ({B3SUM_URL})" >&2; exit 1; }
+
+# Every file must still have the BLAKE3 it had when scanned, or nothing is done
+verify() {
+  if [ ! -f "$2" ] || [ "$(b3sum --no-names -- "$2")" != "$1" ]; then
+    echo "changed or missing, aborting: $2" >&2
+    exit 1
+  fi
+}
+
+# Quarantine never overwrites an earlier quarantined file
+absent() {
+  if [ -e "$1" ] || [ -L "$1" ]; then
+    echo "already exists, aborting: $1" >&2
+    exit 1
+  fi
+}
+"#;
+
+const PS_HEADER: &str = r#"$ErrorActionPreference = 'Stop'
+
+if (-not (Get-Command b3sum -ErrorAction SilentlyContinue)) { throw "b3sum not found ({B3SUM_URL})" }
+
+# Every file must still have the BLAKE3 it had when scanned, or nothing is done
+# (no `--` for b3sum: PowerShell versions differ on passing it; paths are absolute anyway)
+function Test-Blake3([string]$Hash, [string]$Path) {
+    if (-not (Test-Path -LiteralPath $Path -PathType Leaf) -or (& b3sum --no-names $Path) -ne $Hash) {
+        throw "changed or missing, aborting: $Path"
+    }
+}
+"#;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sh(path: &str) -> String {
+        String::from_utf8(quote(Shell::Sh, Path::new(path))).unwrap()
+    }
+
+    fn ps(path: &str) -> String {
+        String::from_utf8(quote(Shell::PowerShell, Path::new(path))).unwrap()
+    }
+
+    #[test]
+    fn sh_quotes_single_quotes_spaces_and_newlines() {
+        assert_eq!(sh("/a/it's"), r"'/a/it'\''s'");
+        assert_eq!(sh("/a/b c\nd"), "'/a/b c\nd'");
+        assert_eq!(sh("/a/$HOME `x` \\"), "'/a/$HOME `x` \\'");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn sh_quoting_round_trips_through_the_shell() {
+        let names = [
+            "/a/it's",
+            "/a/b c\nd\n",
+            "/a/$(x) `y` $z \\ \"q\"",
+            "-n",
+            "'''",
+        ];
+        for name in names {
+            let script = format!("printf %s {}", sh(name));
+            let out = std::process::Command::new("sh")
+                .args(["-c", &script])
+                .output()
+                .expect("sh");
+            assert_eq!(String::from_utf8_lossy(&out.stdout), name);
+        }
+    }
+
+    #[test]
+    fn leading_dash_is_not_an_option() {
+        assert_eq!(remove(Shell::Sh, Path::new("-rf")), b"rm -- '-rf'");
+        assert_eq!(
+            remove(Shell::PowerShell, Path::new("-Recurse")),
+            b"Remove-Item -LiteralPath '-Recurse'"
+        );
+    }
+
+    #[test]
+    fn powershell_doubles_every_single_quote() {
+        assert_eq!(ps(r"C:\it's"), r"'C:\it''s'");
+        assert_eq!(ps("C:\\b c\nd"), "'C:\\b c\nd'");
+        assert_eq!(ps("C:\\$env:x `y"), "'C:\\$env:x `y'");
+        // PowerShell also ends a quoted string at typographic single quotes
+        assert_eq!(
+            ps("a\u{2018}b\u{2019}c\u{201a}d\u{201b}e"),
+            "'a\u{2018}\u{2018}b\u{2019}\u{2019}c\u{201a}\u{201a}d\u{201b}\u{201b}e'"
+        );
+    }
+}
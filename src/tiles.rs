@@ -0,0 +1,273 @@
+//! Crop-resistant hashing: a grid of overlapping square tiles, each hashed separately,
+//! so a crop of an image (e.g. a square crop of a landscape shot) still shares tiles with it.
+
+use img_hash::image::{DynamicImage, GenericImageView, imageops::FilterType};
+use img_hash::{Hasher, ImageHash};
+
+use crate::types::TileHash;
+
+/// Tile side = short side / `TILE_DIVISOR`; tiles start every short side / `STEP_DIVISOR`.
+/// Tile size follows the short side, which a crop to a narrower aspect ratio keeps.
+const TILE_DIVISOR: u32 = 2;
+const STEP_DIVISOR: u32 = 8;
+
+/// Images are shrunk before tiling so each tile is this multiple of the hash size.
+const DOWNSCALE_FACTOR: u32 = 8;
+
+/// Tiles with fewer (or more) than this fraction of bits set are near-flat (sky, walls,
+/// blank paper) and match almost anything; they are ignored.
+const FLAT_FRACTION: f64 = 0.1;
+
+/// Square region in original image pixels.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Region {
+    pub x: u32,
+    pub y: u32,
+    pub size: u32,
+}
+
+/// A tile of the group seed matched to a tile of the member.
+#[derive(Debug, Clone, Copy)]
+pub struct TilePair {
+    pub seed: Region,
+    pub member: Region,
+    pub dist_bits: u32,
+}
+
+#[derive(Debug, Clone)]
+pub struct TileMatch {
+    /// Tiles of the image with fewer tiles that found a match, out of `total`.
+    pub matched: usize,
+    pub total: usize,
+    /// Mean Hamming distance of the matched tiles.
+    pub mean_dist_bits: f64,
+    pub pairs: Vec<TilePair>,
+}
+
+impl TileMatch {
+    /// Bounding box (x, y, w, h) of the matched tiles in the seed.
+    pub fn seed_bounds(&self) -> (u32, u32, u32, u32) {
+        bounds(self.pairs.iter().map(|p| p.seed))
+    }
+
+    /// Bounding box (x, y, w, h) of the matched tiles in the member.
+    pub fn member_bounds(&self) -> (u32, u32, u32, u32) {
+        bounds(self.pairs.iter().map(|p| p.member))
+    }
+}
+
+fn bounds(regions: impl Iterator<Item = Region>) -> (u32, u32, u32, u32) {
+    let (x0, y0, x1, y1) = regions.fold((u32::MAX, u32::MAX, 0, 0), |(x0, y0, x1, y1), r| {
+        (
+            x0.min(r.x),
+            y0.min(r.y),
+            x1.max(r.x + r.size),
+            y1.max(r.y + r.size),
+        )
+    });
+    match x1 > x0 {
+        true => (x0, y0, x1 - x0, y1 - y0),
+        false => (0, 0, 0, 0),
+    }
+}
+
+/// Hash every tile of a (pre-processed) image. Regions are in original pixels.
+pub fn tile_hashes(img: &DynamicImage, hasher: &Hasher, hash_w: u32, hash_h: u32) -> Vec<TileHash> {
+    let (w, h) = img.dimensions();
+    let short = w.min(h);
+    if short / STEP_DIVISOR == 0 {
+        return Vec::new();
+    }
+
+    // Shrink so a tile is DOWNSCALE_FACTOR × the hash size (never upscale)
+    let target_tile = hash_w.max(hash_h).saturating_mul(DOWNSCALE_FACTOR).max(1);
+    let scale = (target_tile as f64 / (short / TILE_DIVISOR) as f64).min(1.0);
+    let small = match scale < 1.0 {
+        true => img.resize_exact(
+            ((w as f64 * scale).round() as u32).max(1),
+            ((h as f64 * scale).round() as u32).max(1),
+            FilterType::Triangle,
+        ),
+        false => img.clone(),
+    };
+
+    let (tile, step) = (short / TILE_DIVISOR, short / STEP_DIVISOR);
+    let to_small = |v: u32| (v as f64 * scale).round() as u32;
+    let (sw, sh) = small.dimensions();
+    let positions = |len: u32| (0..=len.saturating_sub(tile)).step_by(step as usize);
+
+    let mut out = Vec::new();
+    for y in positions(h) {
+        for x in positions(w) {
+            let (sx, sy) = (to_small(x).min(sw - 1), to_small(y).min(sh - 1));
+            let ss = to_small(tile).min(sw - sx).min(sh - sy).max(1);
+            let crop = small.crop_imm(sx, sy, ss, ss);
+            out.push(TileHash {
+                x,
+                y,
+                size: tile,
+                hash: hasher.hash_image(&crop).to_base64(),
+            });
+        }
+    }
+    out
+}
+
+/// Decode cached tiles, dropping near-flat ones.
+pub fn decode_tiles(tiles: &[TileHash]) -> Vec<(Region, ImageHash)> {
+    tiles
+        .iter()
+        .filter_map(|t| {
+            let hash = ImageHash::<Box<[u8]>>::from_base64(&t.hash).ok()?;
+            let bits = hash.as_bytes().len() * 8;
+            let ones: u32 = hash.as_bytes().iter().map(|b| b.count_ones()).sum();
+            let fraction = ones as f64 / bits.max(1) as f64;
+            let region = Region {
+                x: t.x,
+                y: t.y,
+                size: t.size,
+            };
+            (FLAT_FRACTION..=1.0 - FLAT_FRACTION)
+                .contains(&fraction)
+                .then_some((region, hash))
+        })
+        .collect()
+}
+
+/// Match the tiles of the image with fewer tiles (the likely crop) against the other's.
+/// Matches when at least `min_fraction` of them have a tile within `threshold` bits.
+pub fn match_tiles(
+    seed: &[(Region, ImageHash)],
+    member: &[(Region, ImageHash)],
+    threshold: u32,
+    min_fraction: f64,
+) -> Option<TileMatch> {
+    let seed_is_crop = seed.len() <= member.len();
+    let (small, large) = match seed_is_crop {
+        true => (seed, member),
+        false => (member, seed),
+    };
+    if small.is_empty() {
+        return None;
+    }
+
+    let pairs: Vec<TilePair> = small
+        .iter()
+        .filter_map(|(region, hash)| {
+            let (best, dist) = large
+                .iter()
+                .map(|(r, h)| (*r, hash.dist(h)))
+                .min_by_key(|(_, d)| *d)?;
+            (dist <= threshold).then_some(match seed_is_crop {
+                true => TilePair {
+                    seed: *region,
+                    member: best,
+                    dist_bits: dist,
+                },
+                false => TilePair {
+                    seed: best,
+                    member: *region,
+                    dist_bits: dist,
+                },
+            })
+        })
+        .collect();
+
+    let total = small.len();
+    if pairs.is_empty() || (pairs.len() as f64) < min_fraction * total as f64 {
+        return None;
+    }
+    let mean_dist_bits = pairs.iter().map(|p| p.dist_bits as f64).sum::<f64>() / pairs.len() as f64;
+    Some(TileMatch {
+        matched: pairs.len(),
+        total,
+        mean_dist_bits,
+        pairs,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        hashing::build_hasher,
+        types::{DEFAULT_HASH_ALG, DEFAULT_MIN_TILE_MATCH, DEFAULT_THRESHOLD},
+    };
+    use img_hash::image::{GrayImage, Luma};
+
+    /// Smooth, non-repeating texture; another `k` gives an unrelated one.
+    fn textured(w: u32, h: u32, k: f64) -> DynamicImage {
+        DynamicImage::ImageLuma8(GrayImage::from_fn(w, h, |x, y| {
+            let (x, y) = (x as f64 * k, y as f64 * k);
+            let v = 128.0
+                + 60.0 * (x / 46.0 + 2.1 * (y / 74.0).sin()).sin()
+                + 60.0 * (y / 34.0 + 1.7 * (x / 58.0).sin()).cos();
+            Luma([v.clamp(0.0, 255.0) as u8])
+        }))
+    }
+
+    fn hashes(img: &DynamicImage) -> Vec<TileHash> {
+        tile_hashes(img, &build_hasher(DEFAULT_HASH_ALG, 16, 16), 16, 16)
+    }
+
+    fn tiles(img: &DynamicImage) -> Vec<(Region, ImageHash)> {
+        decode_tiles(&hashes(img))
+    }
+
+    fn matched(a: &[(Region, ImageHash)], b: &[(Region, ImageHash)]) -> Option<TileMatch> {
+        match_tiles(a, b, DEFAULT_THRESHOLD, DEFAULT_MIN_TILE_MATCH)
+    }
+
+    #[test]
+    fn square_centre_crop_matches_its_landscape_original() {
+        let landscape = textured(640, 360, 1.0);
+        let crop = landscape.crop_imm(140, 0, 360, 360);
+        let (landscape, crop) = (tiles(&landscape), tiles(&crop));
+        let m = matched(&landscape, &crop).expect("crop matches");
+        assert!(
+            m.matched as f64 >= DEFAULT_MIN_TILE_MATCH * m.total as f64,
+            "{m:?}"
+        );
+        // Tiles start every 45px, so the nearest tile to x = 140 is at 135
+        assert_eq!(m.seed_bounds(), (135, 0, 360, 360));
+        assert_eq!(m.member_bounds(), (0, 0, 360, 360));
+
+        // Either way round, regions stay with their own image
+        let m = matched(&crop, &landscape).expect("crop matches");
+        assert_eq!(m.seed_bounds(), (0, 0, 360, 360));
+        assert_eq!(m.member_bounds(), (135, 0, 360, 360));
+    }
+
+    #[test]
+    fn unrelated_image_does_not_match() {
+        let a = tiles(&textured(640, 360, 1.0));
+        let b = tiles(&textured(360, 360, 1.7));
+        assert!(!a.is_empty() && !b.is_empty());
+        assert!(matched(&a, &b).is_none());
+    }
+
+    #[test]
+    fn flat_tiles_are_dropped() {
+        let flat = hashes(&DynamicImage::ImageLuma8(GrayImage::from_pixel(
+            640,
+            360,
+            Luma([90]),
+        )));
+        assert!(!flat.is_empty());
+        assert!(decode_tiles(&flat).is_empty());
+
+        // Flat left half, textured right half: only tiles reaching into the texture remain
+        let texture = textured(640, 360, 1.0).to_luma8();
+        let half = hashes(&DynamicImage::ImageLuma8(GrayImage::from_fn(
+            640,
+            360,
+            |x, y| match x < 320 {
+                true => Luma([90]),
+                false => *texture.get_pixel(x, y),
+            },
+        )));
+        let kept = decode_tiles(&half);
+        assert!(!kept.is_empty() && kept.len() < half.len());
+        assert!(kept.iter().all(|(r, _)| r.x + r.size > 320), "{kept:?}");
+    }
+}
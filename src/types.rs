@@ -8,9 +8,15 @@ pub const IMAGE_EXTENSIONS: &[&str] = &[
 
 pub const VIDEO_EXTENSIONS: &[&str] = &["mp4", "m4v", "mov", "mkv", "avi", "webm", "mpg", "mpeg"];
 
+/// Output format.
+pub const DEFAULT_OUTPUT_FORMAT: OutputFormat = OutputFormat::Pretty;
+
 /// Perceptual hash algorithm.
 pub const DEFAULT_HASH_ALG: HashAlg = HashAlg::DoubleGradient;
 
+/// Multi-hash fusion
+pub const DEFAULT_FUSION: Fusion = Fusion::All;
+
 /// Hash size
 pub const DEFAULT_HASH_W: u32 = 16;
 pub const DEFAULT_HASH_H: u32 = 16;
@@ -18,6 +24,30 @@ pub const DEFAULT_HASH_H: u32 = 16;
 /// Hamming distance threshold.
 pub const DEFAULT_THRESHOLD: u32 = 10;
 
+/// How transparent pixels are flattened before hashing (opt-in: raw channels, as before).
+pub const DEFAULT_ALPHA: AlphaPolicy = AlphaPolicy::None;
+
+/// Colour signature: off, or flag / exclude pairs whose colours differ.
+pub const DEFAULT_COLOR_MODE: ColorMode = ColorMode::Off;
+
+/// Colour signature distance threshold (mean chroma difference, 0-255 scale).
+pub const DEFAULT_COLOR_THRESHOLD: u32 = 12;
+
+/// Crop-resistant ~ Fraction of the cropped image's tiles that must match.
+pub const DEFAULT_MIN_TILE_MATCH: f64 = 0.5;
+
+/// Cleanup script ~ Which file of each group is kept.
+pub const DEFAULT_KEEP_POLICY: KeepPolicy = KeepPolicy::Largest;
+
+/// Review ~ Where removed files are moved (relative to the working directory).
+pub const DEFAULT_TRASH_DIR: &str = ".phash-trash";
+
+/// Review ~ Action journal (JSON lines), for undo.
+pub const DEFAULT_JOURNAL_FILE: &str = ".phash-journal.jsonl";
+
+/// Review ~ Terminal image previews.
+pub const DEFAULT_PREVIEW: PreviewMode = PreviewMode::Auto;
+
 /// Video ~ Frame to start sampling from.
 pub const DEFAULT_SAMPLE_START: usize = 0;
 
@@ -27,15 +57,57 @@ pub const DEFAULT_SAMPLE_COUNT: usize = 10;
 /// Video ~ Number of frames to sample over; 0 = auto.
 pub const DEFAULT_SAMPLE_WINDOW: usize = 0;
 
+/// Video ~ Seconds between samples; 0 = off (use sample count).
+pub const DEFAULT_SAMPLE_INTERVAL: f64 = 0.0;
+
+/// Video ~ Seconds between samples when fingerprinting for clip detection and no interval is given.
+pub const DEFAULT_CLIP_SAMPLE_INTERVAL: f64 = 1.0;
+
 /// Video ~ Aggregation strategy default.
 pub const DEFAULT_AGGREGATION: Aggregation = Aggregation::Medoid;
 
+/// Video ~ Comparison strategy default.
+pub const DEFAULT_VIDEO_COMPARE: VideoCompare = VideoCompare::Aggregate;
+
+/// Video ~ Fraction of overlapping frames that must match in sequence comparison.
+pub const DEFAULT_MIN_SIMILARITY: f64 = 0.8;
+
+/// Video ~ Which signal to group videos on.
+pub const DEFAULT_MATCH_ON: MatchOn = MatchOn::Visual;
+
+/// Video ~ Audio fingerprint Hamming distance threshold (out of 384 bits).
+pub const DEFAULT_AUDIO_THRESHOLD: u32 = 40;
+
+/// Video ~ Seconds before decoding a file is abandoned; 0 = no limit.
+pub const DEFAULT_DECODE_TIMEOUT: f64 = 0.0;
+
 /// Default parallelism. If 0, Rayon decides.
 pub const DEFAULT_PARALLELISM: usize = 0;
 
 /// Cache filename
 pub const DEFAULT_CACHE_FILE_NAME: &str = ".phash-cache.json";
 
+/// Media type of a scanned file; each type has its own pipeline and is grouped separately.
+#[derive(
+    Debug, Clone, Copy, ValueEnum, PartialEq, Eq, Serialize, Deserialize, schemars::JsonSchema,
+)]
+#[serde(rename_all = "lowercase")]
+pub enum MediaType {
+    #[value(name = "images", alias = "image")]
+    Image,
+    #[value(name = "videos", alias = "video")]
+    Video,
+}
+
+impl MediaType {
+    pub fn extensions(self) -> &'static [&'static str] {
+        match self {
+            MediaType::Image => IMAGE_EXTENSIONS,
+            MediaType::Video => VIDEO_EXTENSIONS,
+        }
+    }
+}
+
 /// Hashing algorithm choices, mirrored from `img_hash`.
 #[derive(Debug, Clone, Copy, ValueEnum, PartialEq, Eq, Serialize, Deserialize)]
 pub enum HashAlg {
@@ -44,6 +116,106 @@ pub enum HashAlg {
     DoubleGradient,
 }
 
+impl HashAlg {
+    /// CLI name, e.g. "double-gradient".
+    pub fn name(self) -> String {
+        self.to_possible_value()
+            .map(|v| v.get_name().to_string())
+            .unwrap_or_default()
+    }
+}
+
+/// Alpha policy for images with transparency
+/// - None: Hash the raw colour channels (transparent pixels keep whatever colour they store).
+/// - White / Black: Composite onto a solid background.
+/// - Checker: Composite onto an 8px light-grey checkerboard.
+/// - Luma: Hash the alpha channel itself as a grayscale silhouette.
+#[derive(Debug, Clone, Copy, ValueEnum, PartialEq, Eq, Serialize, Deserialize, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum AlphaPolicy {
+    /// Raw colour channels; also assumed for cache entries written before alpha handling.
+    #[default]
+    None,
+    White,
+    Black,
+    Checker,
+    Luma,
+}
+
+/// Output format
+/// - Pretty: Human-readable text.
+/// - Json: One pretty-printed JSON document (run metadata envelope, see `schema/`).
+/// - Ndjson: One JSON object per line (group or match), for streaming consumers.
+/// - Csv: One row per group member (or match), for spreadsheets.
+#[derive(Debug, Clone, Copy, ValueEnum, PartialEq, Eq)]
+pub enum OutputFormat {
+    Pretty,
+    Json,
+    Ndjson,
+    Csv,
+}
+
+/// Bit string order (`hash` command)
+/// - LsbFirst: Bit i of the hash at position i, the order `img_hash` computes and compares bits in
+///   (least significant bit of each byte first).
+/// - MsbFirst: Each byte most significant bit first, so the string reads like the hex.
+#[derive(Debug, Clone, Copy, ValueEnum, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum BitOrder {
+    LsbFirst,
+    MsbFirst,
+}
+
+/// Cleanup script ~ Which file of each group to keep (ties go to the earlier member)
+/// - First: The group's first member (the one distances are measured to).
+/// - Largest / Smallest: By file size.
+/// - Newest / Oldest: By modification time.
+/// - Resolution: Most pixels.
+/// - ShortestPath: Shortest path, e.g. the original over "copy of ..." in a subfolder.
+#[derive(Debug, Clone, Copy, ValueEnum, PartialEq, Eq)]
+pub enum KeepPolicy {
+    First,
+    Largest,
+    Smallest,
+    Newest,
+    Oldest,
+    Resolution,
+    ShortestPath,
+}
+
+/// Review ~ Terminal image previews
+/// - Auto: Kitty graphics protocol if the terminal is known to support it, otherwise off.
+/// - Kitty: Kitty graphics protocol (kitty, WezTerm, Ghostty, Konsole).
+/// - Sixel: Sixel graphics (xterm -ti vt340, foot, mlterm, WezTerm, …).
+/// - Off: Metadata only.
+#[derive(Debug, Clone, Copy, ValueEnum, PartialEq, Eq)]
+pub enum PreviewMode {
+    Auto,
+    Kitty,
+    Sixel,
+    Off,
+}
+
+/// Multi-hash fusion, when several `--hash-alg` are given
+/// - All: Every algorithm's (normalised) distance must be within threshold.
+/// - Weighted: The weighted mean of normalised distances must be within threshold.
+#[derive(Debug, Clone, Copy, ValueEnum, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Fusion {
+    All,
+    Weighted,
+}
+
+/// Colour signature, for images (hashes only see luma)
+/// - Off: Colour is ignored; grayscale and recoloured copies are plain duplicates.
+/// - Flag: Group as usual, but mark members whose colours differ as colour variants.
+/// - Exclude: Only group images whose colours also match.
+#[derive(Debug, Clone, Copy, ValueEnum, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ColorMode {
+    Off,
+    Flag,
+    Exclude,
+}
+
 /// Video ~ Hash aggregation strategy
 /// - Majority: Slower, bitwise majority vote across frame hashes.
 /// - Medoid: Faster, picks the frame with the smallest hamming distance to all others.
@@ -53,10 +225,35 @@ pub enum Aggregation {
     Medoid,
 }
 
+/// Video ~ Comparison strategy
+/// - Aggregate: Compare the single medoid/majority hash per video.
+/// - Sequence: Align per-frame hashes by time offset; tolerates trims and re-edits.
+/// - Clips: Find shorter videos contained within longer ones (dense sampling).
+#[derive(Debug, Clone, Copy, ValueEnum, PartialEq, Eq, Serialize, Deserialize)]
+pub enum VideoCompare {
+    Aggregate,
+    Sequence,
+    Clips,
+}
+
+/// Video ~ Signal used to group videos
+/// - Visual: Perceptual hash of the frames only.
+/// - Audio: Chromagram fingerprint of the audio stream only.
+/// - Both: Visual match that must also match on audio.
+#[derive(Debug, Clone, Copy, ValueEnum, PartialEq, Eq, Serialize, Deserialize)]
+pub enum MatchOn {
+    Visual,
+    Audio,
+    Both,
+}
+
 /// Cache
 ///
 /// Cache Version
-pub const CACHE_VERSION: u32 = 3;
+pub const CACHE_VERSION: u32 = 8;
+
+/// Saved hashes (`scan --save-hashes`) Version
+pub const SAVED_HASHES_VERSION: u32 = 2;
 
 /// Cache schema persisted to JSON.
 #[derive(Debug, Serialize, Deserialize, Default)]
@@ -64,41 +261,310 @@ pub struct CacheFile {
     pub version: u32,
     // pub by_blake3: std::collections::HashMap<String, Vec<CacheEntry>>,
     pub by_blake3: std::collections::BTreeMap<String, Vec<CacheEntry>>,
+    /// Files that failed to hash, so they aren't retried every run.
+    #[serde(default)]
+    pub failed_by_blake3: std::collections::BTreeMap<String, Vec<FailedEntry>>,
 }
 
-/// A single cache entry with the exact parameters used to compute the hash.
-#[derive(Debug, Serialize, Deserialize, Clone)]
-pub struct CacheEntry {
+/// The exact parameters used to compute a hash; an entry is reused only when these match.
+/// Video fields are `None` for images.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct HashParams {
     pub hash_alg: HashAlg,
     pub hash_w: u32,
     pub hash_h: u32,
-    pub perceptual_hash: String,
     // Video
     pub sample_start: Option<usize>,
     pub sample_count: Option<usize>,
     pub sample_window: Option<usize>,
+    #[serde(default)]
+    pub sample_interval: Option<f64>,
     pub aggregation: Option<Aggregation>,
+    // Pre-processing
+    #[serde(default)]
+    pub autocrop: bool,
+    #[serde(default)]
+    pub alpha: AlphaPolicy,
+}
+
+impl HashParams {
+    pub fn from_cfg(cfg: &AppConfig, hash_alg: HashAlg, media_type: MediaType) -> Self {
+        let video = media_type == MediaType::Video;
+        HashParams {
+            hash_alg,
+            hash_w: cfg.hash_w,
+            hash_h: cfg.hash_h,
+            sample_start: video.then_some(cfg.sample_start),
+            sample_count: video.then_some(cfg.sample_count),
+            sample_window: video.then_some(cfg.sample_window),
+            sample_interval: video.then_some(cfg.sample_interval),
+            aggregation: video.then_some(cfg.aggregation),
+            autocrop: cfg.autocrop,
+            // Video frames have no alpha channel
+            alpha: match video {
+                true => AlphaPolicy::None,
+                false => cfg.alpha,
+            },
+        }
+    }
+
+    /// Animated images: image params plus the frame sampling/aggregation they depend on.
+    pub fn for_animation(cfg: &AppConfig, hash_alg: HashAlg) -> Self {
+        HashParams {
+            sample_count: Some(cfg.sample_count),
+            aggregation: Some(cfg.aggregation),
+            ..HashParams::from_cfg(cfg, hash_alg, MediaType::Image)
+        }
+    }
+}
+
+/// A single cache entry with the exact parameters used to compute the hash.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct CacheEntry {
+    #[serde(flatten)]
+    pub params: HashParams,
+    pub perceptual_hash: String,
+    /// Animated image (GIF/APNG/WebP) hashed from sampled frames; params are `for_animation`.
+    #[serde(default)]
+    pub animated: bool,
+    // Video / Animated
+    pub frames: Option<Vec<FrameHash>>,
+    // Audio (`audio` = fingerprint was attempted; `audio_hash` is None for silent videos)
+    #[serde(default)]
+    pub audio: bool,
+    #[serde(default)]
+    pub audio_hash: Option<String>,
+    #[serde(default)]
+    pub meta: Option<VideoMeta>,
+    /// Image ~ Colour signature, if requested (`--color`); not part of the key.
+    #[serde(default)]
+    pub color: Option<String>,
+    /// Image ~ Tile hashes, if requested (`--crop-resistant`); not part of the key.
+    #[serde(default)]
+    pub tiles: Option<Vec<TileHash>>,
+    /// Dimensions and format, read while hashing (absent in older entries).
+    #[serde(default)]
+    pub media: Option<MediaInfo>,
+}
+
+/// Pixel dimensions and format of a file, as decoded.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct MediaInfo {
+    pub width: u32,
+    pub height: u32,
+    /// Image format (e.g. "jpeg") or video container (e.g. "matroska,webm").
+    pub format: String,
+    /// Video ~ Codec (e.g. "h264").
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub codec: Option<String>,
+}
+
+/// Per-file metadata reported with `--with-metadata`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FileInfo {
+    pub size_bytes: u64,
+    /// Seconds since the Unix epoch.
+    pub modified_secs: Option<u64>,
+    pub media: MediaInfo,
+}
+
+impl FileInfo {
+    /// Stat the file; `media` comes from the hashing pass (or cache).
+    pub fn stat(path: &std::path::Path, media: MediaInfo) -> std::io::Result<Self> {
+        let md = std::fs::metadata(path)?;
+        let modified_secs = md
+            .modified()
+            .ok()
+            .and_then(|t| t.duration_since(std::time::UNIX_EPOCH).ok())
+            .map(|d| d.as_secs());
+        Ok(FileInfo {
+            size_bytes: md.len(),
+            modified_secs,
+            media,
+        })
+    }
+}
+
+/// Version of this tool; failures recorded by another version are retried.
+pub const TOOL_VERSION: &str = env!("CARGO_PKG_VERSION");
+
+/// Why a file failed to hash.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FailureKind {
+    Image,
+    Decode,
+    NoSamples,
+    Timeout,
+}
+
+/// A file that failed to hash with these parameters (negative cache).
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct FailedEntry {
+    #[serde(flatten)]
+    pub params: HashParams,
+    pub kind: FailureKind,
+    pub error: String,
+    pub tool_version: String,
+    /// Timeout in effect when it failed (0 = none); a longer timeout retries a timed-out file.
+    #[serde(default)]
+    pub decode_timeout: f64,
+}
+
+/// Video ~ Stream metadata read during decode (size as displayed: square pixels, upright).
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+pub struct VideoMeta {
+    pub duration_secs: Option<f64>,
+    pub width: u32,
+    pub height: u32,
+    pub fps: Option<f64>,
+}
+
+impl VideoMeta {
+    pub fn aspect(&self) -> Option<f64> {
+        (self.width > 0 && self.height > 0).then(|| self.width as f64 / self.height as f64)
+    }
+}
+
+/// Video ~ Maximum difference between two values: relative ("5%") or absolute ("2.5").
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Tolerance {
+    Relative(f64),
+    Absolute(f64),
+}
+
+impl Tolerance {
+    pub fn allows(self, a: f64, b: f64) -> bool {
+        let diff = (a - b).abs();
+        match self {
+            Tolerance::Relative(p) => diff <= p * a.abs().max(b.abs()),
+            Tolerance::Absolute(x) => diff <= x,
+        }
+    }
+}
+
+impl std::str::FromStr for Tolerance {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        let (num, relative) = match s.strip_suffix('%') {
+            Some(n) => (n.trim(), true),
+            None => (s, false),
+        };
+        let v: f64 = num
+            .parse()
+            .map_err(|_| format!("expected a number or percentage (e.g. 5%), got \"{s}\""))?;
+        if !v.is_finite() || v < 0.0 {
+            return Err(format!("must be a non-negative number, got \"{s}\""));
+        }
+        Ok(match relative {
+            true => Tolerance::Relative(v / 100.0),
+            false => Tolerance::Absolute(v),
+        })
+    }
+}
+
+/// Video ~ A sampled frame's perceptual hash and presentation time.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct FrameHash {
+    pub pts_secs: f64,
+    pub hash: String,
+}
+
+/// Image ~ Crop-resistant: a square tile's position and size (original pixels) and its hash.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct TileHash {
+    pub x: u32,
+    pub y: u32,
+    pub size: u32,
+    pub hash: String,
 }
 
 /// App-wide Config, reducing boiler-plate function arguments
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct AppConfig {
+    #[serde(skip)]
     pub media_paths: Vec<PathBuf>,
-    pub hash_alg: HashAlg,
+    /// One or more algorithms (deduplicated); the first is the primary.
+    pub hash_algs: Vec<HashAlg>,
     pub hash_w: u32,
     pub hash_h: u32,
     pub parallelism: usize,
+    pub autocrop: bool,
+    pub alpha: AlphaPolicy,
+    pub color: bool,
+    pub crop_resistant: bool,
+    pub with_metadata: bool,
     // Video
     pub sample_start: usize,
     pub sample_count: usize,
     pub sample_window: usize,
+    pub sample_interval: f64,
     pub aggregation: Aggregation,
+    pub audio: bool,
+    pub decode_timeout: f64,
+    pub retry_failed: bool,
+}
+
+impl AppConfig {
+    /// Used where only one hash is compared (sequences, clips, frame matching, failures).
+    pub fn primary_alg(&self) -> HashAlg {
+        self.hash_algs[0]
+    }
+}
+
+/// A file that could not be hashed, reported in the JSON output's `errors`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FileError {
+    pub path: PathBuf,
+    pub error: String,
 }
 
 /// Pipeline Result for displaying information to user.
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct PipelineResult {
     pub path: PathBuf,
     pub blake3: String,
-    pub perceptual_hash: String,
+    /// One perceptual hash per `--hash-alg`, in order; `hashes[0]` is the primary.
+    pub hashes: Vec<String>,
+    pub media_type: MediaType,
+    // Video / Animated (primary algorithm)
+    pub frames: Option<Vec<FrameHash>>,
+    pub audio_hash: Option<String>,
+    pub meta: Option<VideoMeta>,
+    // Image
+    pub color: Option<String>,
+    pub tiles: Option<Vec<TileHash>>,
+    /// File metadata, with `--with-metadata`.
+    pub info: Option<FileInfo>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tolerance_parses_percentages_and_numbers() {
+        assert_eq!("5%".parse(), Ok(Tolerance::Relative(0.05)));
+        assert_eq!(" 12.5 % ".parse(), Ok(Tolerance::Relative(0.125)));
+        assert_eq!("2.5".parse(), Ok(Tolerance::Absolute(2.5)));
+        assert_eq!("0".parse(), Ok(Tolerance::Absolute(0.0)));
+    }
+
+    #[test]
+    fn tolerance_rejects_bad_input() {
+        for bad in ["", "%", "abc", "5%%", "5s", "-1", "-5%", "inf", "NaN"] {
+            assert!(bad.parse::<Tolerance>().is_err(), "{bad:?} parsed");
+        }
+    }
+
+    #[test]
+    fn tolerance_allows_within_bounds() {
+        let relative = Tolerance::Relative(0.05);
+        assert!(relative.allows(100.0, 105.0) && relative.allows(105.0, 100.0));
+        assert!(!relative.allows(100.0, 106.0));
+        let absolute = Tolerance::Absolute(2.0);
+        assert!(absolute.allows(10.0, 12.0) && !absolute.allows(10.0, 12.5));
+    }
 }
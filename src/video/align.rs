@@ -0,0 +1,205 @@
+//! Video ~ Sequence alignment: slide one video's frame hashes over another's in time.
+
+use img_hash::ImageHash;
+
+use crate::types::FrameHash;
+
+/// Minimum overlapping frames for an alignment to count (capped by the shorter sequence).
+const MIN_OVERLAP_FRAMES: usize = 3;
+
+/// Number of most-voted offsets that are scored in full.
+const CANDIDATE_OFFSETS: usize = 5;
+
+/// Ordered frame hashes: (presentation time in seconds, hash).
+pub type Sequence = Vec<(f64, ImageHash)>;
+
+#[derive(Debug, Clone, Copy)]
+pub struct Alignment {
+    /// Seconds to add to a timestamp in `a` to reach the matching frame in `b`.
+    pub offset_secs: f64,
+    /// Fraction of overlapping frames whose Hamming distance is <= threshold.
+    pub similarity: f64,
+    /// Number of frames of `a` matched at this offset.
+    pub matched: usize,
+    /// Mean Hamming distance of the matched frames.
+    pub mean_dist_bits: f64,
+}
+
+/// Decode cached base64 frame hashes, ordered by timestamp.
+pub fn decode_sequence(frames: &[FrameHash]) -> Sequence {
+    let mut seq: Sequence = frames
+        .iter()
+        .filter_map(|f| {
+            ImageHash::from_base64(&f.hash)
+                .ok()
+                .map(|h| (f.pts_secs, h))
+        })
+        .collect();
+    seq.sort_by(|x, y| x.0.total_cmp(&y.0));
+    seq
+}
+
+/// Align `b` against `a` by sliding it in time.
+///
+/// - Every frame pair within `threshold` votes for the offset `t_b - t_a`.
+/// - Votes are binned by half the sample spacing; the most-voted bins are scored in full.
+/// - Scoring pairs each frame of `a` with the nearest frame of `b` at `t_a + offset`,
+///   counting only frames that land inside `b`'s span.
+/// - Highest similarity wins (ties: lower mean distance).
+pub fn align(a: &[(f64, ImageHash)], b: &[(f64, ImageHash)], threshold: u32) -> Option<Alignment> {
+    if a.is_empty() || b.is_empty() {
+        return None;
+    }
+
+    // Offset bin width; half the coarser sampling interval.
+    let tol = (median_gap(a).max(median_gap(b)) / 2.0).max(1e-3);
+
+    // 1) Vote
+    let mut votes: Vec<(i64, f64)> = Vec::new();
+    for (ta, ha) in a {
+        for (tb, hb) in b {
+            if ha.dist(hb) <= threshold {
+                let offset = tb - ta;
+                votes.push(((offset / tol).round() as i64, offset));
+            }
+        }
+    }
+    if votes.is_empty() {
+        return None;
+    }
+
+    // 2) Bin votes -> (count, mean offset), most-voted first
+    votes.sort_by_key(|v| v.0);
+    let mut bins: Vec<(usize, f64)> = votes
+        .chunk_by(|x, y| x.0 == y.0)
+        .map(|bin| {
+            (
+                bin.len(),
+                bin.iter().map(|v| v.1).sum::<f64>() / bin.len() as f64,
+            )
+        })
+        .collect();
+    bins.sort_by_key(|bin| std::cmp::Reverse(bin.0));
+
+    // 3) Score candidates
+    let min_overlap = MIN_OVERLAP_FRAMES.min(a.len()).min(b.len());
+    bins.iter()
+        .take(CANDIDATE_OFFSETS)
+        .filter_map(|&(_, offset)| score(a, b, offset, tol, threshold, min_overlap))
+        .max_by(|x, y| {
+            x.similarity
+                .total_cmp(&y.similarity)
+                .then_with(|| y.mean_dist_bits.total_cmp(&x.mean_dist_bits))
+        })
+}
+
+/// Score a single offset; `None` if too few frames overlap.
+fn score(
+    a: &[(f64, ImageHash)],
+    b: &[(f64, ImageHash)],
+    offset: f64,
+    tol: f64,
+    threshold: u32,
+    min_overlap: usize,
+) -> Option<Alignment> {
+    let (b_first, b_last) = (b[0].0 - tol, b[b.len() - 1].0 + tol);
+
+    let mut overlap = 0usize;
+    let mut matched = 0usize;
+    let mut dist_sum = 0u64;
+    for (ta, ha) in a {
+        let t = ta + offset;
+        if t < b_first || t > b_last {
+            continue;
+        }
+        overlap += 1;
+        let dist = ha.dist(&b[nearest(b, t)].1);
+        if dist <= threshold {
+            matched += 1;
+            dist_sum += dist as u64;
+        }
+    }
+
+    if overlap < min_overlap || matched == 0 {
+        return None;
+    }
+    Some(Alignment {
+        offset_secs: offset,
+        similarity: matched as f64 / overlap as f64,
+        matched,
+        mean_dist_bits: dist_sum as f64 / matched as f64,
+    })
+}
+
+/// Index of the frame in `seq` closest to `t` (seq sorted by time).
+fn nearest(seq: &[(f64, ImageHash)], t: f64) -> usize {
+    let i = seq.partition_point(|(ts, _)| *ts < t);
+    match i {
+        0 => 0,
+        i if i >= seq.len() => seq.len() - 1,
+        i if (seq[i].0 - t) < (t - seq[i - 1].0) => i,
+        i => i - 1,
+    }
+}
+
+/// Median interval between consecutive samples.
+fn median_gap(seq: &[(f64, ImageHash)]) -> f64 {
+    let mut gaps: Vec<f64> = seq.windows(2).map(|w| w[1].0 - w[0].0).collect();
+    if gaps.is_empty() {
+        return 0.0;
+    }
+    gaps.sort_by(f64::total_cmp);
+    gaps[gaps.len() / 2]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::video::test_util::hashes;
+
+    /// One frame per second from `start`.
+    fn sequence(start: f64, hashes: &[ImageHash]) -> Sequence {
+        hashes
+            .iter()
+            .enumerate()
+            .map(|(i, h)| (start + i as f64, h.clone()))
+            .collect()
+    }
+
+    #[test]
+    fn known_offset_is_recovered() {
+        let hs = hashes(1, 20);
+        let a = sequence(0.0, &hs);
+        // `b` is `a` with its first 5 seconds cut
+        let b = sequence(0.0, &hs[5..]);
+        let al = align(&a, &b, 10).expect("alignment");
+        assert!((al.offset_secs + 5.0).abs() < 1e-9, "{al:?}");
+        assert_eq!(al.similarity, 1.0);
+        assert_eq!(al.matched, 15);
+        assert_eq!(al.mean_dist_bits, 0.0);
+
+        let al = align(&b, &a, 10).expect("alignment");
+        assert!((al.offset_secs - 5.0).abs() < 1e-9, "{al:?}");
+    }
+
+    #[test]
+    fn unrelated_sequences_do_not_align() {
+        let a = sequence(0.0, &hashes(2, 20));
+        let b = sequence(0.0, &hashes(3, 20));
+        assert!(align(&a, &b, 10).is_none());
+        assert!(align(&a, &[], 10).is_none());
+    }
+
+    #[test]
+    fn single_frame_sequence_aligns() {
+        let hs = hashes(4, 20);
+        let a = sequence(7.0, &hs[7..8]);
+        let b = sequence(0.0, &hs);
+        let al = align(&a, &b, 10).expect("alignment");
+        assert!(al.offset_secs.abs() < 1e-9, "{al:?}");
+        assert_eq!((al.similarity, al.matched), (1.0, 1));
+
+        let al = align(&a, &a, 10).expect("alignment");
+        assert_eq!((al.offset_secs, al.matched), (0.0, 1));
+    }
+}
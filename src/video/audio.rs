@@ -0,0 +1,289 @@
+//! Video ~ Audio fingerprint: chromagram of the audio stream folded into fixed-duration segments
+//! of 12 bits, compared by sliding one fingerprint over the other.
+
+use std::sync::Arc;
+
+use ffmpeg::util::format::sample::{Sample, Type as SampleType};
+use ffmpeg::{ChannelLayout, Packet, codec, format, frame, media, software::resampling};
+use ffmpeg_next as ffmpeg;
+use img_hash::ImageHash;
+use rustfft::{Fft, FftPlanner, num_complex::Complex};
+
+use crate::errors::VideoError;
+
+/// Mono analysis rate; chroma only needs up to ~4 kHz.
+const AUDIO_RATE: u32 = 11025;
+
+/// FFT window (~370 ms) and hop (50% overlap).
+const FRAME_SIZE: usize = 4096;
+const HOP_SIZE: usize = 2048;
+
+/// Frequency range folded into the 12 pitch classes.
+const MIN_FREQ: f32 = 55.0;
+const MAX_FREQ: f32 = 4000.0;
+
+/// FFT windows per fingerprint segment (~0.74 s); 12 bits each (one per pitch class).
+const SEGMENT_HOPS: usize = 4;
+
+/// Segments that must overlap for a distance (~24 s, or all of a shorter fingerprint);
+/// distances are scaled to this many segments (384 bits).
+const WINDOW_SEGMENTS: usize = 32;
+
+/// Decodes the best audio stream packet by packet and accumulates a chromagram.
+pub struct AudioFingerprinter {
+    stream_index: usize,
+    decoder: codec::decoder::Audio,
+    resampler: resampling::Context,
+    decoded: frame::Audio,
+    /// Mono samples waiting for a full FFT window.
+    pending: Vec<f32>,
+    fft: Arc<dyn Fft<f32>>,
+    window: Vec<f32>,
+    /// Pitch class (0-11) of each FFT bin within range.
+    bin_pitch: Vec<Option<usize>>,
+    chroma: Vec<[f32; 12]>,
+}
+
+impl AudioFingerprinter {
+    /// Open a decoder + resampler (→ mono f32 @ 11025 Hz); `None` if there is no audio stream.
+    pub fn open(ictx: &format::context::Input) -> Result<Option<Self>, VideoError> {
+        let Some(stream) = ictx.streams().best(media::Type::Audio) else {
+            return Ok(None);
+        };
+        let stream_index = stream.index();
+
+        let ctx = codec::Context::from_parameters(stream.parameters())
+            .map_err(|e| VideoError::Decode(format!("audio decoder ctx: {e}")))?;
+        let decoder = ctx
+            .decoder()
+            .audio()
+            .map_err(|e| VideoError::Decode(format!("open audio decoder: {e}")))?;
+
+        // Some containers leave the layout unset; derive it from the channel count.
+        let layout = match decoder.channel_layout() {
+            l if l.is_empty() => ChannelLayout::default(decoder.channels() as i32),
+            l => l,
+        };
+        let resampler = resampling::Context::get(
+            decoder.format(),
+            layout,
+            decoder.rate(),
+            Sample::F32(SampleType::Packed),
+            ChannelLayout::MONO,
+            AUDIO_RATE,
+        )
+        .map_err(|e| VideoError::Decode(format!("swr ctx: {e}")))?;
+
+        let mut planner = FftPlanner::new();
+        let window = (0..FRAME_SIZE)
+            .map(|i| 0.5 - 0.5 * (2.0 * std::f32::consts::PI * i as f32 / FRAME_SIZE as f32).cos())
+            .collect();
+        let bin_pitch = (0..FRAME_SIZE / 2)
+            .map(|k| {
+                let freq = k as f32 * AUDIO_RATE as f32 / FRAME_SIZE as f32;
+                (MIN_FREQ..=MAX_FREQ).contains(&freq).then(|| {
+                    // MIDI note number, folded to pitch class
+                    let midi = (12.0 * (freq / 440.0).log2() + 69.0).round() as i64;
+                    midi.rem_euclid(12) as usize
+                })
+            })
+            .collect();
+
+        Ok(Some(AudioFingerprinter {
+            stream_index,
+            decoder,
+            resampler,
+            decoded: frame::Audio::empty(),
+            pending: Vec::with_capacity(FRAME_SIZE * 2),
+            fft: planner.plan_fft_forward(FRAME_SIZE),
+            window,
+            bin_pitch,
+            chroma: Vec::new(),
+        }))
+    }
+
+    pub fn stream_index(&self) -> usize {
+        self.stream_index
+    }
+
+    /// Decode one audio packet; undecodable packets are skipped like video ones.
+    pub fn send_packet(&mut self, pkt: &Packet) -> Result<(), VideoError> {
+        if self.decoder.send_packet(pkt).is_err() {
+            return Ok(());
+        }
+        self.drain()
+    }
+
+    /// Flush the decoder and resampler, then fold the chromagram into a hash.
+    /// `None` if no audio was decoded.
+    pub fn finish(mut self) -> Result<Option<ImageHash>, VideoError> {
+        if self.decoder.send_eof().is_ok() {
+            self.drain()?;
+        }
+        let mut resampled = frame::Audio::empty();
+        if self.resampler.flush(&mut resampled).is_ok() && resampled.samples() > 0 {
+            self.push_samples(&resampled);
+        }
+        Ok(fingerprint(&self.chroma))
+    }
+
+    fn drain(&mut self) -> Result<(), VideoError> {
+        while self.decoder.receive_frame(&mut self.decoded).is_ok() {
+            let mut resampled = frame::Audio::empty();
+            self.resampler
+                .run(&self.decoded, &mut resampled)
+                .map_err(|e| VideoError::Decode(format!("swr run: {e}")))?;
+            if resampled.samples() > 0 {
+                self.push_samples(&resampled);
+            }
+        }
+        Ok(())
+    }
+
+    fn push_samples(&mut self, resampled: &frame::Audio) {
+        self.pending.extend_from_slice(resampled.plane::<f32>(0));
+        while self.pending.len() >= FRAME_SIZE {
+            self.analyze_window();
+            self.pending.drain(..HOP_SIZE);
+        }
+    }
+
+    /// Hann-windowed FFT of the first `FRAME_SIZE` pending samples → 12-bin chroma.
+    fn analyze_window(&mut self) {
+        let mut buf: Vec<Complex<f32>> = self.pending[..FRAME_SIZE]
+            .iter()
+            .zip(&self.window)
+            .map(|(&s, &w)| Complex::new(s * w, 0.0))
+            .collect();
+        self.fft.process(&mut buf);
+
+        let mut chroma = [0f32; 12];
+        for (bin, pitch) in self.bin_pitch.iter().enumerate() {
+            if let Some(pc) = pitch {
+                chroma[*pc] += buf[bin].norm();
+            }
+        }
+        self.chroma.push(chroma);
+    }
+}
+
+/// Fold chroma frames into segments of `SEGMENT_HOPS` frames, so the fingerprint of a trimmed
+/// or extended copy is the same sequence with segments added or dropped at the ends.
+/// Bit (segment, pitch class) = that pitch class is above the segment's mean energy,
+/// so the hash is independent of volume.
+fn fingerprint(chroma: &[[f32; 12]]) -> Option<ImageHash> {
+    if chroma.is_empty() {
+        return None;
+    }
+
+    let segments = chroma.chunks(SEGMENT_HOPS);
+    let mut bytes = vec![0u8; (segments.len() * 12).div_ceil(8)];
+    for (seg, frames) in segments.enumerate() {
+        let mut energies = [0f32; 12];
+        for frame in frames {
+            for (acc, v) in energies.iter_mut().zip(frame) {
+                *acc += v;
+            }
+        }
+        let mean = energies.iter().sum::<f32>() / 12.0;
+        for (pc, &e) in energies.iter().enumerate() {
+            if e > mean {
+                let bit = seg * 12 + pc;
+                bytes[bit / 8] |= 1 << (bit % 8);
+            }
+        }
+    }
+    ImageHash::from_bytes(&bytes).ok()
+}
+
+/// Audio distance in bits per `WINDOW_SEGMENTS` segments, at the segment offset where the
+/// fingerprints agree best (one may start later, or be longer, than the other).
+pub fn distance(a: &ImageHash, b: &ImageHash) -> u32 {
+    let (a, b) = (segments(a), segments(b));
+    if a.is_empty() || b.is_empty() {
+        return u32::MAX;
+    }
+    let min_overlap = WINDOW_SEGMENTS.min(a.len()).min(b.len());
+
+    // Slide b along a: b[k] lines up with a[k + shift]
+    let shifts = (min_overlap as isize - b.len() as isize)..=(a.len() - min_overlap) as isize;
+    shifts
+        .map(|shift| {
+            let (a, b) = match shift >= 0 {
+                true => (&a[shift as usize..], &b[..]),
+                false => (&a[..], &b[(-shift) as usize..]),
+            };
+            let overlap = a.len().min(b.len());
+            let bits: u32 = a.iter().zip(b).map(|(x, y)| (x ^ y).count_ones()).sum();
+            bits as f64 * WINDOW_SEGMENTS as f64 / overlap as f64
+        })
+        .min_by(f64::total_cmp)
+        .map_or(u32::MAX, |d| d.round() as u32)
+}
+
+/// The 12-bit pitch-class codes of a fingerprint's segments.
+fn segments(hash: &ImageHash) -> Vec<u16> {
+    let bytes = hash.as_bytes();
+    (0..bytes.len() * 8 / 12)
+        .map(|seg| {
+            (0..12).fold(0u16, |code, pc| {
+                let bit = seg * 12 + pc;
+                code | (((bytes[bit / 8] >> (bit % 8)) & 1) as u16) << pc
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::video::test_util::Lcg;
+
+    /// `n` chroma frames of held notes (8 frames each) with pseudo-random energies.
+    fn song(seed: u64, n: usize) -> Vec<[f32; 12]> {
+        let mut rng = Lcg::new(seed);
+        let mut frames = Vec::with_capacity(n);
+        while frames.len() < n {
+            let note: [f32; 12] = std::array::from_fn(|_| rng.next_f32());
+            frames.extend(std::iter::repeat_n(note, 8));
+        }
+        frames.truncate(n);
+        frames
+    }
+
+    fn fp(chroma: &[[f32; 12]]) -> ImageHash {
+        fingerprint(chroma).expect("chroma frames")
+    }
+
+    #[test]
+    fn segments_have_a_fixed_duration() {
+        let chroma = song(1, 401);
+        let hash = fp(&chroma);
+        assert_eq!(segments(&hash).len(), 401usize.div_ceil(SEGMENT_HOPS));
+        assert!(fingerprint(&[]).is_none());
+    }
+
+    #[test]
+    fn trimmed_copy_matches_at_its_offset() {
+        let full = song(2, 800);
+        // Cut 40 segments from the start and some from the end
+        let trimmed = &full[SEGMENT_HOPS * 40..700];
+        assert_eq!(distance(&fp(&full), &fp(trimmed)), 0);
+        assert_eq!(distance(&fp(trimmed), &fp(&full)), 0);
+    }
+
+    #[test]
+    fn changed_intro_adds_little_distance() {
+        let full = song(3, 800);
+        let mut reupload = song(4, SEGMENT_HOPS * 6);
+        reupload.extend_from_slice(&full[SEGMENT_HOPS * 10..]);
+        let d = distance(&fp(&full), &fp(&reupload));
+        assert!(d <= 10, "distance {d}");
+    }
+
+    #[test]
+    fn different_audio_is_far_apart() {
+        let d = distance(&fp(&song(5, 800)), &fp(&song(6, 800)));
+        assert!(d > 100, "distance {d}");
+    }
+}
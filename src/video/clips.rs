@@ -0,0 +1,149 @@
+//! Video ~ Clip containment: find short videos that were cut from longer ones.
+
+use indicatif::ParallelProgressIterator;
+use rayon::prelude::*;
+
+use super::align::{self, Sequence};
+use crate::{progress, types::PipelineResult};
+
+/// A short video located inside a longer one.
+#[derive(Debug, Clone)]
+pub struct ClipMatch {
+    pub clip: usize,
+    pub source: usize,
+    /// Time range in the source the clip was taken from (seconds).
+    pub start_secs: f64,
+    pub end_secs: f64,
+    /// Fraction of the clip's sampled frames matched inside the source.
+    pub confidence: f64,
+    /// Mean Hamming distance of the matched frames.
+    pub dist_bits: u32,
+}
+
+/// For each video in `items[videos]`, align it against every longer video and keep the best source.
+///
+/// - Requires dense per-frame hashes (`--sample-interval`); aggregate hashes can't locate a clip.
+/// - Confidence is `matched clip frames / clip frames`, so partially overlapping videos score low.
+/// - Matches below `min_confidence` are dropped; results are sorted by confidence desc.
+pub fn find_clips(
+    items: &[PipelineResult],
+    videos: &[usize],
+    threshold: u32,
+    min_confidence: f64,
+) -> Vec<ClipMatch> {
+    let sequences: Vec<Sequence> = videos
+        .iter()
+        .map(|&v| align::decode_sequence(items[v].frames.as_deref().unwrap_or_default()))
+        .collect();
+    let durations: Vec<f64> = sequences.iter().map(span_secs).collect();
+
+    let matching_pb = progress::bar(videos.len() as u64, "Matching");
+    let mut matches: Vec<ClipMatch> = (0..videos.len())
+        .into_par_iter()
+        .progress_with(matching_pb.clone())
+        .filter_map(|clip| {
+            let seq = &sequences[clip];
+            let (first, last) = (seq.first()?.0, seq.last()?.0);
+
+            (0..videos.len())
+                .filter(|&source| durations[source] > durations[clip])
+                .filter_map(|source| {
+                    let a = align::align(seq, &sequences[source], threshold)?;
+                    Some(ClipMatch {
+                        clip: videos[clip],
+                        source: videos[source],
+                        start_secs: first + a.offset_secs,
+                        end_secs: last + a.offset_secs,
+                        confidence: a.matched as f64 / seq.len() as f64,
+                        dist_bits: a.mean_dist_bits.round() as u32,
+                    })
+                })
+                .filter(|m| m.confidence >= min_confidence)
+                .max_by(|x, y| {
+                    x.confidence
+                        .total_cmp(&y.confidence)
+                        .then_with(|| y.dist_bits.cmp(&x.dist_bits))
+                })
+        })
+        .collect();
+    matching_pb.finish_and_clear();
+
+    matches.sort_by(|x, y| y.confidence.total_cmp(&x.confidence));
+    matches
+}
+
+/// Time spanned by the sampled frames.
+fn span_secs(seq: &Sequence) -> f64 {
+    match (seq.first(), seq.last()) {
+        (Some(first), Some(last)) => last.0 - first.0,
+        _ => 0.0,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use img_hash::ImageHash;
+
+    use super::*;
+    use crate::{
+        types::{FrameHash, MediaType},
+        video::test_util::hashes,
+    };
+
+    /// A video sampled once per second from 0s.
+    fn video(name: &str, frames: &[ImageHash]) -> PipelineResult {
+        PipelineResult {
+            path: name.into(),
+            blake3: name.into(),
+            hashes: vec![frames[0].to_base64()],
+            media_type: MediaType::Video,
+            frames: Some(
+                frames
+                    .iter()
+                    .enumerate()
+                    .map(|(i, h)| FrameHash {
+                        pts_secs: i as f64,
+                        hash: h.to_base64(),
+                    })
+                    .collect(),
+            ),
+            audio_hash: None,
+            meta: None,
+            color: None,
+            tiles: None,
+            info: None,
+        }
+    }
+
+    #[test]
+    fn clip_is_located_in_its_source() {
+        let source = hashes(1, 30);
+        let items = [video("source", &source), video("clip", &source[10..20])];
+        let matches = find_clips(&items, &[0, 1], 10, 0.8);
+        assert_eq!(matches.len(), 1);
+        let m = &matches[0];
+        assert_eq!((m.clip, m.source), (1, 0));
+        assert!((m.start_secs - 10.0).abs() < 1e-9 && (m.end_secs - 19.0).abs() < 1e-9);
+        assert_eq!((m.confidence, m.dist_bits), (1.0, 0));
+    }
+
+    #[test]
+    fn confidence_counts_unmatched_clip_frames() {
+        // The clip's second half runs past the end of the source
+        let source = hashes(2, 30);
+        let mut clip = source[25..].to_vec();
+        clip.extend(hashes(3, 5));
+        let items = [video("source", &source), video("clip", &clip)];
+
+        let matches = find_clips(&items, &[0, 1], 10, 0.4);
+        assert_eq!(matches.len(), 1);
+        assert_eq!(matches[0].confidence, 0.5);
+        assert!(find_clips(&items, &[0, 1], 10, 0.8).is_empty());
+    }
+
+    #[test]
+    fn unrelated_videos_are_not_clips() {
+        let items = [video("a", &hashes(4, 30)), video("b", &hashes(5, 10))];
+        assert!(find_clips(&items, &[0, 1], 10, 0.0).is_empty());
+    }
+}
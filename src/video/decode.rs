@@ -1,13 +1,18 @@
 use std::path::Path;
+use std::time::{Duration, Instant};
 
-use ffmpeg::{codec, format, frame, media, software::scaling, util::format::pixel};
+use ffmpeg::{Packet, codec, format, frame, media, software::scaling};
 use ffmpeg_next as ffmpeg;
 
-use img_hash::image::{DynamicImage, ImageBuffer, Rgb};
+use img_hash::image::DynamicImage;
 use img_hash::{Hasher, ImageHash};
 
 use crate::errors::{AppError, VideoError};
-use crate::video::sample;
+use crate::preprocess::Preprocess;
+use crate::types::{AppConfig, MediaInfo, VideoMeta};
+use crate::video::audio::AudioFingerprinter;
+use crate::video::orientation::Orientation;
+use crate::video::{sample, scale};
 
 #[inline]
 pub fn init_ffmpeg() -> Result<(), AppError> {
@@ -17,11 +22,39 @@ pub fn init_ffmpeg() -> Result<(), AppError> {
     Ok(())
 }
 
+/// Point after which decoding a file is abandoned (`--decode-timeout`).
+#[derive(Debug, Clone, Copy)]
+struct Deadline {
+    at: Instant,
+    secs: f64,
+}
+
+impl Deadline {
+    fn from_cfg(cfg: &AppConfig) -> Option<Self> {
+        (cfg.decode_timeout > 0.0).then(|| Deadline {
+            at: Instant::now() + Duration::from_secs_f64(cfg.decode_timeout),
+            secs: cfg.decode_timeout,
+        })
+    }
+
+    fn check(deadline: Option<Deadline>) -> Result<(), VideoError> {
+        match deadline {
+            Some(d) if Instant::now() >= d.at => Err(VideoError::Timeout(d.secs)),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Open with an interrupt callback so blocking demuxer I/O also stops at the deadline.
 fn open_input_and_decoder(
     path: &Path,
+    deadline: Option<Deadline>,
 ) -> Result<(format::context::Input, usize, codec::decoder::Video), VideoError> {
-    let ictx = format::input(path)
-        .map_err(|e| VideoError::Decode(format!("open {}: {e}", path.display())))?;
+    let ictx = format::input_with_interrupt(path, move || Deadline::check(deadline).is_err())
+        .map_err(|e| match e {
+            ffmpeg::Error::Exit => VideoError::Timeout(deadline.map_or(0.0, |d| d.secs)),
+            e => VideoError::Decode(format!("open {}: {e}", path.display())),
+        })?;
     let stream = ictx
         .streams()
         .best(media::Type::Video)
@@ -38,79 +71,203 @@ fn open_input_and_decoder(
     Ok((ictx, stream_index, decoder))
 }
 
-/// TODO: Downscale to increase performance + FAST_BILINEAR.
-fn build_rgb_scaler(dec: &codec::decoder::Video) -> Result<scaling::Context, VideoError> {
-    let (w, h) = (dec.width(), dec.height());
-    let src_fmt = dec.format();
-    scaling::Context::get(
-        src_fmt,
-        w,
-        h,
-        pixel::Pixel::RGB24,
-        w,
-        h,
-        scaling::Flags::BILINEAR,
-    )
-    .map_err(|e| VideoError::Decode(format!("sws ctx: {e}")))
+/// Consecutive packet read errors after which a file is given up on (a read error that
+/// never clears, e.g. a truncated or unreadable file, would otherwise loop forever).
+const MAX_READ_ERRORS: u32 = 64;
+
+/// `scale::gray_scaler` for this decoder's frames.
+fn build_gray_scaler(
+    dec: &codec::decoder::Video,
+    dst_w: u32,
+    dst_h: u32,
+) -> Result<scaling::Context, VideoError> {
+    scale::gray_scaler(dec.format(), (dec.width(), dec.height()), (dst_w, dst_h))
+        .map_err(|e| VideoError::Decode(format!("sws ctx: {e}")))
 }
 
-/// Copy a packed RGB24 frame (with stride) into an owned `DynamicImage`
-fn copy_rgb_to_image(rgb: &frame::Video) -> Result<DynamicImage, AppError> {
-    let (w, h) = (rgb.width(), rgb.height());
-    let data = rgb.data(0);
-    let stride = rgb.stride(0) as usize;
-    let mut owned = vec![0u8; (w as usize) * (h as usize) * 3];
-    for y in 0..(h as usize) {
-        let src = &data[y * stride..y * stride + (w as usize) * 3];
-        let dst = &mut owned[y * (w as usize) * 3..(y + 1) * (w as usize) * 3];
-        dst.copy_from_slice(src);
-    }
-    let buf: ImageBuffer<Rgb<u8>, _> = ImageBuffer::from_raw(w, h, owned)
-        .ok_or_else(|| AppError::Video(VideoError::Decode("image buffer alloc failed".into())))?;
-    Ok(DynamicImage::ImageRgb8(buf))
+fn copy_gray_to_image(gray: &frame::Video) -> Result<DynamicImage, AppError> {
+    scale::gray_to_image(gray)
+        .ok_or_else(|| AppError::Video(VideoError::Decode("image buffer alloc failed".into())))
 }
 
-/// Decode, Sample `sample_count` frames, evenly spaced over a window up to `sample_window`
-pub fn decode_sample_even_window_hash(
+/// A sampled frame's hash and its presentation time.
+pub struct SampledHash {
+    pub pts_secs: f64,
+    pub hash: ImageHash,
+}
+
+/// A sampled, downscaled GRAY8 frame and its presentation time.
+pub struct SampledFrame {
+    pub pts_secs: f64,
+    pub image: DynamicImage,
+}
+
+/// Frame hashes (one list per hasher) plus the audio fingerprint (if requested and the file has audio).
+pub struct DecodedHashes {
+    pub frames: Vec<Vec<SampledHash>>,
+    pub audio_hash: Option<ImageHash>,
+    pub meta: VideoMeta,
+    pub media: MediaInfo,
+}
+
+/// Sampled frames, audio fingerprint and stream metadata from one demux pass.
+struct Decoded {
+    frames: Vec<SampledFrame>,
+    audio_hash: Option<ImageHash>,
+    meta: VideoMeta,
+    media: MediaInfo,
+}
+
+/// Decode + Sample, then pre-process (shared with images) and hash each frame with each hasher.
+/// Auto-crop is detected across all sampled frames, so a dark scene can't over-crop.
+pub fn decode_sample_hash(
     path: &Path,
-    sample_start: usize,
-    sample_count: usize,
-    sample_window: usize,
-    hasher: &Hasher,
-) -> Result<Vec<ImageHash>, AppError> {
-    if sample_count == 0 {
-        return Ok(Vec::new());
-    }
+    cfg: &AppConfig,
+    hashers: &[Hasher],
+) -> Result<DecodedHashes, AppError> {
+    let Decoded {
+        frames,
+        audio_hash,
+        meta,
+        media,
+    } = decode_sample(path, cfg, cfg.audio)?;
+    let (times, images): (Vec<f64>, Vec<DynamicImage>) =
+        frames.into_iter().map(|f| (f.pts_secs, f.image)).unzip();
+    let images = Preprocess::from_cfg(cfg).apply_all(images);
 
-    let (mut ictx, sidx, mut dec) = open_input_and_decoder(path)?;
+    let frames = hashers
+        .iter()
+        .map(|hasher| {
+            times
+                .iter()
+                .zip(&images)
+                .map(|(&pts_secs, img)| SampledHash {
+                    pts_secs,
+                    hash: hasher.hash_image(img),
+                })
+                .collect()
+        })
+        .collect();
+    Ok(DecodedHashes {
+        frames,
+        audio_hash,
+        meta,
+        media,
+    })
+}
+
+/// Decode + Sample only (upright, downscaled GRAY8), e.g. for report thumbnails: at most `max`
+/// frames, evenly spaced over the same window as the hashed samples, so a dense
+/// `sample_interval` isn't decoded in full for a strip of a few frames.
+pub fn decode_sample_frames(
+    path: &Path,
+    cfg: &AppConfig,
+    max: usize,
+) -> Result<Vec<SampledFrame>, AppError> {
+    let cfg = AppConfig {
+        sample_interval: 0.0,
+        sample_count: cfg.sample_count.min(max),
+        ..cfg.clone()
+    };
+    Ok(decode_sample(path, &cfg, false)?.frames)
+}
+
+/// Decode, Sample frames within a window up to `sample_window`:
+/// - `sample_interval` = 0: `sample_count` frames, evenly spaced.
+/// - `sample_interval` > 0: one frame every `sample_interval` seconds (dense fingerprint).
+///
+/// With `with_audio`, audio packets from the same demux pass feed the audio fingerprint,
+/// and reading continues past the last video sample until the audio stream ends.
+fn decode_sample(path: &Path, cfg: &AppConfig, with_audio: bool) -> Result<Decoded, AppError> {
+    let interval = cfg.sample_interval;
+
+    let deadline = Deadline::from_cfg(cfg);
+    let (mut ictx, sidx, mut dec) = open_input_and_decoder(path, deadline)?;
     let stream = ictx
         .stream(sidx)
         .ok_or_else(|| VideoError::Decode("stream index out of range".into()))?;
 
     let total = sample::get_total_frames(&stream).unwrap_or(0);
-    let sample_plan = sample::plan_even_sampling(total, sample_count, sample_start, sample_window);
+    let timeline = sample::Timeline::from_stream(&stream);
+    let sample_plan = match interval > 0.0 {
+        true => sample::plan_interval_sampling(total, cfg.sample_start, cfg.sample_window),
+        false => {
+            sample::plan_even_sampling(total, cfg.sample_count, cfg.sample_start, cfg.sample_window)
+        }
+    };
     // Debug
     // eprintln!(
     //     "Start({:?}) Step({:?}) Take({:?}) Total({:?}) File({:?})",
     //     sample_plan.start, sample_plan.step, sample_plan.take, total, path
     // );
 
-    let mut scaler = build_rgb_scaler(&dec)?;
+    // Scale to square pixels here; rotation is applied to the small GRAY8 frame
+    let orientation = Orientation::from_stream(&stream, &dec);
+    let (disp_w, disp_h) = orientation.display_dims(dec.width(), dec.height());
+    let (dst_w, dst_h) = scale::scaled_dims(disp_w, disp_h, cfg.hash_w, cfg.hash_h);
+    let (width, height) = orientation.upright_dims(dec.width(), dec.height());
+    let meta = VideoMeta {
+        duration_secs: sample::get_duration_secs(&ictx, &stream),
+        width,
+        height,
+        fps: timeline.fps(),
+    };
+    let media = MediaInfo {
+        width,
+        height,
+        format: ictx.format().name().to_string(),
+        codec: Some(dec.id().name().to_string()),
+    };
+    let mut scaler = build_gray_scaler(&dec, dst_w, dst_h)?;
     let mut decoded = frame::Video::empty();
-    let mut rgb = frame::Video::empty();
+    let mut gray = frame::Video::empty();
+
+    let mut audio = match with_audio {
+        true => AudioFingerprinter::open(&ictx)?,
+        false => None,
+    };
+    let mut video_done = interval <= 0.0 && sample_plan.take == 0;
 
-    let mut out: Vec<ImageHash> = Vec::with_capacity(sample_count);
+    let mut out: Vec<SampledFrame> = Vec::with_capacity(cfg.sample_count);
     let mut idx = 0usize;
     let mut remaining = sample_plan.take;
-
-    #[inline]
-    fn should_take(idx: usize, start: usize, step: usize, remaining: usize) -> bool {
-        remaining > 0 && idx >= start && ((idx - start) % step == 0)
-    }
+    let mut next_secs = 0.0f64;
 
     // Read packets & decode frames.
-    'packets: for (st, pkt) in ictx.packets() {
-        if st.index() != sidx {
+    // Packets are read directly (not `ictx.packets()`, which retries read errors forever)
+    // so the deadline is checked between every read.
+    let mut read_errors = 0u32;
+    loop {
+        Deadline::check(deadline)?;
+        let mut pkt = Packet::empty();
+        match pkt.read(&mut ictx) {
+            Ok(()) => read_errors = 0,
+            Err(ffmpeg::Error::Eof) => break,
+            // Includes interrupts (AVERROR_EXIT); the deadline check above reports them
+            Err(e) => {
+                read_errors += 1;
+                if read_errors >= MAX_READ_ERRORS {
+                    return Err(VideoError::Decode(format!("read packet: {e}")).into());
+                }
+                continue;
+            }
+        }
+        let st_index = pkt.stream();
+
+        if let Some(audio) = audio.as_mut()
+            && st_index == audio.stream_index()
+        {
+            audio.send_packet(&pkt)?;
+            continue;
+        }
+        if video_done {
+            // Only still reading for audio
+            if audio.is_none() {
+                break;
+            }
+            continue;
+        }
+        if st_index != sidx {
             continue;
         }
         if dec.send_packet(&pkt).is_err() {
@@ -118,15 +275,28 @@ pub fn decode_sample_even_window_hash(
         }
 
         while dec.receive_frame(&mut decoded).is_ok() {
-            if should_take(idx, sample_plan.start, sample_plan.step, remaining) {
+            if !sample_plan.in_window(idx) {
+                video_done = true;
+                break;
+            }
+            let secs = timeline.secs(decoded.timestamp(), idx);
+            let take = match interval > 0.0 {
+                true => idx >= sample_plan.start && secs >= next_secs,
+                false => sample_plan.takes(idx, remaining),
+            };
+            if take {
                 scaler
-                    .run(&decoded, &mut rgb)
+                    .run(&decoded, &mut gray)
                     .map_err(|e| VideoError::Decode(format!("sws run: {e}")))?;
-                let img = copy_rgb_to_image(&rgb)?;
-                out.push(hasher.hash_image(&img));
+                out.push(SampledFrame {
+                    pts_secs: secs,
+                    image: orientation.apply(copy_gray_to_image(&gray)?),
+                });
+                next_secs = secs + interval;
                 remaining -= 1;
-                if remaining <= 0 {
-                    break 'packets;
+                if remaining == 0 {
+                    video_done = true;
+                    break;
                 }
             }
             idx += 1;
@@ -137,15 +307,24 @@ pub fn decode_sample_even_window_hash(
         return Err(AppError::Video(VideoError::NoSamples));
     }
 
-    if out.len() < sample_count {
+    if interval <= 0.0 && out.len() < cfg.sample_count {
         eprintln!(
             "note: sampled {} of requested {} ({} frames available in window) for {}",
             out.len(),
-            sample_count,
+            cfg.sample_count,
             sample_plan.take,
             path.display()
         );
     }
 
-    Ok(out)
+    let audio_hash = match audio {
+        Some(audio) => audio.finish()?,
+        None => None,
+    };
+    Ok(Decoded {
+        frames: out,
+        audio_hash,
+        meta,
+        media,
+    })
 }
@@ -0,0 +1,63 @@
+//! Video ~ Frame matching: link images (screenshots, poster frames) back to their source video.
+
+use img_hash::ImageHash;
+use indicatif::ParallelProgressIterator;
+use rayon::prelude::*;
+
+use super::align::{self, Sequence};
+use crate::{progress, types::PipelineResult};
+
+/// An image matching a sampled frame of a video.
+#[derive(Debug, Clone)]
+pub struct FrameMatch {
+    pub image: usize,
+    pub video: usize,
+    /// Timestamp of the closest sampled frame (seconds).
+    pub pts_secs: f64,
+    pub dist_bits: u32,
+}
+
+/// Compare every image against every sampled frame of every video.
+///
+/// - `images` / `videos` are indices into `items`; videos must carry per-frame hashes.
+/// - Each (image, video) pair reports its closest frame if within `threshold` bits.
+/// - Matches are sorted by image, then ascending distance.
+pub fn match_images_to_frames(
+    items: &[PipelineResult],
+    images: &[usize],
+    videos: &[usize],
+    threshold: u32,
+) -> Vec<FrameMatch> {
+    let sequences: Vec<(usize, Sequence)> = videos
+        .iter()
+        .map(|&v| {
+            let frames = items[v].frames.as_deref().unwrap_or_default();
+            (v, align::decode_sequence(frames))
+        })
+        .collect();
+
+    let matching_pb = progress::bar(images.len() as u64, "Matching");
+    let mut matches: Vec<FrameMatch> = images
+        .par_iter()
+        .progress_with(matching_pb.clone())
+        .flat_map_iter(|&image| {
+            let hash = ImageHash::from_base64(&items[image].hashes[0]).expect("valid base64 pHash");
+            sequences.iter().filter_map(move |(video, seq)| {
+                let (pts_secs, dist) = seq
+                    .iter()
+                    .map(|(t, h)| (*t, hash.dist(h)))
+                    .min_by_key(|(_, d)| *d)?;
+                (dist <= threshold).then_some(FrameMatch {
+                    image,
+                    video: *video,
+                    pts_secs,
+                    dist_bits: dist,
+                })
+            })
+        })
+        .collect();
+    matching_pb.finish_and_clear();
+
+    matches.sort_by_key(|m| (m.image, m.dist_bits));
+    matches
+}
@@ -1,4 +1,12 @@
-mod aggregate;
-mod decode;
+pub mod aggregate;
+pub mod align;
+pub mod audio;
+pub mod clips;
+pub mod decode;
+pub mod frame_match;
+mod orientation;
 pub mod pipeline;
 mod sample;
+mod scale;
+#[cfg(test)]
+mod test_util;
@@ -0,0 +1,127 @@
+//! Video ~ Display transform: rotation/mirroring from the display matrix and sample aspect ratio.
+
+use ffmpeg::codec::packet::side_data::Type as SideDataType;
+use ffmpeg_next as ffmpeg;
+use img_hash::image::DynamicImage;
+
+/// How decoded frames must be transformed to look the way players show them.
+/// Applied as: rotate clockwise, then flip horizontally, then vertically.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Orientation {
+    /// Clockwise rotation in degrees: 0, 90, 180 or 270.
+    pub rotate_cw: u32,
+    pub flip_h: bool,
+    pub flip_v: bool,
+    /// Sample (pixel) aspect ratio; 1.0 for square pixels.
+    pub sar: f64,
+}
+
+impl Orientation {
+    /// Read the display matrix side data (or the legacy `rotate` tag) and the decoder's SAR.
+    pub fn from_stream(
+        stream: &ffmpeg::format::stream::Stream,
+        dec: &ffmpeg::codec::decoder::Video,
+    ) -> Self {
+        let sar = dec.aspect_ratio();
+        let sar = match (sar.numerator(), sar.denominator()) {
+            (n, d) if n > 0 && d > 0 => n as f64 / d as f64,
+            _ => 1.0,
+        };
+
+        let matrix = stream
+            .side_data()
+            .find(|sd| sd.kind() == SideDataType::DisplayMatrix)
+            .and_then(|sd| parse_display_matrix(sd.data()));
+        let (rotate_cw, flip_h, flip_v) = match matrix {
+            Some(m) => transform_from_matrix(&m),
+            None => {
+                let tag = stream
+                    .metadata()
+                    .get("rotate")
+                    .and_then(|r| r.parse::<f64>().ok());
+                (tag.map_or(0, normalize_degrees), false, false)
+            }
+        };
+
+        Orientation {
+            rotate_cw,
+            flip_h,
+            flip_v,
+            sar,
+        }
+    }
+
+    /// Stored frame size corrected to square pixels (before rotation).
+    pub fn display_dims(&self, w: u32, h: u32) -> (u32, u32) {
+        match self.sar {
+            s if s > 1.0 => (((w as f64) * s).round().max(1.0) as u32, h),
+            s if s < 1.0 => (w, ((h as f64) / s).round().max(1.0) as u32),
+            _ => (w, h),
+        }
+    }
+
+    /// Size as displayed: square pixels, then rotated.
+    pub fn upright_dims(&self, w: u32, h: u32) -> (u32, u32) {
+        let (w, h) = self.display_dims(w, h);
+        match self.rotate_cw {
+            90 | 270 => (h, w),
+            _ => (w, h),
+        }
+    }
+
+    /// Rotate/mirror a decoded frame upright.
+    pub fn apply(&self, img: DynamicImage) -> DynamicImage {
+        let img = match self.rotate_cw {
+            90 => img.rotate90(),
+            180 => img.rotate180(),
+            270 => img.rotate270(),
+            _ => img,
+        };
+        let img = if self.flip_h { img.fliph() } else { img };
+        if self.flip_v { img.flipv() } else { img }
+    }
+}
+
+/// 3x3 display matrix of 32-bit native-endian integers (16.16 fixed point, 2.30 last column).
+fn parse_display_matrix(data: &[u8]) -> Option<[i32; 9]> {
+    if data.len() < 36 {
+        return None;
+    }
+    let mut m = [0i32; 9];
+    for (v, bytes) in m.iter_mut().zip(data.chunks_exact(4)) {
+        *v = i32::from_ne_bytes(bytes.try_into().ok()?);
+    }
+    Some(m)
+}
+
+/// Same transforms as the ffmpeg CLI's autorotate (transpose/hflip/vflip per rotation).
+fn transform_from_matrix(m: &[i32; 9]) -> (u32, bool, bool) {
+    let fp = |v: i32| v as f64 / 65536.0;
+    let scale0 = fp(m[0]).hypot(fp(m[3]));
+    let scale1 = fp(m[1]).hypot(fp(m[4]));
+    if scale0 == 0.0 || scale1 == 0.0 {
+        return (0, false, false);
+    }
+    // Clockwise degrees, i.e. -av_display_rotation_get()
+    let theta = (fp(m[1]) / scale1).atan2(fp(m[0]) / scale0).to_degrees();
+
+    match normalize_degrees(theta) {
+        // transpose=cclock_flip is a clockwise rotation then horizontal flip
+        90 => (90, m[3] > 0, false),
+        180 => (0, m[0] < 0, m[4] < 0),
+        // transpose=clock_flip is a counter-clockwise rotation then horizontal flip
+        270 => (270, m[3] < 0, false),
+        _ => (0, false, m[4] < 0),
+    }
+}
+
+/// Round to the nearest multiple of 90 in [0, 360); other angles are ignored (0).
+fn normalize_degrees(deg: f64) -> u32 {
+    let deg = deg.round().rem_euclid(360.0);
+    match deg {
+        d if (d - 90.0).abs() < 1.0 => 90,
+        d if (d - 180.0).abs() < 1.0 => 180,
+        d if (d - 270.0).abs() < 1.0 => 270,
+        _ => 0,
+    }
+}
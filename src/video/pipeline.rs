@@ -5,6 +5,7 @@ use std::{
     sync::{Arc, Mutex},
 };
 
+use img_hash::ImageHash;
 use indicatif::ParallelProgressIterator;
 use rayon::prelude::*;
 
@@ -15,7 +16,7 @@ use crate::{cache, hashing, progress, types};
 pub fn run(
     cfg: types::AppConfig,
     cache: &mut types::CacheFile,
-) -> Result<Vec<types::PipelineResult>, AppError> {
+) -> Result<(Vec<types::PipelineResult>, Vec<types::FileError>), AppError> {
     // Initialise ffmpeg
     decode::init_ffmpeg()?;
 
@@ -25,52 +26,19 @@ pub fn run(
     // Shared cache
     let cache_arc = Arc::new(Mutex::new(std::mem::take(cache)));
 
-    // Parallelise Video Processing
-    let results: Vec<Result<types::PipelineResult, (PathBuf, AppError)>> = {
-        if cfg.parallelism > 0 {
-            let pool = rayon::ThreadPoolBuilder::new()
-                .num_threads(cfg.parallelism)
-                .build()?;
-            pool.install(|| {
-                cfg.media_paths
-                    .par_iter()
-                    .map(|p| {
-                        process_one_video(p.as_path(), &cfg, &cache_arc).map_err(|e| (p.clone(), e))
-                    })
-                    .progress_with(progress_bar.clone())
-                    .collect()
-            })
-        } else {
-            cfg.media_paths
-                .par_iter()
-                .map(|p| {
-                    process_one_video(p.as_path(), &cfg, &cache_arc).map_err(|e| (p.clone(), e))
-                })
-                .progress_with(progress_bar.clone())
-                .collect()
-        }
-    };
+    // Parallelise Video Processing on the caller's Rayon pool
+    let results: Vec<Result<types::PipelineResult, (PathBuf, AppError)>> = cfg
+        .media_paths
+        .par_iter()
+        .map(|p| process_one_video(p.as_path(), &cfg, &cache_arc).map_err(|e| (p.clone(), e)))
+        .progress_with(progress_bar.clone())
+        .collect();
 
     // Clear Progress
     progress_bar.finish_and_clear();
 
-    // Collect Successes and Failures
-    let mut oks: Vec<types::PipelineResult> = Vec::new();
-    let mut errs: Vec<(PathBuf, AppError)> = Vec::new();
-    for r in results {
-        match r {
-            Ok(ok) => oks.push(ok),
-            Err((p, e)) => errs.push((p, e)),
-        }
-    }
-
-    // Error Report
-    if !errs.is_empty() {
-        errs.iter().for_each(|(p, e)| {
-            eprintln!("warn: {} -> {}", p.display(), e);
-        });
-        eprintln!("note: {} file(s) failed", errs.len());
-    }
+    // Collect Successes and Failures (reported as they're split)
+    let (oks, errors) = progress::collect(results);
 
     // Return cache to caller
     let updated = Arc::into_inner(cache_arc)
@@ -79,7 +47,7 @@ pub fn run(
         .unwrap();
     *cache = updated;
 
-    Ok(oks)
+    Ok((oks, errors))
 }
 
 fn process_one_video(
@@ -91,58 +59,109 @@ fn process_one_video(
     let key = hashing::compute_blake3(path)?;
 
     // Cache Hit with matching params? Return Early - Single Thread (Read Lock)
-    if let Some(entry) = {
+    if let Some(entries) = {
         let cm = cache_arc.lock().unwrap();
-        cache::lookup(&cm, &key, cfg, /*is_video=*/ true)
+        cache::lookup(&cm, &key, cfg, types::MediaType::Video)
     } {
-        return Ok(types::PipelineResult {
-            path: path.to_path_buf(),
-            blake3: key,
-            perceptual_hash: entry.perceptual_hash.clone(),
-        });
+        return Ok(to_result(path, key, entries, cfg));
     }
 
-    // Decode, Sample, Hash Samples
-    let hasher = hashing::build_hasher(cfg.hash_alg, cfg.hash_w, cfg.hash_h);
-    let frame_hashes = decode::decode_sample_even_window_hash(
-        path,
-        cfg.sample_start,
-        cfg.sample_count,
-        cfg.sample_window,
-        &hasher,
-    )?;
-
-    // Aggregate to one hash
-    let video_hash = match cfg.aggregation {
-        types::Aggregation::Medoid => aggregate::aggregate_medoid(&frame_hashes),
-        types::Aggregation::Majority => aggregate::aggregate_majority_as_real(&frame_hashes),
+    // Failed in a previous run? Skip unless retrying (or given a longer timeout).
+    if let Some(failure) = {
+        let cm = cache_arc.lock().unwrap();
+        cache::lookup_failure(&cm, &key, cfg, types::MediaType::Video)
+    } {
+        return Err(AppError::PreviouslyFailed(failure.error));
     }
-    .ok_or_else(|| AppError::Video(VideoError::NoSamples))?;
-    let phash_b64 = video_hash.to_base64();
+
+    // Decode + Hash (failures are recorded in the cache)
+    let entries = match hash_video(path, cfg) {
+        Ok(entries) => entries,
+        Err(e) => {
+            let mut cm = cache_arc.lock().unwrap();
+            cache::record_failure(&mut cm, key, cfg, types::MediaType::Video, &e);
+            return Err(e);
+        }
+    };
 
     // Upsert - Single Thread (Write Lock)
     {
         let mut cm = cache_arc.lock().unwrap();
-        cache::upsert(
-            &mut cm,
-            key.clone(),
-            types::CacheEntry {
-                hash_alg: cfg.hash_alg,
-                hash_w: cfg.hash_w,
-                hash_h: cfg.hash_h,
-                perceptual_hash: phash_b64.clone(),
-                sample_start: Some(cfg.sample_start),
-                sample_count: Some(cfg.sample_count),
-                sample_window: Some(cfg.sample_window),
-                aggregation: Some(cfg.aggregation),
-            },
-        );
+        for entry in &entries {
+            cache::upsert(&mut cm, key.clone(), entry.clone());
+        }
     }
 
     // Return
-    Ok(types::PipelineResult {
+    Ok(to_result(path, key, entries, cfg))
+}
+
+/// `entries` are one per `--hash-alg`; the primary's frames are kept.
+fn to_result(
+    path: &Path,
+    key: String,
+    entries: Vec<types::CacheEntry>,
+    cfg: &types::AppConfig,
+) -> types::PipelineResult {
+    let primary = entries[0].clone();
+    let info = match (cfg.with_metadata, primary.media) {
+        (true, Some(media)) => types::FileInfo::stat(path, media).ok(),
+        _ => None,
+    };
+    types::PipelineResult {
         path: path.to_path_buf(),
         blake3: key,
-        perceptual_hash: phash_b64,
-    })
+        hashes: entries.into_iter().map(|e| e.perceptual_hash).collect(),
+        media_type: types::MediaType::Video,
+        frames: primary.frames,
+        audio_hash: primary.audio_hash,
+        meta: primary.meta,
+        color: None,
+        tiles: None,
+        info,
+    }
+}
+
+/// Decode, Sample, Hash Samples, then Aggregate to one hash.
+/// Returns one entry per `--hash-alg`, from a single decode.
+fn hash_video(path: &Path, cfg: &types::AppConfig) -> Result<Vec<types::CacheEntry>, AppError> {
+    let hashers = hashing::build_hashers(cfg);
+    let decoded = decode::decode_sample_hash(path, cfg, &hashers)?;
+    let audio_hash = decoded.audio_hash.map(|h| h.to_base64());
+
+    cfg.hash_algs
+        .iter()
+        .zip(decoded.frames)
+        .map(|(&alg, frame_hashes)| {
+            // Aggregate to one hash
+            let hashes: Vec<ImageHash> = frame_hashes.iter().map(|f| f.hash.clone()).collect();
+            let video_hash = match cfg.aggregation {
+                types::Aggregation::Medoid => aggregate::aggregate_medoid(&hashes),
+                types::Aggregation::Majority => aggregate::aggregate_majority_as_real(&hashes),
+            }
+            .ok_or_else(|| AppError::Video(VideoError::NoSamples))?;
+
+            // Keep the ordered frame hashes for sequence comparison
+            let frames: Vec<types::FrameHash> = frame_hashes
+                .iter()
+                .map(|f| types::FrameHash {
+                    pts_secs: f.pts_secs,
+                    hash: f.hash.to_base64(),
+                })
+                .collect();
+
+            Ok(types::CacheEntry {
+                params: types::HashParams::from_cfg(cfg, alg, types::MediaType::Video),
+                perceptual_hash: video_hash.to_base64(),
+                animated: false,
+                frames: Some(frames),
+                audio: cfg.audio,
+                audio_hash: audio_hash.clone(),
+                meta: Some(decoded.meta),
+                color: None,
+                tiles: None,
+                media: Some(decoded.media.clone()),
+            })
+        })
+        .collect()
 }
@@ -33,11 +33,81 @@ pub fn get_total_frames(stream: &ffmpeg::format::stream::Stream) -> Option<usize
     }
 }
 
+/// Duration in seconds: container duration, else the stream's.
+pub fn get_duration_secs(
+    ictx: &ffmpeg::format::context::Input,
+    stream: &ffmpeg::format::stream::Stream,
+) -> Option<f64> {
+    let ratio = |r: ffmpeg::Rational| r.numerator() as f64 / r.denominator() as f64;
+    let secs = match ictx.duration() {
+        d if d > 0 => d as f64 * ratio(ffmpeg::rescale::TIME_BASE),
+        _ if stream.duration() > 0 && stream.time_base().denominator() != 0 => {
+            stream.duration() as f64 * ratio(stream.time_base())
+        }
+        _ => return None,
+    };
+    (secs.is_finite() && secs > 0.0).then_some(secs)
+}
+
+/// Converts decoded frame timestamps to seconds from the start of the stream.
+#[derive(Debug, Clone, Copy)]
+pub struct Timeline {
+    time_base: f64,
+    start: i64,
+    fps: f64,
+}
+
+impl Timeline {
+    pub fn from_stream(stream: &ffmpeg::format::stream::Stream) -> Self {
+        let tb = stream.time_base();
+        let fps = stream.avg_frame_rate();
+        let ratio = |n: i32, d: i32| if d == 0 { 0.0 } else { n as f64 / d as f64 };
+        // AV_NOPTS_VALUE (i64::MIN) when unknown.
+        let start = stream.start_time();
+        Timeline {
+            time_base: ratio(tb.numerator(), tb.denominator()),
+            start: if start == i64::MIN { 0 } else { start },
+            fps: ratio(fps.numerator(), fps.denominator()),
+        }
+    }
+
+    /// Average frame rate, if known.
+    pub fn fps(&self) -> Option<f64> {
+        (self.fps > 0.0).then_some(self.fps)
+    }
+
+    /// Best-effort timestamp in seconds; falls back to `idx / avg_fps` when the frame has none.
+    pub fn secs(&self, ts: Option<i64>, idx: usize) -> f64 {
+        match ts {
+            Some(ts) if self.time_base > 0.0 => {
+                ((ts - self.start) as f64 * self.time_base).max(0.0)
+            }
+            _ if self.fps > 0.0 => idx as f64 / self.fps,
+            _ => 0.0,
+        }
+    }
+}
+
 #[derive(Debug, Clone, Copy)]
 pub struct SamplingPlan {
     pub start: usize,
     pub step: usize,
     pub take: usize, // How many samples we can take within the window (<= sample_count)
+    pub end: usize,  // Exclusive frame index where the window ends
+}
+
+impl SamplingPlan {
+    /// Even sampling: frame `idx` is one of the `step`-spaced samples (while any remain).
+    #[inline]
+    pub fn takes(&self, idx: usize, remaining: usize) -> bool {
+        remaining > 0 && idx >= self.start && (idx - self.start).is_multiple_of(self.step)
+    }
+
+    /// Frame `idx` is before the end of the window; decoding stops at the first one past it.
+    #[inline]
+    pub fn in_window(&self, idx: usize) -> bool {
+        idx < self.end
+    }
 }
 
 // Generate `step` to distribute samples evenly within `sample_window`
@@ -52,6 +122,7 @@ pub fn plan_even_sampling(
             start: 0,
             step: 1,
             take: 0,
+            end: 0,
         };
     }
 
@@ -82,5 +153,106 @@ pub fn plan_even_sampling(
     // How many we can actually take with that step inside the window.
     let take = (window_len / step).min(sample_count);
 
-    SamplingPlan { start, step, take }
+    SamplingPlan {
+        start,
+        step,
+        take,
+        end: start + window_len,
+    }
+}
+
+// Interval sampling: every frame in the window is a candidate; the decoder picks by timestamp.
+// Unknown frame counts sample to the end of the stream.
+pub fn plan_interval_sampling(
+    total_frames: usize,
+    sample_start: usize,
+    sample_window: usize,
+) -> SamplingPlan {
+    if total_frames == 0 {
+        return SamplingPlan {
+            start: sample_start,
+            step: 1,
+            take: usize::MAX,
+            end: match sample_window {
+                0 => usize::MAX,
+                w => sample_start.saturating_add(w),
+            },
+        };
+    }
+
+    let plan = plan_even_sampling(total_frames, usize::MAX, sample_start, sample_window);
+    SamplingPlan {
+        step: 1,
+        take: usize::MAX,
+        ..plan
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Frames an even plan samples from `total` decoded frames, with or without stopping at
+    /// the window end (the decoder stops there; before the window end existed it didn't).
+    fn picked(plan: &SamplingPlan, total: usize, stop_at_end: bool) -> Vec<usize> {
+        let mut remaining = plan.take;
+        let mut out = Vec::new();
+        for idx in 0..total {
+            if stop_at_end && !plan.in_window(idx) {
+                break;
+            }
+            if plan.takes(idx, remaining) {
+                out.push(idx);
+                remaining -= 1;
+                if remaining == 0 {
+                    break;
+                }
+            }
+        }
+        out
+    }
+
+    #[test]
+    fn window_end_does_not_change_even_sampling() {
+        for total in [1, 2, 9, 10, 11, 97, 240, 1000] {
+            for count in [1, 2, 5, 10, 16, 300] {
+                for start in [0, 3, 50, 5000] {
+                    for window in [0, 1, 7, 30, 1000] {
+                        let plan = plan_even_sampling(total, count, start, window);
+                        let expected: Vec<usize> =
+                            (0..plan.take).map(|k| plan.start + k * plan.step).collect();
+                        assert_eq!(picked(&plan, total, false), expected);
+                        assert_eq!(picked(&plan, total, true), expected);
+                        assert!(expected.iter().all(|&idx| plan.in_window(idx)));
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn even_sampling_spreads_over_the_window() {
+        let plan = plan_even_sampling(1000, 10, 100, 500);
+        assert_eq!(
+            (plan.start, plan.step, plan.take, plan.end),
+            (100, 50, 10, 600)
+        );
+        // Start past the end: half-way
+        let plan = plan_even_sampling(100, 4, 500, 0);
+        assert_eq!(
+            (plan.start, plan.step, plan.take, plan.end),
+            (50, 12, 4, 100)
+        );
+        assert_eq!(plan_even_sampling(0, 10, 0, 0).take, 0);
+    }
+
+    #[test]
+    fn interval_sampling_takes_every_frame_in_the_window() {
+        let plan = plan_interval_sampling(1000, 100, 300);
+        assert_eq!((plan.start, plan.step, plan.end), (100, 1, 400));
+        assert_eq!(plan.take, usize::MAX);
+        // Unknown frame count: to the end of the window, or of the stream
+        assert_eq!(plan_interval_sampling(0, 100, 300).end, 400);
+        assert_eq!(plan_interval_sampling(0, 100, 0).end, usize::MAX);
+    }
 }
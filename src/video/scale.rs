@@ -0,0 +1,60 @@
+//! Video ~ Frame downscaling: decoded frames go to small GRAY8 images in one sws pass.
+//!
+//! Only depends on ffmpeg and image, so `benches/scale.rs` measures these same functions.
+
+use ffmpeg::{format::Pixel, frame, software::scaling};
+use ffmpeg_next as ffmpeg;
+use img_hash::image::{DynamicImage, ImageBuffer, Luma};
+
+/// Intermediate frame size as a multiple of the hash size (long side).
+/// `img_hash` shrinks to the hash size anyway, so full-resolution frames are wasted work.
+const DOWNSCALE_FACTOR: u32 = 8;
+
+/// Scale the frame's long side to `max(hash_w, hash_h) * DOWNSCALE_FACTOR`, preserving aspect.
+/// Never upscales.
+pub fn scaled_dims(src_w: u32, src_h: u32, hash_w: u32, hash_h: u32) -> (u32, u32) {
+    let target = hash_w.max(hash_h).saturating_mul(DOWNSCALE_FACTOR).max(1);
+    let long = src_w.max(src_h);
+    if long <= target {
+        return (src_w.max(1), src_h.max(1));
+    }
+    let w = ((src_w as u64 * target as u64) / long as u64).max(1) as u32;
+    let h = ((src_h as u64 * target as u64) / long as u64).max(1) as u32;
+    (w, h)
+}
+
+/// Downscale + convert to GRAY8 in a single sws pass; all hash algorithms operate on luma.
+/// Built once per video and reused for every sampled frame.
+pub fn gray_scaler(
+    src_format: Pixel,
+    (src_w, src_h): (u32, u32),
+    (dst_w, dst_h): (u32, u32),
+) -> Result<scaling::Context, ffmpeg::Error> {
+    scaling::Context::get(
+        src_format,
+        src_w,
+        src_h,
+        Pixel::GRAY8,
+        dst_w,
+        dst_h,
+        scaling::Flags::FAST_BILINEAR,
+    )
+}
+
+/// Copy a GRAY8 frame (with stride) into an owned `DynamicImage` consumed by the hasher.
+pub fn gray_to_image(gray: &frame::Video) -> Option<DynamicImage> {
+    let (w, h) = (gray.width() as usize, gray.height() as usize);
+    let data = gray.data(0);
+    let stride = gray.stride(0);
+    let owned = if stride == w {
+        data[..w * h].to_vec()
+    } else {
+        let mut owned = Vec::with_capacity(w * h);
+        for row in data.chunks(stride).take(h) {
+            owned.extend_from_slice(&row[..w]);
+        }
+        owned
+    };
+    let buf: ImageBuffer<Luma<u8>, _> = ImageBuffer::from_raw(w as u32, h as u32, owned)?;
+    Some(DynamicImage::ImageLuma8(buf))
+}
@@ -0,0 +1,36 @@
+//! Video ~ Test fixtures: deterministic pseudo-random frame hashes and signals.
+
+use img_hash::ImageHash;
+
+/// 64-bit LCG (Knuth's MMIX constants); the same seed gives the same fixture on every run.
+pub(crate) struct Lcg(u64);
+
+impl Lcg {
+    pub(crate) fn new(seed: u64) -> Self {
+        Lcg(seed)
+    }
+
+    pub(crate) fn next_u64(&mut self) -> u64 {
+        self.0 = self
+            .0
+            .wrapping_mul(6364136223846793005)
+            .wrapping_add(1442695040888963407);
+        self.0
+    }
+
+    /// Uniform in `[0, 1)`.
+    pub(crate) fn next_f32(&mut self) -> f32 {
+        (self.next_u64() >> 40) as f32 / (1u64 << 24) as f32
+    }
+}
+
+/// `n` unrelated 256-bit hashes (pseudo-random, so any two are ~128 bits apart).
+pub(crate) fn hashes(seed: u64, n: usize) -> Vec<ImageHash> {
+    let mut rng = Lcg::new(seed);
+    (0..n)
+        .map(|_| {
+            let bytes: Vec<u8> = (0..32).map(|_| (rng.next_u64() >> 56) as u8).collect();
+            ImageHash::from_bytes(&bytes).expect("hash bytes")
+        })
+        .collect()
+}